@@ -0,0 +1,230 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied.  See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::collections::BinaryHeap;
+
+use crate::constants::Weight;
+use crate::constants::{add_weights, NodeId, INVALID_NODE, WEIGHT_MAX};
+use crate::heap_item::HeapItem;
+use crate::preparation_graph::PreparationGraph;
+use crate::shortest_path::ShortestPath;
+use crate::valid_flags::ValidFlags;
+
+const EARTH_RADIUS_METERS: f64 = 6_371_000.0;
+
+/// Calculates shortest paths using the A* algorithm, which does not require any preparation of
+/// the graph, but uses a heuristic to guide the search towards the target.
+pub struct AStar {
+    num_nodes: usize,
+    data: Vec<Data>,
+    valid_flags: ValidFlags,
+    heap: BinaryHeap<HeapItem>,
+}
+
+impl AStar {
+    pub fn new(num_nodes: usize) -> Self {
+        AStar {
+            num_nodes,
+            data: (0..num_nodes).map(|_i| Data::new()).collect(),
+            valid_flags: ValidFlags::new(num_nodes),
+            heap: BinaryHeap::new(),
+        }
+    }
+
+    /// Calculates the shortest path from `start` to `end`. `heuristic` must return a lower bound
+    /// of the weight of the shortest path from the given node to `end`, for example based on the
+    /// (haversine) distance between the node coordinates, see `haversine_distance()`. The heuristic
+    /// also needs to be consistent, i.e. for every edge from a to b the heuristic of a must not be
+    /// larger than the heuristic of b plus the edge weight, otherwise the returned path is not
+    /// necessarily the shortest.
+    pub fn calc_path<H>(
+        &mut self,
+        graph: &PreparationGraph,
+        start: NodeId,
+        end: NodeId,
+        heuristic: H,
+    ) -> Option<ShortestPath>
+    where
+        H: Fn(NodeId) -> Weight,
+    {
+        assert_eq!(
+            graph.get_num_nodes(),
+            self.num_nodes,
+            "given graph has invalid node count"
+        );
+        assert!(start < self.num_nodes, "invalid start node");
+        assert!(end < self.num_nodes, "invalid end node");
+        if start == end {
+            return Some(ShortestPath::singular(start));
+        }
+        self.heap.clear();
+        self.valid_flags.invalidate_all();
+        self.update_node(start, 0, INVALID_NODE);
+        self.heap.push(HeapItem::new(heuristic(start), start));
+
+        while let Some(curr) = self.heap.pop() {
+            if self.is_settled(curr.node_id) {
+                continue;
+            }
+            let curr_weight = self.data[curr.node_id].weight;
+            for out_edge in graph.get_out_edges(curr.node_id) {
+                let adj = out_edge.adj_node;
+                let weight = add_weights(curr_weight, out_edge.weight);
+                if weight < self.get_weight(adj) {
+                    self.update_node(adj, weight, curr.node_id);
+                    self.heap
+                        .push(HeapItem::new(add_weights(weight, heuristic(adj)), adj));
+                }
+            }
+            self.data[curr.node_id].settled = true;
+            if curr.node_id == end {
+                return Some(self.build_path(start, end));
+            }
+        }
+        None
+    }
+
+    fn build_path(&self, start: NodeId, end: NodeId) -> ShortestPath {
+        let mut result = Vec::new();
+        let mut node = end;
+        while self.data[node].parent != INVALID_NODE {
+            result.push(node);
+            node = self.data[node].parent;
+        }
+        result.push(start);
+        result.reverse();
+        ShortestPath::new(start, end, self.data[end].weight, result)
+    }
+
+    fn update_node(&mut self, node: NodeId, weight: Weight, parent: NodeId) {
+        self.valid_flags.set_valid(node);
+        self.data[node].settled = false;
+        self.data[node].weight = weight;
+        self.data[node].parent = parent;
+    }
+
+    fn is_settled(&self, node: NodeId) -> bool {
+        self.valid_flags.is_valid(node) && self.data[node].settled
+    }
+
+    fn get_weight(&self, node: NodeId) -> Weight {
+        if self.valid_flags.is_valid(node) {
+            self.data[node].weight
+        } else {
+            WEIGHT_MAX
+        }
+    }
+}
+
+/// Returns the great-circle distance in meters between two (latitude, longitude) coordinates
+/// given in degrees, using the haversine formula.
+pub fn haversine_distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (lat_a, lat_b) = (a.0.to_radians(), b.0.to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lon = (b.1 - a.1).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_METERS * h.sqrt().min(1.0).asin()
+}
+
+struct Data {
+    settled: bool,
+    weight: Weight,
+    parent: NodeId,
+}
+
+impl Data {
+    fn new() -> Self {
+        Data {
+            settled: false,
+            weight: WEIGHT_MAX,
+            parent: INVALID_NODE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::Rng;
+
+    use crate::dijkstra::Dijkstra;
+
+    use super::*;
+
+    #[test]
+    fn haversine() {
+        assert_eq!(0.0, haversine_distance((52.0, 8.0), (52.0, 8.0)));
+        // one degree of latitude is about 111km
+        let d = haversine_distance((52.0, 8.0), (53.0, 8.0));
+        assert!((d - 111_195.0).abs() < 1.0, "{}", d);
+        let d = haversine_distance((0.0, 0.0), (0.0, 180.0));
+        assert!((d - std::f64::consts::PI * EARTH_RADIUS_METERS).abs() < 1.0);
+    }
+
+    #[test]
+    fn simple_path() {
+        // 0 -> 1 -> 2
+        // |         |
+        // 3 ------> 4
+        let mut g = PreparationGraph::new(5);
+        g.add_edge(0, 1, 1);
+        g.add_edge(1, 2, 1);
+        g.add_edge(2, 4, 1);
+        g.add_edge(0, 3, 1);
+        g.add_edge(3, 4, 5);
+        let mut a_star = AStar::new(g.get_num_nodes());
+        let path = a_star.calc_path(&g, 0, 4, |_| 0).unwrap();
+        assert_eq!(&vec![0, 1, 2, 4], path.get_nodes());
+        assert_eq!(3, path.get_weight());
+        assert_eq!(None, a_star.calc_path(&g, 4, 0, |_| 0));
+        assert_eq!(
+            Some(ShortestPath::singular(2)),
+            a_star.calc_path(&g, 2, 2, |_| 0)
+        );
+    }
+
+    #[test]
+    fn same_weights_as_dijkstra() {
+        const NUM_NODES: usize = 100;
+        let mut rng: StdRng = rand::SeedableRng::seed_from_u64(1);
+        let coordinates: Vec<(f64, f64)> = (0..NUM_NODES)
+            .map(|_| (rng.gen_range(52.0, 52.1), rng.gen_range(8.0, 8.1)))
+            .collect();
+        let mut g = PreparationGraph::new(NUM_NODES);
+        for _ in 0..3 * NUM_NODES {
+            let from = rng.gen_range(0, NUM_NODES);
+            let to = rng.gen_range(0, NUM_NODES);
+            // the edge weight is never smaller than the distance, so the heuristic is admissible
+            let distance = haversine_distance(coordinates[from], coordinates[to]);
+            g.add_edge(from, to, distance.ceil() as Weight + rng.gen_range(0, 100));
+        }
+        let mut a_star = AStar::new(NUM_NODES);
+        let mut dijkstra = Dijkstra::new(NUM_NODES);
+        for _ in 0..100 {
+            let source = rng.gen_range(0, NUM_NODES);
+            let target = rng.gen_range(0, NUM_NODES);
+            let heuristic =
+                |node: NodeId| haversine_distance(coordinates[node], coordinates[target]) as Weight;
+            assert_eq!(
+                dijkstra.calc_path(&g, source, target),
+                a_star.calc_path(&g, source, target, heuristic)
+            );
+        }
+    }
+}
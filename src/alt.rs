@@ -0,0 +1,223 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied.  See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::cmp;
+
+use crate::a_star::AStar;
+use crate::constants::{NodeId, Weight, WEIGHT_MAX};
+use crate::dijkstra::Dijkstra;
+use crate::preparation_graph::PreparationGraph;
+use crate::shortest_path::ShortestPath;
+
+/// Calculates shortest paths using A* with lower bounds derived from the distances to and from a
+/// few landmark nodes via the triangle inequality (ALT). The preprocessing is much cheaper than
+/// building a `FastGraph`, but the queries are also considerably slower.
+pub struct Alt {
+    landmarks: Vec<NodeId>,
+    // weights from each landmark to every node, WEIGHT_MAX if the node cannot be reached
+    from_landmarks: Vec<Vec<Weight>>,
+    // weights from every node to each landmark, WEIGHT_MAX if the landmark cannot be reached
+    to_landmarks: Vec<Vec<Weight>>,
+    a_star: AStar,
+}
+
+impl Alt {
+    /// Selects (up to) `num_landmarks` landmarks and calculates the distances between them and
+    /// all nodes of the graph. The landmarks are chosen one after another such that each new
+    /// landmark is as far away as possible from the ones selected before.
+    pub fn preprocess(graph: &PreparationGraph, num_landmarks: usize) -> Self {
+        let num_nodes = graph.get_num_nodes();
+        let reverse_graph = Alt::reverse(graph);
+        let mut dijkstra = Dijkstra::new(num_nodes);
+        // we need separate instances, because Dijkstra re-uses the tree for the same start node
+        let mut reverse_dijkstra = Dijkstra::new(num_nodes);
+        let mut landmarks = Vec::new();
+        let mut from_landmarks = Vec::new();
+        let mut to_landmarks = Vec::new();
+        // the smallest weight between each node and any of the landmarks (in either direction)
+        let mut min_weights = Alt::to_weights(&dijkstra.calc_tree(graph, 0));
+        while landmarks.len() < cmp::min(num_landmarks, num_nodes) {
+            let landmark = Alt::farthest_node(&min_weights);
+            if landmarks.contains(&landmark) {
+                break;
+            }
+            let from_landmark = Alt::to_weights(&dijkstra.calc_tree(graph, landmark));
+            let to_landmark =
+                Alt::to_weights(&reverse_dijkstra.calc_tree(&reverse_graph, landmark));
+            if landmarks.is_empty() {
+                min_weights = vec![WEIGHT_MAX; num_nodes];
+            }
+            for node in 0..num_nodes {
+                min_weights[node] = cmp::min(
+                    min_weights[node],
+                    cmp::min(from_landmark[node], to_landmark[node]),
+                );
+            }
+            landmarks.push(landmark);
+            from_landmarks.push(from_landmark);
+            to_landmarks.push(to_landmark);
+        }
+        Alt {
+            landmarks,
+            from_landmarks,
+            to_landmarks,
+            a_star: AStar::new(num_nodes),
+        }
+    }
+
+    pub fn get_landmarks(&self) -> &[NodeId] {
+        &self.landmarks
+    }
+
+    /// Calculates the shortest path from `start` to `end`. The graph must be the same that was
+    /// used for the preprocessing.
+    pub fn calc_path(
+        &mut self,
+        graph: &PreparationGraph,
+        start: NodeId,
+        end: NodeId,
+    ) -> Option<ShortestPath> {
+        let from_landmarks = &self.from_landmarks;
+        let to_landmarks = &self.to_landmarks;
+        self.a_star.calc_path(graph, start, end, |node| {
+            Alt::calc_lower_bound(from_landmarks, to_landmarks, node, end)
+        })
+    }
+
+    /// Returns a lower bound for the weight of the shortest path from `node` to `end`, or
+    /// WEIGHT_MAX if `end` cannot be reached from `node` at all.
+    fn calc_lower_bound(
+        from_landmarks: &[Vec<Weight>],
+        to_landmarks: &[Vec<Weight>],
+        node: NodeId,
+        end: NodeId,
+    ) -> Weight {
+        let mut result = 0;
+        for (from_landmark, to_landmark) in from_landmarks.iter().zip(to_landmarks.iter()) {
+            // d(l, end) <= d(l, node) + d(node, end)
+            let (l_node, l_end) = (from_landmark[node], from_landmark[end]);
+            if l_node != WEIGHT_MAX {
+                if l_end == WEIGHT_MAX {
+                    return WEIGHT_MAX;
+                }
+                result = cmp::max(result, l_end.saturating_sub(l_node));
+            }
+            // d(node, l) <= d(node, end) + d(end, l)
+            let (node_l, end_l) = (to_landmark[node], to_landmark[end]);
+            if end_l != WEIGHT_MAX {
+                if node_l == WEIGHT_MAX {
+                    return WEIGHT_MAX;
+                }
+                result = cmp::max(result, node_l.saturating_sub(end_l));
+            }
+        }
+        result
+    }
+
+    fn reverse(graph: &PreparationGraph) -> PreparationGraph {
+        let mut result = PreparationGraph::new(graph.get_num_nodes());
+        for node in 0..graph.get_num_nodes() {
+            for arc in graph.get_out_edges(node) {
+                result.add_edge(arc.adj_node, node, arc.weight);
+            }
+        }
+        result
+    }
+
+    fn to_weights(tree: &[Option<Weight>]) -> Vec<Weight> {
+        tree.iter().map(|w| w.unwrap_or(WEIGHT_MAX)).collect()
+    }
+
+    fn farthest_node(min_weights: &[Weight]) -> NodeId {
+        let mut result = 0;
+        for (node, &weight) in min_weights.iter().enumerate() {
+            if weight > min_weights[result] {
+                result = node;
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::Rng;
+
+    use super::*;
+
+    #[test]
+    fn selects_farthest_landmarks() {
+        // 0 -> 1 -> 2 -> 3 -> 4
+        let mut g = PreparationGraph::new(5);
+        for i in 0..4 {
+            g.add_edge(i, i + 1, 1);
+        }
+        let alt = Alt::preprocess(&g, 2);
+        assert_eq!(&[4, 0], alt.get_landmarks());
+        // we cannot select more landmarks than there are nodes
+        let alt = Alt::preprocess(&g, 10);
+        assert_eq!(5, alt.get_landmarks().len());
+    }
+
+    #[test]
+    fn simple_path() {
+        // 0 -> 1 -> 2
+        // |         |
+        // 3 ------> 4
+        let mut g = PreparationGraph::new(5);
+        g.add_edge(0, 1, 1);
+        g.add_edge(1, 2, 1);
+        g.add_edge(2, 4, 1);
+        g.add_edge(0, 3, 1);
+        g.add_edge(3, 4, 5);
+        let mut alt = Alt::preprocess(&g, 2);
+        let path = alt.calc_path(&g, 0, 4).unwrap();
+        assert_eq!(&vec![0, 1, 2, 4], path.get_nodes());
+        assert_eq!(3, path.get_weight());
+        assert_eq!(None, alt.calc_path(&g, 4, 0));
+        assert_eq!(None, alt.calc_path(&g, 1, 3));
+    }
+
+    #[test]
+    fn same_weights_as_dijkstra() {
+        const NUM_NODES: usize = 100;
+        let mut rng: StdRng = rand::SeedableRng::seed_from_u64(3);
+        let mut g = PreparationGraph::new(NUM_NODES);
+        for _ in 0..2 * NUM_NODES {
+            let from = rng.gen_range(0, NUM_NODES);
+            let to = rng.gen_range(0, NUM_NODES);
+            if from != to {
+                g.add_edge(from, to, rng.gen_range(0, 100));
+            }
+        }
+        let mut alt = Alt::preprocess(&g, 4);
+        let mut dijkstra = Dijkstra::new(NUM_NODES);
+        for _ in 0..100 {
+            let source = rng.gen_range(0, NUM_NODES);
+            let target = rng.gen_range(0, NUM_NODES);
+            assert_eq!(
+                dijkstra
+                    .calc_path(&g, source, target)
+                    .map(|p| p.get_weight()),
+                alt.calc_path(&g, source, target).map(|p| p.get_weight())
+            );
+        }
+    }
+}
@@ -0,0 +1,178 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied.  See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::constants::NodeId;
+use crate::fast_graph::FastGraph;
+use crate::path_calculator::PathCalculator;
+use crate::shortest_path::ShortestPath;
+
+/// Wraps a `PathCalculator` and keeps the results of the `capacity` most recently used
+/// `(start, end)` queries, which is useful if the same queries are run over and over again.
+/// The cache is cleared automatically when `calc_path()` is called with a different graph, or
+/// with a graph that was modified since, e.g. by `FastGraph::increase_edge_weight()`.
+pub struct CachedCalculator {
+    calc: PathCalculator,
+    capacity: usize,
+    // the generation of the graph the cached results belong to
+    graph_generation: Option<usize>,
+    // maps each query to its result and the time it was last used
+    entries: HashMap<(NodeId, NodeId), (Option<ShortestPath>, u64)>,
+    // maps the time an entry was last used to its query, the first entry is evicted first
+    last_used: BTreeMap<u64, (NodeId, NodeId)>,
+    time: u64,
+}
+
+impl CachedCalculator {
+    pub fn new(calc: PathCalculator, capacity: usize) -> Self {
+        CachedCalculator {
+            calc,
+            capacity,
+            graph_generation: None,
+            entries: HashMap::with_capacity(capacity),
+            last_used: BTreeMap::new(),
+            time: 0,
+        }
+    }
+
+    /// Like `PathCalculator::calc_path()`, but returns the cached result if the same query was
+    /// run recently.
+    pub fn calc_path(
+        &mut self,
+        graph: &FastGraph,
+        start: NodeId,
+        end: NodeId,
+    ) -> Option<ShortestPath> {
+        if self.graph_generation != Some(graph.get_generation()) {
+            self.clear();
+            self.graph_generation = Some(graph.get_generation());
+        }
+        self.time += 1;
+        if let Some((path, time)) = self.entries.get_mut(&(start, end)) {
+            self.last_used.remove(time);
+            self.last_used.insert(self.time, (start, end));
+            *time = self.time;
+            return path.clone();
+        }
+        let path = self.calc.calc_path(graph, start, end);
+        if self.capacity == 0 {
+            return path;
+        }
+        if self.entries.len() == self.capacity {
+            let (&time, &oldest) = self.last_used.iter().next().unwrap();
+            self.last_used.remove(&time);
+            self.entries.remove(&oldest);
+        }
+        self.entries.insert((start, end), (path.clone(), self.time));
+        self.last_used.insert(self.time, (start, end));
+        path
+    }
+
+    /// Removes all cached results
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.last_used.clear();
+    }
+
+    /// Returns the number of currently cached results
+    pub fn get_num_cached(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn into_inner(self) -> PathCalculator {
+        self.calc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input_graph::InputGraph;
+    use crate::prepare;
+
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        // 0 -> 1 -> 2 -> 3
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 1);
+        g.add_edge(1, 2, 2);
+        g.add_edge(2, 3, 3);
+        g.freeze();
+        let fast_graph = prepare(&g);
+        let mut calc = CachedCalculator::new(PathCalculator::new(4), 2);
+        assert_eq!(6, calc.calc_path(&fast_graph, 0, 3).unwrap().get_weight());
+        assert_eq!(5, calc.calc_path(&fast_graph, 1, 3).unwrap().get_weight());
+        // using 0->3 again makes 1->3 the least recently used entry
+        assert_eq!(6, calc.calc_path(&fast_graph, 0, 3).unwrap().get_weight());
+        assert!(calc.calc_path(&fast_graph, 3, 0).is_none());
+        assert_eq!(2, calc.get_num_cached());
+        assert!(calc.entries.contains_key(&(0, 3)));
+        assert!(calc.entries.contains_key(&(3, 0)));
+        assert!(!calc.entries.contains_key(&(1, 3)));
+        calc.clear();
+        assert_eq!(0, calc.get_num_cached());
+    }
+
+    #[test]
+    fn cleared_for_other_graph() {
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 1);
+        g.freeze();
+        let fast_graph = prepare(&g);
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 7);
+        g.add_edge(1, 0, 7);
+        g.freeze();
+        let other_graph = prepare(&g);
+        let mut calc = CachedCalculator::new(PathCalculator::new(2), 10);
+        assert_eq!(1, calc.calc_path(&fast_graph, 0, 1).unwrap().get_weight());
+        assert_eq!(7, calc.calc_path(&other_graph, 0, 1).unwrap().get_weight());
+        assert_eq!(1, calc.get_num_cached());
+    }
+
+    #[test]
+    fn cleared_for_replaced_graph() {
+        // the new graph has the same shape and probably ends up at the same address
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 1);
+        g.freeze();
+        let mut fast_graph = prepare(&g);
+        let mut calc = CachedCalculator::new(PathCalculator::new(2), 10);
+        assert_eq!(1, calc.calc_path(&fast_graph, 0, 1).unwrap().get_weight());
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 7);
+        g.freeze();
+        fast_graph = prepare(&g);
+        assert_eq!(7, calc.calc_path(&fast_graph, 0, 1).unwrap().get_weight());
+    }
+
+    #[test]
+    fn cleared_for_modified_graph() {
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 1);
+        g.freeze();
+        let mut fast_graph = prepare(&g);
+        let mut calc = CachedCalculator::new(PathCalculator::new(2), 10);
+        assert_eq!(1, calc.calc_path(&fast_graph, 0, 1).unwrap().get_weight());
+        fast_graph.increase_edge_weight((0, true), 7);
+        assert_eq!(7, calc.calc_path(&fast_graph, 0, 1).unwrap().get_weight());
+    }
+}
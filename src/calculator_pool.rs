@@ -0,0 +1,147 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied.  See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::ops::{Deref, DerefMut};
+
+use crossbeam_queue::ArrayQueue;
+
+use crate::fast_graph::FastGraph;
+use crate::path_calculator::PathCalculator;
+
+/// A pool of path calculators that can be shared between threads, so each thread can check out
+/// its own calculator to run queries on the same `FastGraph`. A calculator is returned to the
+/// pool when the `PooledCalculator` is dropped.
+pub struct CalculatorPool {
+    num_nodes: usize,
+    calculators: ArrayQueue<PathCalculator>,
+}
+
+impl CalculatorPool {
+    /// Creates a pool that keeps up to `size` calculators for the given graph. The calculators
+    /// are created lazily, and if more than `size` calculators are checked out at the same time
+    /// the additional ones are discarded once they are dropped.
+    pub fn new(graph: &FastGraph, size: usize) -> Self {
+        CalculatorPool {
+            num_nodes: graph.get_num_nodes(),
+            calculators: ArrayQueue::new(size.max(1)),
+        }
+    }
+
+    pub fn get(&self) -> PooledCalculator<'_> {
+        let calculator = self
+            .calculators
+            .pop()
+            .unwrap_or_else(|| PathCalculator::new(self.num_nodes));
+        PooledCalculator {
+            pool: self,
+            calculator: Some(calculator),
+        }
+    }
+
+    /// Returns the number of calculators that are currently available in the pool
+    pub fn get_num_available(&self) -> usize {
+        self.calculators.len()
+    }
+}
+
+pub struct PooledCalculator<'a> {
+    pool: &'a CalculatorPool,
+    calculator: Option<PathCalculator>,
+}
+
+impl Deref for PooledCalculator<'_> {
+    type Target = PathCalculator;
+
+    fn deref(&self) -> &PathCalculator {
+        self.calculator.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledCalculator<'_> {
+    fn deref_mut(&mut self) -> &mut PathCalculator {
+        self.calculator.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledCalculator<'_> {
+    fn drop(&mut self) {
+        if let Some(calculator) = self.calculator.take() {
+            // if the pool is full already the calculator is simply dropped
+            let _ = self.pool.calculators.push(calculator);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::input_graph::InputGraph;
+    use crate::{calc_weight, prepare};
+
+    use super::*;
+
+    #[test]
+    fn check_out_and_return() {
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 3);
+        g.add_edge(1, 2, 4);
+        g.freeze();
+        let fast_graph = prepare(&g);
+        let pool = CalculatorPool::new(&fast_graph, 2);
+        assert_eq!(0, pool.get_num_available());
+        {
+            let mut a = pool.get();
+            let mut b = pool.get();
+            let mut c = pool.get();
+            assert_eq!(Some(7), a.calc_weight(&fast_graph, 0, 2));
+            assert_eq!(Some(4), b.calc_weight(&fast_graph, 1, 2));
+            assert_eq!(None, c.calc_weight(&fast_graph, 2, 0));
+        }
+        assert_eq!(2, pool.get_num_available());
+        let _a = pool.get();
+        assert_eq!(1, pool.get_num_available());
+    }
+
+    #[test]
+    fn queries_from_multiple_threads() {
+        let mut g = InputGraph::new();
+        for i in 0..20 {
+            g.add_edge_bidir(i, i + 1, i + 1);
+        }
+        g.freeze();
+        let fast_graph = prepare(&g);
+        let pool = CalculatorPool::new(&fast_graph, 4);
+        thread::scope(|scope| {
+            for t in 0..4 {
+                let (pool, fast_graph) = (&pool, &fast_graph);
+                scope.spawn(move || {
+                    for target in 0..21 {
+                        let mut calculator = pool.get();
+                        assert_eq!(
+                            calc_weight(fast_graph, t, target),
+                            calculator.calc_weight(fast_graph, t, target)
+                        );
+                    }
+                });
+            }
+        });
+        assert!(pool.get_num_available() <= 4);
+    }
+}
@@ -21,7 +21,28 @@ pub type NodeId = usize;
 pub type EdgeId = usize;
 pub type Weight = usize;
 
-pub const INVALID_NODE: NodeId = std::usize::MAX;
-pub const INVALID_EDGE: EdgeId = std::usize::MAX;
-pub const WEIGHT_MAX: Weight = std::usize::MAX;
+pub const INVALID_NODE: NodeId = usize::MAX;
+pub const INVALID_EDGE: EdgeId = usize::MAX;
+pub const WEIGHT_MAX: Weight = usize::MAX;
 pub const WEIGHT_ZERO: Weight = 0;
+
+/// Adds two weights. The sum saturates at `WEIGHT_MAX` instead of overflowing, so a path via an
+/// unreachable node (with weight `WEIGHT_MAX`) is never shorter than any other path. All
+/// searches use this to combine weights.
+#[inline]
+pub fn add_weights(a: Weight, b: Weight) -> Weight {
+    a.saturating_add(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_weights_saturates() {
+        assert_eq!(5, add_weights(2, 3));
+        assert_eq!(WEIGHT_MAX, add_weights(WEIGHT_MAX, 1));
+        assert_eq!(WEIGHT_MAX, add_weights(WEIGHT_MAX - 1, 2));
+        assert_eq!(WEIGHT_MAX, add_weights(WEIGHT_MAX, WEIGHT_ZERO));
+    }
+}
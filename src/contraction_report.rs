@@ -0,0 +1,178 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied.  See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use crate::constants::NodeId;
+use crate::constants::Weight;
+use crate::fast_graph::FastGraph;
+use crate::shortest_path::ShortestPath;
+
+/// The result of a single witness search run while contracting `center_node`: the search
+/// looked for a path from `from` to `to` that avoids `center_node` and is not longer than
+/// `weight`, which is the weight of the path via `center_node`. If no such witness was found
+/// a shortcut was added.
+#[derive(Debug)]
+pub struct WitnessSearch {
+    pub from: NodeId,
+    pub center_node: NodeId,
+    pub to: NodeId,
+    pub weight: Weight,
+    pub witness: Option<ShortestPath>,
+}
+
+impl WitnessSearch {
+    pub fn added_shortcut(&self) -> bool {
+        self.witness.is_none()
+    }
+}
+
+/// The witness searches run while contracting a single node, see
+/// `ContractionReport::calc_node_stats()`
+#[derive(Debug, PartialEq)]
+pub struct NodeContractionStats {
+    pub node: NodeId,
+    pub rank: usize,
+    pub num_witness_searches: usize,
+    pub num_shortcuts: usize,
+}
+
+/// Records all witness searches run while contracting the nodes during the preparation. This is
+/// meant for debugging only, for example to find out why a certain shortcut was (not) added.
+#[derive(Debug, Default)]
+pub struct ContractionReport {
+    witness_searches: Vec<WitnessSearch>,
+}
+
+impl ContractionReport {
+    pub fn new() -> Self {
+        ContractionReport {
+            witness_searches: vec![],
+        }
+    }
+
+    pub fn add(&mut self, witness_search: WitnessSearch) {
+        self.witness_searches.push(witness_search);
+    }
+
+    /// All witness searches in the order they were run
+    pub fn get_witness_searches(&self) -> &[WitnessSearch] {
+        &self.witness_searches
+    }
+
+    /// The witness search that was run for the path `from -> center_node -> to`, if any
+    pub fn get_witness_search(
+        &self,
+        from: NodeId,
+        center_node: NodeId,
+        to: NodeId,
+    ) -> Option<&WitnessSearch> {
+        self.witness_searches
+            .iter()
+            .find(|s| s.from == from && s.center_node == center_node && s.to == to)
+    }
+
+    /// Summarizes the witness searches for each node of the given graph, which must be the one
+    /// this report was created for. The result is indexed by node id. Nodes with many shortcuts
+    /// are usually highly connected hubs that slow down both the preparation and the queries.
+    pub fn calc_node_stats(&self, fast_graph: &FastGraph) -> Vec<NodeContractionStats> {
+        let mut result: Vec<NodeContractionStats> = (0..fast_graph.get_num_nodes())
+            .map(|node| NodeContractionStats {
+                node,
+                rank: fast_graph.get_rank(node),
+                num_witness_searches: 0,
+                num_shortcuts: 0,
+            })
+            .collect();
+        for search in &self.witness_searches {
+            let stats = &mut result[search.center_node];
+            stats.num_witness_searches += 1;
+            if search.added_shortcut() {
+                stats.num_shortcuts += 1;
+            }
+        }
+        result
+    }
+
+    /// The number of witness searches that did not find a witness, i.e. the number of added
+    /// shortcuts
+    pub fn get_num_shortcuts(&self) -> usize {
+        self.witness_searches
+            .iter()
+            .filter(|s| s.added_shortcut())
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input_graph::InputGraph;
+    use crate::prepare_with_report;
+
+    #[test]
+    fn witness_searches() {
+        // 0 - 1 - 2
+        //  \- 3 -/
+        let mut g = InputGraph::new();
+        g.add_edge_bidir(0, 1, 1);
+        g.add_edge_bidir(1, 2, 1);
+        g.add_edge_bidir(0, 3, 1);
+        g.add_edge_bidir(3, 2, 5);
+        g.freeze();
+        let (fast_graph, report) = prepare_with_report(&g);
+        assert_eq!(vec![2, 1, 3, 0], fast_graph.get_node_ordering());
+        assert_eq!(6, report.get_witness_searches().len());
+        assert_eq!(0, report.get_num_shortcuts());
+        let search = report.get_witness_search(1, 2, 3).unwrap();
+        assert_eq!(6, search.weight);
+        let witness = search.witness.as_ref().unwrap();
+        assert_eq!(2, witness.get_weight());
+        assert_eq!(&vec![1, 0, 3], witness.get_nodes());
+        assert!(report.get_witness_search(1, 3, 2).is_none());
+    }
+
+    #[test]
+    fn node_stats() {
+        // 0 - 1 - 2
+        // |       |
+        // 5 - 4 - 3
+        let mut g = InputGraph::new();
+        for node in 0..6 {
+            g.add_edge_bidir(node, (node + 1) % 6, 1);
+        }
+        g.freeze();
+        let (fast_graph, report) = prepare_with_report(&g);
+        let stats = report.calc_node_stats(&fast_graph);
+        assert_eq!(6, stats.len());
+        for (node, s) in stats.iter().enumerate() {
+            assert_eq!(node, s.node);
+            assert_eq!(fast_graph.get_rank(node), s.rank);
+        }
+        assert_eq!(
+            report.get_witness_searches().len(),
+            stats.iter().map(|s| s.num_witness_searches).sum::<usize>()
+        );
+        assert_eq!(
+            report.get_num_shortcuts(),
+            stats.iter().map(|s| s.num_shortcuts).sum::<usize>()
+        );
+        // node 0 is contracted first and needs the shortcuts 1->5 and 5->1
+        assert_eq!(0, stats[0].rank);
+        assert_eq!(4, stats[0].num_witness_searches);
+        assert_eq!(2, stats[0].num_shortcuts);
+    }
+}
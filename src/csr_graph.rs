@@ -0,0 +1,201 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied.  See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::collections::BinaryHeap;
+use std::convert::TryFrom;
+
+use crate::constants::{add_weights, NodeId, Weight, WEIGHT_MAX};
+use crate::fast_graph::{FastGraph, FastGraphEdge};
+use crate::heap_item::HeapItem;
+
+/// A copy of the (upward) edges of a `FastGraph` stored in plain `u32` arrays using the
+/// compressed sparse row format, e.g. to be able to map the graph into memory directly. As for
+/// `FastGraph` the first edge ids are indexed by the node ranks, but the adjacent nodes are node
+/// ids. The shortcut information is not included, so only path weights can be calculated.
+#[derive(Debug, PartialEq)]
+pub struct CsrGraph {
+    pub ranks: Vec<u32>,
+    pub fwd: CsrEdges,
+    pub bwd: CsrEdges,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CsrEdges {
+    pub first_edge_ids: Vec<u32>,
+    pub adj_nodes: Vec<u32>,
+    pub weights: Vec<u32>,
+}
+
+impl CsrGraph {
+    /// Converts the given graph, or returns an error if any of the node ids, edge ids or weights
+    /// do not fit into a `u32`.
+    pub fn from_fast_graph(graph: &FastGraph) -> Result<Self, String> {
+        Ok(CsrGraph {
+            ranks: convert(&graph.ranks, "rank")?,
+            fwd: CsrEdges::new(&graph.first_edge_ids_fwd, &graph.edges_fwd)?,
+            bwd: CsrEdges::new(&graph.first_edge_ids_bwd, &graph.edges_bwd)?,
+        })
+    }
+
+    pub fn get_num_nodes(&self) -> usize {
+        self.ranks.len()
+    }
+
+    /// Calculates the weight of the shortest path from `start` to `end`, or returns `None` if
+    /// there is no such path. Unlike `PathCalculator` this allocates the search data for every
+    /// query.
+    pub fn calc_weight(&self, start: NodeId, end: NodeId) -> Option<Weight> {
+        let num_nodes = self.get_num_nodes();
+        assert!(start < num_nodes, "invalid start node");
+        assert!(end < num_nodes, "invalid end node");
+        let mut weights_fwd = vec![WEIGHT_MAX; num_nodes];
+        let mut weights_bwd = vec![WEIGHT_MAX; num_nodes];
+        let mut heap_fwd = BinaryHeap::new();
+        let mut heap_bwd = BinaryHeap::new();
+        weights_fwd[start] = 0;
+        weights_bwd[end] = 0;
+        heap_fwd.push(HeapItem::new(0, start));
+        heap_bwd.push(HeapItem::new(0, end));
+        let mut best_weight = WEIGHT_MAX;
+        loop {
+            let top_fwd = heap_fwd.peek().map_or(WEIGHT_MAX, |item| item.weight);
+            let top_bwd = heap_bwd.peek().map_or(WEIGHT_MAX, |item| item.weight);
+            if top_fwd.min(top_bwd) >= best_weight {
+                break;
+            }
+            if top_fwd <= top_bwd {
+                let curr = heap_fwd.pop().unwrap();
+                self.fwd.settle(
+                    curr,
+                    &self.ranks,
+                    &mut weights_fwd,
+                    &weights_bwd,
+                    &mut heap_fwd,
+                    &mut best_weight,
+                );
+            } else {
+                let curr = heap_bwd.pop().unwrap();
+                self.bwd.settle(
+                    curr,
+                    &self.ranks,
+                    &mut weights_bwd,
+                    &weights_fwd,
+                    &mut heap_bwd,
+                    &mut best_weight,
+                );
+            }
+        }
+        if best_weight == WEIGHT_MAX {
+            None
+        } else {
+            Some(best_weight)
+        }
+    }
+}
+
+impl CsrEdges {
+    fn new(first_edge_ids: &[usize], edges: &[FastGraphEdge]) -> Result<Self, String> {
+        let adj_nodes: Vec<NodeId> = edges.iter().map(|e| e.adj_node).collect();
+        let weights: Vec<Weight> = edges.iter().map(|e| e.weight).collect();
+        Ok(CsrEdges {
+            first_edge_ids: convert(first_edge_ids, "edge id")?,
+            adj_nodes: convert(&adj_nodes, "node id")?,
+            weights: convert(&weights, "weight")?,
+        })
+    }
+
+    fn settle(
+        &self,
+        curr: HeapItem,
+        ranks: &[u32],
+        weights: &mut [Weight],
+        other_weights: &[Weight],
+        heap: &mut BinaryHeap<HeapItem>,
+        best_weight: &mut Weight,
+    ) {
+        if curr.weight > weights[curr.node_id] {
+            // there is a better heap item for this node already
+            return;
+        }
+        if other_weights[curr.node_id] != WEIGHT_MAX {
+            *best_weight =
+                (*best_weight).min(add_weights(curr.weight, other_weights[curr.node_id]));
+        }
+        let rank = ranks[curr.node_id] as usize;
+        let begin = self.first_edge_ids[rank] as usize;
+        let end = self.first_edge_ids[rank + 1] as usize;
+        for edge_id in begin..end {
+            let adj = self.adj_nodes[edge_id] as NodeId;
+            let weight = add_weights(curr.weight, self.weights[edge_id] as Weight);
+            if weight < weights[adj] {
+                weights[adj] = weight;
+                heap.push(HeapItem::new(weight, adj));
+            }
+        }
+    }
+}
+
+fn convert(values: &[usize], name: &str) -> Result<Vec<u32>, String> {
+    values
+        .iter()
+        .map(|&v| u32::try_from(v).map_err(|_| format!("{} {} does not fit into u32", name, v)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::Rng;
+
+    use crate::input_graph::InputGraph;
+    use crate::{calc_weight, prepare};
+
+    use super::*;
+
+    #[test]
+    fn same_weights_as_fast_graph() {
+        let mut rng: StdRng = rand::SeedableRng::seed_from_u64(7);
+        let input_graph = InputGraph::random(&mut rng, 100, 2.0);
+        let fast_graph = prepare(&input_graph);
+        let csr_graph = fast_graph.to_csr().unwrap();
+        assert_eq!(100, csr_graph.get_num_nodes());
+        for _ in 0..100 {
+            let source = rng.gen_range(0, 100);
+            let target = rng.gen_range(0, 100);
+            assert_eq!(
+                calc_weight(&fast_graph, source, target),
+                csr_graph.calc_weight(source, target)
+            );
+        }
+    }
+
+    #[test]
+    fn weight_too_large() {
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, u32::MAX as Weight + 1);
+        g.freeze();
+        assert_eq!(
+            Err(format!(
+                "weight {} does not fit into u32",
+                u32::MAX as Weight + 1
+            )),
+            prepare(&g).to_csr()
+        );
+    }
+}
@@ -0,0 +1,118 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied.  See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::constants::{NodeId, Weight, WEIGHT_ZERO};
+use crate::fast_graph::FastGraph;
+use crate::path_calculator::PathCalculator;
+
+/// Returns the largest weight of the shortest paths from `node` to all nodes that can be reached
+/// from it, see `PathCalculator::calc_tree()`. Nodes that cannot be reached are ignored, so the
+/// eccentricity of a node without out-edges is zero.
+pub fn node_eccentricity(graph: &FastGraph, node: NodeId) -> Weight {
+    PathCalculator::new(graph.get_num_nodes())
+        .calc_tree(graph, node)
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap_or(WEIGHT_ZERO)
+}
+
+/// Estimates the diameter of the graph, i.e. the largest weight of all shortest paths, by running
+/// `num_samples` queries between random nodes and returning the largest weight that was found.
+/// Pairs of nodes that are not connected are ignored. This is a lower bound of the diameter,
+/// which can be useful to choose maximum weights for the searches. The same `seed` always yields
+/// the same queries for the same graph.
+pub fn estimate_diameter(
+    graph: &FastGraph,
+    calc: &mut PathCalculator,
+    num_samples: usize,
+    seed: u64,
+) -> Weight {
+    let num_nodes = graph.get_num_nodes();
+    if num_nodes == 0 {
+        return WEIGHT_ZERO;
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..num_samples)
+        .filter_map(|_| {
+            let start = rng.gen_range(0, num_nodes);
+            let end = rng.gen_range(0, num_nodes);
+            calc.calc_weight(graph, start, end)
+        })
+        .max()
+        .unwrap_or(WEIGHT_ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dijkstra::Dijkstra;
+    use crate::input_graph::InputGraph;
+    use crate::preparation_graph::PreparationGraph;
+    use crate::test_utils::random_input_graph;
+    use crate::{prepare, prepare_up_to};
+
+    use super::*;
+
+    #[test]
+    fn eccentricity_chain() {
+        // 0 -> 1 -> 2 -> 3, 5 -> 4
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 2);
+        g.add_edge(1, 2, 3);
+        g.add_edge(2, 3, 4);
+        g.add_edge(5, 4, 1);
+        g.freeze();
+        let fast_graph = prepare(&g);
+        assert_eq!(9, node_eccentricity(&fast_graph, 0));
+        assert_eq!(4, node_eccentricity(&fast_graph, 2));
+        assert_eq!(0, node_eccentricity(&fast_graph, 3));
+        assert_eq!(0, node_eccentricity(&fast_graph, 4));
+        assert_eq!(1, node_eccentricity(&fast_graph, 5));
+        let mut calc = PathCalculator::new(fast_graph.get_num_nodes());
+        let diameter = estimate_diameter(&fast_graph, &mut calc, 200, 1);
+        assert_eq!(9, diameter);
+        assert_eq!(diameter, estimate_diameter(&fast_graph, &mut calc, 200, 1));
+        assert_eq!(0, estimate_diameter(&fast_graph, &mut calc, 0, 1));
+    }
+
+    #[test]
+    fn eccentricity_random() {
+        let g = random_input_graph(100, 2.5, 20, 4);
+        let n = g.get_num_nodes();
+        let fast_graph = prepare(&g);
+        let preparation_graph = PreparationGraph::from_input_graph(&g);
+        let mut dijkstra = Dijkstra::new(n);
+        let mut calc = PathCalculator::new(n);
+        // the nodes of the core are not contracted, but the weights must still be right
+        let core_graph = prepare_up_to(&g, 0.7);
+        let mut max_eccentricity = 0;
+        for node in 0..n {
+            let expected = dijkstra.calc_tree(&preparation_graph, node);
+            assert_eq!(expected, calc.calc_tree(&fast_graph, node));
+            assert_eq!(expected, calc.calc_tree(&core_graph, node));
+            let eccentricity = expected.into_iter().flatten().max().unwrap();
+            assert_eq!(eccentricity, node_eccentricity(&fast_graph, node));
+            max_eccentricity = max_eccentricity.max(eccentricity);
+        }
+        assert!(estimate_diameter(&fast_graph, &mut calc, 100, 2) <= max_eccentricity);
+    }
+}
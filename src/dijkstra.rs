@@ -17,44 +17,89 @@
  * under the License.
  */
 
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, HashMap};
 
 use crate::constants::Weight;
-use crate::constants::{NodeId, INVALID_NODE, WEIGHT_MAX};
+use crate::constants::{add_weights, NodeId, INVALID_NODE, WEIGHT_MAX};
 use crate::heap_item::HeapItem;
-use crate::preparation_graph::PreparationGraph;
-use crate::shortest_path::ShortestPath;
+use crate::preparation_graph::{Arc, PreparationGraph};
+use crate::queue::{BinaryHeapQueue, DialQueue, Queue};
+use crate::shortest_path::{NoPathReason, ShortestPath};
 use crate::valid_flags::ValidFlags;
 
-pub struct Dijkstra {
+/// A plain Dijkstra search on a `PreparationGraph`. The priority queue can be chosen with the
+/// type parameter, see `with_queue()`.
+pub struct Dijkstra<Q = BinaryHeapQueue> {
     num_nodes: usize,
     data: Vec<Data>,
     valid_flags: ValidFlags,
-    heap: BinaryHeap<HeapItem>,
+    heap: Q,
     avoid_node: NodeId,
+    avoid_edge: (NodeId, usize),
     max_weight: Weight,
     start_node: NodeId,
 }
 
+/// A `Dijkstra` search using Dial's bucket queue, which finds the same paths, but takes nodes from
+/// the queue in constant time. All queued weights exceed the last settled weight by at most the
+/// maximum edge weight, so the queue is a circular array with at most one bucket more than the
+/// maximum edge weight. Use this for small integer weights, e.g.
+/// `DijkstraDial::with_queue(num_nodes, 0)`.
+pub type DijkstraDial = Dijkstra<DialQueue>;
+
 impl Dijkstra {
     pub fn new(num_nodes: usize) -> Self {
-        let heap = BinaryHeap::new();
+        Dijkstra::with_capacity(num_nodes, 0)
+    }
+
+    /// Like `new()`, but reserves space for `heap_capacity` items in the heap up front, so the
+    /// first queries do not need to grow it.
+    pub fn with_capacity(num_nodes: usize, heap_capacity: usize) -> Self {
+        Dijkstra::with_queue(num_nodes, heap_capacity)
+    }
+}
+
+impl<Q: Queue> Dijkstra<Q> {
+    /// Like `with_capacity()`, but uses the queue given by the type parameter, e.g.
+    /// `Dijkstra::<DialQueue>::with_queue(num_nodes, 0)`.
+    pub fn with_queue(num_nodes: usize, heap_capacity: usize) -> Self {
         Dijkstra {
             num_nodes,
             data: (0..num_nodes).map(|_i| Data::new()).collect(),
             valid_flags: ValidFlags::new(num_nodes),
-            heap,
+            heap: Q::with_capacity(heap_capacity),
             avoid_node: INVALID_NODE,
+            avoid_edge: (INVALID_NODE, 0),
             max_weight: WEIGHT_MAX,
             start_node: INVALID_NODE,
         }
     }
 
+    /// Prepares this instance for a graph with the given number of nodes and resets all settings
+    /// as if it was newly created, but keeps the allocated memory
+    pub(crate) fn reset(&mut self, num_nodes: usize) {
+        self.num_nodes = num_nodes;
+        self.data.clear();
+        self.data.resize_with(num_nodes, Data::new);
+        self.valid_flags.reset(num_nodes);
+        self.heap.clear();
+        self.avoid_node = INVALID_NODE;
+        self.avoid_edge = (INVALID_NODE, 0);
+        self.max_weight = WEIGHT_MAX;
+        self.start_node = INVALID_NODE;
+    }
+
     pub fn avoid_node(&mut self, node: NodeId) {
         self.avoid_node = node;
         self.start_node = INVALID_NODE;
     }
 
+    /// Avoids the edge with the given index in the out-edges of `from`
+    fn avoid_edge(&mut self, from: NodeId, index: usize) {
+        self.avoid_edge = (from, index);
+        self.start_node = INVALID_NODE;
+    }
+
     pub fn set_max_weight(&mut self, weight: Weight) {
         self.max_weight = weight;
     }
@@ -65,11 +110,7 @@ impl Dijkstra {
         start: NodeId,
         end: NodeId,
     ) -> Option<ShortestPath> {
-        assert_eq!(
-            graph.get_num_nodes(),
-            self.num_nodes,
-            "given graph has invalid node count"
-        );
+        self.check_query(graph, start, end);
         assert!(
             start != self.avoid_node && end != self.avoid_node,
             "path calculation must not start or end with avoided node"
@@ -77,46 +118,452 @@ impl Dijkstra {
         if start == end {
             return Some(ShortestPath::singular(start));
         }
-        if start != self.start_node {
-            self.heap.clear();
-            self.valid_flags.invalidate_all();
-            self.update_node(start, 0, INVALID_NODE);
-            self.heap.push(HeapItem::new(0, start));
-        }
+        self.init_search(start);
         if self.is_settled(end) {
             return self.build_path(start, end);
         }
-        self.start_node = start;
 
-        while !self.heap.is_empty() {
-            let curr = self.heap.pop().unwrap();
-            if self.is_settled(curr.node_id) {
-                // todo: since we are not using a special decrease key operation yet we need to
-                // filter out duplicate heap items here
+        while let Some(curr) = self.settle_next(graph) {
+            if curr.node_id == end {
+                break;
+            }
+            if curr.weight >= self.max_weight {
+                break;
+            }
+        }
+
+        self.build_path(start, end)
+    }
+
+    /// Like `calc_path()`, but tells why no path was found, i.e. whether the two nodes are not
+    /// connected or the search was stopped by the maximum weight.
+    pub fn calc_path_or_reason(
+        &mut self,
+        graph: &PreparationGraph,
+        start: NodeId,
+        end: NodeId,
+    ) -> Result<ShortestPath, NoPathReason> {
+        match self.calc_path(graph, start, end) {
+            Some(path) => Ok(path),
+            // without a maximum weight the search only stops once the heap is empty, and the end
+            // node is only reached without returning a path if its weight is too large
+            None if self.heap.is_empty() && !self.valid_flags.is_valid(end) => {
+                Err(NoPathReason::Disconnected)
+            }
+            None => Err(NoPathReason::ExceededMaxWeight),
+        }
+    }
+
+    /// Calculates the shortest path from `start` to `end` that consists of at most `max_hops`
+    /// edges. This cannot be done with `PathCalculator`, because limiting the number of edges
+    /// breaks the assumptions of the preparation (shortcuts hide how many edges they replace).
+    /// The search keeps multiple labels per node (one for each useful number of hops), so it can
+    /// be up to `max_hops` times slower than `calc_path()`.
+    pub fn calc_path_with_max_hops(
+        &mut self,
+        graph: &PreparationGraph,
+        start: NodeId,
+        end: NodeId,
+        max_hops: usize,
+    ) -> Option<ShortestPath> {
+        self.check_query(graph, start, end);
+        // the smallest number of hops of any label that was settled for each node so far. labels
+        // with more hops (and a larger weight, since they are settled later) are never useful
+        let mut min_hops = vec![usize::MAX; self.num_nodes];
+        let mut labels = vec![HopLabel::new(start, 0, 0, INVALID_NODE)];
+        let mut heap = BinaryHeap::new();
+        heap.push(HeapItem::new(0, 0));
+        while let Some(curr) = heap.pop() {
+            let label = labels[curr.node_id];
+            if label.hops >= min_hops[label.node] {
                 continue;
             }
-            for i in 0..graph.out_edges[curr.node_id].len() {
-                let adj = graph.out_edges[curr.node_id][i].adj_node;
-                let edge_weight = graph.out_edges[curr.node_id][i].weight;
-                if adj == self.avoid_node {
+            min_hops[label.node] = label.hops;
+            if label.node == end {
+                return Some(HopLabel::build_path(&labels, curr.node_id));
+            }
+            if label.hops == max_hops || label.weight >= self.max_weight {
+                continue;
+            }
+            for arc in &graph.out_edges[label.node] {
+                if arc.adj_node == self.avoid_node || label.hops + 1 >= min_hops[arc.adj_node] {
                     continue;
                 }
-                let weight = curr.weight + edge_weight;
-                if weight < self.get_weight(adj) {
-                    self.update_node(adj, weight, curr.node_id);
-                    self.heap.push(HeapItem::new(weight, adj));
-                }
+                let weight = add_weights(label.weight, arc.weight);
+                labels.push(HopLabel::new(
+                    arc.adj_node,
+                    label.hops + 1,
+                    weight,
+                    curr.node_id,
+                ));
+                heap.push(HeapItem::new(weight, labels.len() - 1));
             }
-            self.data[curr.node_id].settled = true;
+        }
+        None
+    }
+
+    /// Calculates the quickest path from `start` to `end` when departing at `departure_time`, for
+    /// edge weights that depend on the time. `weight_fn` returns the weight (travel time) of an
+    /// edge given its payload (see `InputGraph::add_edge_with_payload()`) and the time the edge
+    /// is entered, i.e. the departure time plus the weight of the path up to the edge. The weights
+    /// must be FIFO, i.e. entering an edge later never means leaving it earlier. The weight of the
+    /// returned path is the total travel time. There is no time-dependent version of
+    /// `PathCalculator`, because the shortcuts would need to store whole weight functions.
+    pub fn calc_path_time_dependent<F>(
+        &mut self,
+        graph: &PreparationGraph,
+        start: NodeId,
+        end: NodeId,
+        departure_time: Weight,
+        weight_fn: F,
+    ) -> Option<ShortestPath>
+    where
+        F: Fn(u64, Weight) -> Weight,
+    {
+        self.check_query(graph, start, end);
+        self.calc_path_with_weight_fn(graph, start, end, |arc, weight| {
+            weight_fn(arc.payload, add_weights(departure_time, weight))
+        })
+    }
+
+    /// Calculates the shortest path from `start` to `end`, where entering a node costs the given
+    /// node weight in addition to the edge weights, e.g. the waiting time at a toll booth. The
+    /// weight of `start` is not charged, but the weight of `end` is. There is no such query for
+    /// `PathCalculator`, because the shortcuts do not include the weights of the nodes they skip.
+    /// If the node weights are fixed the graph can be prepared using
+    /// `InputGraph::with_node_costs()` instead.
+    pub fn calc_path_with_node_weights(
+        &mut self,
+        graph: &PreparationGraph,
+        start: NodeId,
+        end: NodeId,
+        node_weights: &[Weight],
+    ) -> Option<ShortestPath> {
+        self.check_query(graph, start, end);
+        assert_eq!(
+            self.num_nodes,
+            node_weights.len(),
+            "there must be one weight per node"
+        );
+        self.calc_path_with_weight_fn(graph, start, end, |arc, _weight| {
+            add_weights(arc.weight, node_weights[arc.adj_node])
+        })
+    }
+
+    /// Runs a search that does not use or change the cached shortest path tree, because the edge
+    /// weights are determined by `weight_fn(arc, weight of the path up to the arc)`.
+    fn calc_path_with_weight_fn<F>(
+        &self,
+        graph: &PreparationGraph,
+        start: NodeId,
+        end: NodeId,
+        weight_fn: F,
+    ) -> Option<ShortestPath>
+    where
+        F: Fn(&Arc, Weight) -> Weight,
+    {
+        let mut weights = vec![WEIGHT_MAX; self.num_nodes];
+        let mut parents = vec![INVALID_NODE; self.num_nodes];
+        let mut settled = vec![false; self.num_nodes];
+        let mut heap = BinaryHeap::new();
+        weights[start] = 0;
+        heap.push(HeapItem::new(0, start));
+        while let Some(curr) = heap.pop() {
+            if settled[curr.node_id] {
+                continue;
+            }
+            settled[curr.node_id] = true;
             if curr.node_id == end {
-                break;
+                let mut nodes = vec![end];
+                while *nodes.last().unwrap() != start {
+                    nodes.push(parents[*nodes.last().unwrap()]);
+                }
+                nodes.reverse();
+                return Some(ShortestPath::new(start, end, curr.weight, nodes));
             }
             if curr.weight >= self.max_weight {
                 break;
             }
+            for arc in &graph.out_edges[curr.node_id] {
+                if arc.adj_node == self.avoid_node {
+                    continue;
+                }
+                let edge_weight = weight_fn(arc, curr.weight);
+                let weight = add_weights(curr.weight, edge_weight);
+                if weight < weights[arc.adj_node] {
+                    weights[arc.adj_node] = weight;
+                    parents[arc.adj_node] = curr.node_id;
+                    heap.push(HeapItem::new(weight, arc.adj_node));
+                }
+            }
+        }
+        None
+    }
+
+    /// Calculates the shortest path weights from `start` to all the given `targets`. The search
+    /// stops as soon as all targets are settled, or the maximum weight is exceeded. The returned
+    /// weights are in the same order as the targets and `None` is used for targets that cannot
+    /// be reached (within the maximum weight).
+    pub fn calc_distances_to(
+        &mut self,
+        graph: &PreparationGraph,
+        start: NodeId,
+        targets: &[NodeId],
+    ) -> Vec<Option<Weight>> {
+        self.check_query(graph, start, start);
+        assert_ne!(
+            start, self.avoid_node,
+            "path calculation must not start with avoided node"
+        );
+        self.init_search(start);
+        assert!(
+            targets.iter().all(|&t| t < self.num_nodes),
+            "invalid target node"
+        );
+        let mut remaining_targets: Vec<NodeId> = targets
+            .iter()
+            .cloned()
+            .filter(|&t| !self.is_settled(t))
+            .collect();
+        remaining_targets.sort_unstable();
+        remaining_targets.dedup();
+        let mut num_remaining = remaining_targets.len();
+        while num_remaining > 0 {
+            match self.settle_next(graph) {
+                Some(curr) => {
+                    if remaining_targets.binary_search(&curr.node_id).is_ok() {
+                        num_remaining -= 1;
+                    }
+                    if curr.weight >= self.max_weight {
+                        break;
+                    }
+                }
+                None => break,
+            }
+        }
+        targets
+            .iter()
+            .map(|&t| {
+                if self.valid_flags.is_valid(t) && self.data[t].weight <= self.max_weight {
+                    Some(self.data[t].weight)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Calculates the weights of the shortest paths from `start` to all nodes of the graph. Nodes
+    /// that cannot be reached (within the maximum weight) are `None`.
+    pub fn calc_tree(&mut self, graph: &PreparationGraph, start: NodeId) -> Vec<Option<Weight>> {
+        let all_nodes: Vec<NodeId> = (0..self.num_nodes).collect();
+        self.calc_distances_to(graph, start, &all_nodes)
+    }
+
+    /// Calculates the weights of the shortest paths from `start` to all nodes that can be reached
+    /// with a weight of at most `max_weight`, including `start` itself. Unlike `calc_tree()` this
+    /// only visits the nodes within the given radius, so it is much faster for small radii on
+    /// large graphs. The maximum weight set using `set_max_weight()` is ignored.
+    pub fn calc_reachable_within(
+        &mut self,
+        graph: &PreparationGraph,
+        start: NodeId,
+        max_weight: Weight,
+    ) -> HashMap<NodeId, Weight> {
+        self.check_query(graph, start, start);
+        assert_ne!(
+            start, self.avoid_node,
+            "path calculation must not start with avoided node"
+        );
+        // we need to see every settled node, so we cannot continue a previous search
+        self.start_node = INVALID_NODE;
+        self.init_search(start);
+        let mut result = HashMap::new();
+        while let Some(curr) = self.settle_next(graph) {
+            if curr.weight > max_weight {
+                break;
+            }
+            result.insert(curr.node_id, curr.weight);
+        }
+        result
+    }
+
+    /// Returns the (at most) `k` nodes of `candidates` that are closest to `start` along with
+    /// their weights, sorted by ascending weight. The search stops as soon as `k` candidates are
+    /// settled, so fewer than `k` nodes are returned only if not enough candidates can be reached
+    /// (within the maximum weight). `start` itself is returned with weight zero if it is one of
+    /// the candidates.
+    pub fn k_nearest(
+        &mut self,
+        graph: &PreparationGraph,
+        start: NodeId,
+        candidates: &[NodeId],
+        k: usize,
+    ) -> Vec<(NodeId, Weight)> {
+        self.check_query(graph, start, start);
+        assert_ne!(
+            start, self.avoid_node,
+            "path calculation must not start with avoided node"
+        );
+        assert!(
+            candidates.iter().all(|&c| c < self.num_nodes),
+            "invalid candidate node"
+        );
+        let mut candidates = candidates.to_vec();
+        candidates.sort_unstable();
+        candidates.dedup();
+        // we need to see every settled node, so we cannot continue a previous search
+        self.start_node = INVALID_NODE;
+        self.init_search(start);
+        let mut result = Vec::with_capacity(k.min(candidates.len()));
+        while result.len() < k {
+            match self.settle_next(graph) {
+                Some(curr) => {
+                    if curr.weight > self.max_weight {
+                        break;
+                    }
+                    if candidates.binary_search(&curr.node_id).is_ok() {
+                        result.push((curr.node_id, curr.weight));
+                    }
+                }
+                None => break,
+            }
+        }
+        result
+    }
+
+    /// Calculates all the (loop-free) shortest paths from `start` to `end`, since there can be
+    /// several paths with the same weight. `PathCalculator` cannot do this, because the
+    /// preparation only keeps one of them. Returns an error if there are more than `max_paths`
+    /// shortest paths and an empty list if there is no path at all.
+    pub fn calc_all_shortest(
+        &mut self,
+        graph: &PreparationGraph,
+        start: NodeId,
+        end: NodeId,
+        max_paths: usize,
+    ) -> Result<Vec<ShortestPath>, String> {
+        self.check_query(graph, start, end);
+        let weights = self.calc_tree(graph, start);
+        let weight = match weights[end] {
+            Some(weight) => weight,
+            None => return Ok(vec![]),
+        };
+        // an edge is on a shortest path if it leads from the start to its adjacent node with
+        // minimum weight, so we follow these edges backwards from the end node
+        let is_tight = |arc: &Arc, node: NodeId| match (weights[arc.adj_node], weights[node]) {
+            (Some(weight_adj), Some(weight_node)) => {
+                add_weights(weight_adj, arc.weight) == weight_node
+            }
+            _ => false,
+        };
+        let mut result = vec![];
+        let mut path = vec![end];
+        let mut next_edges = vec![0];
+        while let Some(&node) = path.last() {
+            if node == start {
+                if result.len() == max_paths {
+                    return Err(format!(
+                        "there are more than {} shortest paths from {} to {}",
+                        max_paths, start, end
+                    ));
+                }
+                let nodes = path.iter().rev().cloned().collect();
+                result.push(ShortestPath::new(start, end, weight, nodes));
+                path.pop();
+                next_edges.pop();
+                continue;
+            }
+            let in_edges = &graph.in_edges[node];
+            let i = next_edges[next_edges.len() - 1];
+            if i == in_edges.len() {
+                path.pop();
+                next_edges.pop();
+                continue;
+            }
+            *next_edges.last_mut().unwrap() += 1;
+            let pred = in_edges[i].adj_node;
+            let is_duplicate = in_edges[..i]
+                .iter()
+                .any(|arc| arc.adj_node == pred && is_tight(arc, node));
+            if is_tight(&in_edges[i], node) && !is_duplicate && !path.contains(&pred) {
+                path.push(pred);
+                next_edges.push(0);
+            }
         }
+        Ok(result)
+    }
 
-        return self.build_path(start, end);
+    /// Calculates the shortest path from `start` to `end` and the shortest alternative path that
+    /// differs from it in at least one edge. The alternative is found by excluding each edge of
+    /// the shortest path in turn, so this is about as expensive as running one query per edge of
+    /// the shortest path. Parallel edges are different edges, so the alternative can consist of
+    /// the same nodes as the shortest path.
+    pub fn calc_second_best(
+        &mut self,
+        graph: &PreparationGraph,
+        start: NodeId,
+        end: NodeId,
+    ) -> Option<(ShortestPath, Option<ShortestPath>)> {
+        let best = self.calc_path(graph, start, end)?;
+        if start == end {
+            return Some((best, None));
+        }
+        let mut edges = Vec::with_capacity(best.get_nodes().len());
+        let mut node = end;
+        while self.data[node].parent != INVALID_NODE {
+            edges.push((self.data[node].parent, self.data[node].parent_edge));
+            node = self.data[node].parent;
+        }
+        let second_best = self.calc_path_avoiding_each(graph, start, end, &edges);
+        Some((best, second_best))
+    }
+
+    /// Returns the shortest of the paths from `start` to `end` that avoid one of the given edges,
+    /// which are identified by their node and their index in the out-edges of this node
+    pub(crate) fn calc_path_avoiding_each(
+        &mut self,
+        graph: &PreparationGraph,
+        start: NodeId,
+        end: NodeId,
+        edges: &[(NodeId, usize)],
+    ) -> Option<ShortestPath> {
+        let mut result: Option<ShortestPath> = None;
+        for &(from, index) in edges {
+            self.avoid_edge(from, index);
+            if let Some(path) = self.calc_path(graph, start, end) {
+                if result
+                    .as_ref()
+                    .map_or(true, |p| path.get_weight() < p.get_weight())
+                {
+                    result = Some(path);
+                }
+            }
+        }
+        self.avoid_edge(INVALID_NODE, 0);
+        result
+    }
+
+    /// Returns the weight with which `end` was reached by the last search, even if it exceeds the
+    /// maximum weight and `calc_path` returned `None` for this reason. In this case the weight is
+    /// only an upper bound of the actual shortest path weight. Returns `None` if `end` was not
+    /// reached at all, which does not necessarily mean it is unreachable if a maximum weight is set.
+    pub fn last_reached_weight(&self, end: NodeId) -> Option<Weight> {
+        if self.valid_flags.is_valid(end) {
+            Some(self.data[end].weight)
+        } else {
+            None
+        }
+    }
+
+    fn check_query(&self, graph: &PreparationGraph, start: NodeId, end: NodeId) {
+        assert_eq!(
+            graph.get_num_nodes(),
+            self.num_nodes,
+            "given graph has invalid node count"
+        );
+        assert!(start < self.num_nodes, "invalid start node");
+        assert!(end < self.num_nodes, "invalid end node");
     }
 
     fn build_path(&mut self, start: NodeId, end: NodeId) -> Option<ShortestPath> {
@@ -142,11 +589,49 @@ impl Dijkstra {
         ))
     }
 
-    fn update_node(&mut self, node: NodeId, weight: Weight, parent: NodeId) {
+    fn init_search(&mut self, start: NodeId) {
+        if start != self.start_node {
+            self.heap.clear();
+            self.valid_flags.invalidate_all();
+            self.update_node(start, 0, INVALID_NODE, 0);
+            self.heap.push(HeapItem::new(0, start));
+        }
+        self.start_node = start;
+    }
+
+    /// Settles the next node of the current search and returns it, or `None` if there are no more
+    /// nodes left to settle
+    fn settle_next(&mut self, graph: &PreparationGraph) -> Option<HeapItem> {
+        while let Some(curr) = self.heap.pop() {
+            if self.is_settled(curr.node_id) {
+                // todo: since we are not using a special decrease key operation yet we need to
+                // filter out duplicate heap items here
+                continue;
+            }
+            for i in 0..graph.out_edges[curr.node_id].len() {
+                let adj = graph.out_edges[curr.node_id][i].adj_node;
+                let edge_weight = graph.out_edges[curr.node_id][i].weight;
+                if adj == self.avoid_node || (curr.node_id, i) == self.avoid_edge {
+                    continue;
+                }
+                let weight = add_weights(curr.weight, edge_weight);
+                if weight < self.get_weight(adj) {
+                    self.update_node(adj, weight, curr.node_id, i);
+                    self.heap.push(HeapItem::new(weight, adj));
+                }
+            }
+            self.data[curr.node_id].settled = true;
+            return Some(curr);
+        }
+        None
+    }
+
+    fn update_node(&mut self, node: NodeId, weight: Weight, parent: NodeId, parent_edge: usize) {
         self.valid_flags.set_valid(node);
         self.data[node].settled = false;
         self.data[node].weight = weight;
         self.data[node].parent = parent;
+        self.data[node].parent_edge = parent_edge;
     }
 
     fn is_settled(&self, node: NodeId) -> bool {
@@ -166,6 +651,8 @@ struct Data {
     settled: bool,
     weight: Weight,
     parent: NodeId,
+    // the index of the edge in the out-edges of the parent
+    parent_edge: usize,
 }
 
 impl Data {
@@ -175,14 +662,61 @@ impl Data {
             settled: false,
             weight: WEIGHT_MAX,
             parent: INVALID_NODE,
+            parent_edge: 0,
         }
     }
 }
 
+#[derive(Clone, Copy)]
+struct HopLabel {
+    node: NodeId,
+    hops: usize,
+    weight: Weight,
+    parent: usize,
+}
+
+impl HopLabel {
+    fn new(node: NodeId, hops: usize, weight: Weight, parent: usize) -> Self {
+        HopLabel {
+            node,
+            hops,
+            weight,
+            parent,
+        }
+    }
+
+    fn build_path(labels: &[HopLabel], last: usize) -> ShortestPath {
+        let mut nodes = vec![];
+        let mut label = last;
+        while label != INVALID_NODE {
+            nodes.push(labels[label].node);
+            label = labels[label].parent;
+        }
+        nodes.reverse();
+        ShortestPath::new(nodes[0], labels[last].node, labels[last].weight, nodes)
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use crate::test_utils::random_input_graph;
+
     use super::*;
 
+    #[test]
+    fn with_capacity() {
+        let mut dijkstra = Dijkstra::with_capacity(3, 50);
+        assert!(dijkstra.heap.capacity() >= 50);
+        let mut g = PreparationGraph::new(3);
+        g.add_edge(0, 1, 2);
+        g.add_edge(1, 2, 3);
+        assert_eq!(5, dijkstra.calc_path(&g, 0, 2).unwrap().get_weight());
+        assert!(dijkstra.heap.capacity() >= 50);
+    }
+
     #[test]
     fn simple_path() {
         //      7 -> 8 -> 9
@@ -244,6 +778,68 @@ mod tests {
         assert_path(&mut d, &g, 0, 2, 13, vec![0, 3, 4, 5, 2]);
     }
 
+    #[test]
+    fn zero_weight_cycle() {
+        // 0 -> 1 -> 2 -> 3
+        //      ^    |
+        //       \---
+        let mut g = PreparationGraph::new(4);
+        g.add_edge(0, 1, 2);
+        g.add_edge(1, 2, 0);
+        g.add_edge(2, 1, 0);
+        g.add_edge(2, 3, 1);
+        let mut d = Dijkstra::new(g.get_num_nodes());
+        assert_path(&mut d, &g, 0, 3, 3, vec![0, 1, 2, 3]);
+        assert_path(&mut d, &g, 2, 1, 0, vec![2, 1]);
+        assert_no_path(&mut d, &g, 3, 0);
+    }
+
+    #[test]
+    fn second_best() {
+        // 0 -> 1 -> 2
+        // |         |
+        // 3 -> 4 -> 5 -> 6
+        let mut g = PreparationGraph::new(7);
+        g.add_edge(0, 1, 1);
+        g.add_edge(1, 2, 1);
+        g.add_edge(2, 5, 1);
+        g.add_edge(0, 3, 2);
+        g.add_edge(3, 4, 1);
+        g.add_edge(4, 5, 1);
+        g.add_edge(5, 6, 1);
+        let mut d = Dijkstra::new(g.get_num_nodes());
+        let (best, second_best) = d.calc_second_best(&g, 0, 5).unwrap();
+        assert_eq!(ShortestPath::new(0, 5, 3, vec![0, 1, 2, 5]), best);
+        assert_eq!(&vec![0, 1, 2, 5], best.get_nodes());
+        let second_best = second_best.unwrap();
+        assert_eq!(4, second_best.get_weight());
+        assert_eq!(&vec![0, 3, 4, 5], second_best.get_nodes());
+        // there is no alternative for the last edge
+        let (best, second_best) = d.calc_second_best(&g, 5, 6).unwrap();
+        assert_eq!(1, best.get_weight());
+        assert!(second_best.is_none());
+        assert!(d.calc_second_best(&g, 6, 0).is_none());
+        // the excluded edges must not affect later queries
+        assert_path(&mut d, &g, 0, 5, 3, vec![0, 1, 2, 5]);
+    }
+
+    #[test]
+    fn second_best_with_parallel_edges() {
+        // two edges from 0 to 1, and a detour via 2
+        let mut g = PreparationGraph::new(3);
+        g.add_edge(0, 1, 4);
+        g.add_edge(0, 1, 1);
+        g.add_edge(0, 2, 3);
+        g.add_edge(2, 1, 3);
+        let mut d = Dijkstra::new(g.get_num_nodes());
+        let (best, second_best) = d.calc_second_best(&g, 0, 1).unwrap();
+        assert_eq!(ShortestPath::new(0, 1, 1, vec![0, 1]), best);
+        assert_eq!(Some(ShortestPath::new(0, 1, 4, vec![0, 1])), second_best);
+        let (best, second_best) = d.calc_second_best(&g, 1, 1).unwrap();
+        assert_eq!(ShortestPath::singular(1), best);
+        assert_eq!(None, second_best);
+    }
+
     #[test]
     fn limit_weight() {
         // 0 -> 1 -> 2 -> 3 -> 4
@@ -261,6 +857,299 @@ mod tests {
         assert_path(&mut d, &g, 0, 3, 3, vec![0, 1, 2, 3]);
     }
 
+    #[test]
+    fn last_reached_weight_over_budget() {
+        // 0 -> 1 -> 2 -> 3    4
+        let mut g = PreparationGraph::new(5);
+        g.add_edge(0, 1, 2);
+        g.add_edge(1, 2, 2);
+        g.add_edge(2, 3, 2);
+        let mut d = Dijkstra::new(g.get_num_nodes());
+        d.set_max_weight(3);
+        assert_no_path(&mut d, &g, 0, 3);
+        // node 3 was reached, but only with a weight exceeding the budget
+        assert_eq!(Some(6), d.last_reached_weight(3));
+        // node 4 cannot be reached at all
+        assert_no_path(&mut d, &g, 0, 4);
+        assert_eq!(None, d.last_reached_weight(4));
+        d.set_max_weight(6);
+        assert_path(&mut d, &g, 1, 3, 4, vec![1, 2, 3]);
+        assert_eq!(Some(4), d.last_reached_weight(3));
+    }
+
+    #[test]
+    fn distances_to_targets() {
+        // 0 -> 1 -> 2 -> 3
+        //      |
+        //      4    5
+        let mut g = PreparationGraph::new(6);
+        g.add_edge(0, 1, 1);
+        g.add_edge(1, 2, 2);
+        g.add_edge(2, 3, 3);
+        g.add_edge(1, 4, 5);
+        let mut d = Dijkstra::new(g.get_num_nodes());
+        assert_eq!(
+            vec![Some(6), Some(0), None, Some(3), Some(6)],
+            d.calc_distances_to(&g, 0, &[3, 0, 5, 2, 4])
+        );
+        assert_eq!(vec![Some(5), None], d.calc_distances_to(&g, 1, &[3, 0]));
+        d.set_max_weight(4);
+        assert_eq!(
+            vec![None, Some(1), Some(3), None],
+            d.calc_distances_to(&g, 0, &[3, 1, 2, 4])
+        );
+    }
+
+    #[test]
+    fn k_nearest() {
+        // 0 -> 1 -> 2 -> 3
+        //      |
+        //      4    5
+        let mut g = PreparationGraph::new(6);
+        g.add_edge(0, 1, 1);
+        g.add_edge(1, 2, 2);
+        g.add_edge(2, 3, 3);
+        g.add_edge(1, 4, 4);
+        let mut d = Dijkstra::new(g.get_num_nodes());
+        assert_eq!(
+            vec![(2, 3), (4, 5)],
+            d.k_nearest(&g, 0, &[3, 4, 5, 2, 2], 2)
+        );
+        assert_eq!(
+            vec![(0, 0), (2, 3), (4, 5), (3, 6)],
+            d.k_nearest(&g, 0, &[3, 4, 5, 2, 0], 10)
+        );
+        assert!(d.k_nearest(&g, 0, &[3, 4], 0).is_empty());
+        assert!(d.k_nearest(&g, 2, &[0, 1, 5], 2).is_empty());
+        d.set_max_weight(5);
+        assert_eq!(vec![(2, 3), (4, 5)], d.k_nearest(&g, 0, &[3, 4, 2], 3));
+    }
+
+    #[test]
+    fn all_shortest_paths() {
+        //   /-> 1 -\
+        // 0 --> 2 --> 3 -> 5
+        //   \-> 4 ----/
+        let mut g = PreparationGraph::new(6);
+        g.add_edge(0, 1, 1);
+        g.add_edge(1, 3, 2);
+        g.add_edge(0, 2, 2);
+        g.add_edge(2, 3, 1);
+        g.add_edge(0, 4, 1);
+        g.add_edge(4, 3, 3);
+        g.add_edge(3, 5, 1);
+        // a parallel edge does not lead to another path
+        g.add_edge(3, 5, 1);
+        let mut d = Dijkstra::new(g.get_num_nodes());
+        let paths = d.calc_all_shortest(&g, 0, 5, 10).unwrap();
+        let nodes: Vec<&Vec<NodeId>> = paths.iter().map(|p| p.get_nodes()).collect();
+        assert_eq!(vec![&vec![0, 1, 3, 5], &vec![0, 2, 3, 5]], nodes);
+        assert!(paths.iter().all(|p| p.get_weight() == 4));
+        assert!(d.calc_all_shortest(&g, 0, 5, 1).is_err());
+        assert_eq!(
+            vec![ShortestPath::singular(3)],
+            d.calc_all_shortest(&g, 3, 3, 1).unwrap()
+        );
+        assert!(d.calc_all_shortest(&g, 5, 0, 1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn all_shortest_paths_zero_weight_cycle() {
+        // 0 -> 1 <-> 2 -> 3, all edges between 1 and 2 have zero weight
+        let mut g = PreparationGraph::new(4);
+        g.add_edge(0, 1, 1);
+        g.add_edge(1, 2, 0);
+        g.add_edge(2, 1, 0);
+        g.add_edge(1, 3, 1);
+        g.add_edge(2, 3, 1);
+        let mut d = Dijkstra::new(g.get_num_nodes());
+        let paths = d.calc_all_shortest(&g, 0, 3, 10).unwrap();
+        let nodes: Vec<&Vec<NodeId>> = paths.iter().map(|p| p.get_nodes()).collect();
+        assert_eq!(vec![&vec![0, 1, 3], &vec![0, 1, 2, 3]], nodes);
+    }
+
+    #[test]
+    fn max_hops() {
+        // 0 -> 1 -> 2 -> 3 -> 4
+        // |    \    |         ^
+        // |     \-> 5 -------/
+        // \--------/
+        let mut g = PreparationGraph::new(6);
+        g.add_edge(0, 1, 1);
+        g.add_edge(1, 2, 1);
+        g.add_edge(2, 3, 1);
+        g.add_edge(3, 4, 1);
+        g.add_edge(2, 5, 2);
+        g.add_edge(1, 5, 4);
+        g.add_edge(5, 4, 2);
+        g.add_edge(0, 5, 10);
+        let mut d = Dijkstra::new(g.get_num_nodes());
+        let path = d.calc_path_with_max_hops(&g, 0, 4, 4).unwrap();
+        assert_eq!(
+            (4, &vec![0, 1, 2, 3, 4]),
+            (path.get_weight(), path.get_nodes())
+        );
+        // node 5 is reached with a lower weight using three hops first, but we need the label
+        // with two hops as well
+        let path = d.calc_path_with_max_hops(&g, 0, 4, 3).unwrap();
+        assert_eq!(
+            (7, &vec![0, 1, 5, 4]),
+            (path.get_weight(), path.get_nodes())
+        );
+        let path = d.calc_path_with_max_hops(&g, 0, 4, 2).unwrap();
+        assert_eq!((12, &vec![0, 5, 4]), (path.get_weight(), path.get_nodes()));
+        assert_eq!(None, d.calc_path_with_max_hops(&g, 0, 4, 1));
+        assert_eq!(
+            Some(ShortestPath::singular(3)),
+            d.calc_path_with_max_hops(&g, 3, 3, 0)
+        );
+    }
+
+    #[test]
+    fn no_path_reason() {
+        // 0 -> 1 -> 2   3
+        let mut g = PreparationGraph::new(4);
+        g.add_edge(0, 1, 5);
+        g.add_edge(1, 2, 5);
+        let mut d = Dijkstra::new(4);
+        assert_eq!(
+            Err(NoPathReason::Disconnected),
+            d.calc_path_or_reason(&g, 0, 3)
+        );
+        assert_eq!(10, d.calc_path_or_reason(&g, 0, 2).unwrap().get_weight());
+        d.set_max_weight(7);
+        assert_eq!(
+            Err(NoPathReason::ExceededMaxWeight),
+            d.calc_path_or_reason(&g, 0, 2)
+        );
+        assert_eq!(
+            Err(NoPathReason::Disconnected),
+            d.calc_path_or_reason(&g, 2, 0)
+        );
+    }
+
+    #[test]
+    fn node_weights() {
+        // 0 -> 1 -> 3
+        //  \-> 2 ->/
+        let mut g = PreparationGraph::new(4);
+        g.add_edge(0, 1, 1);
+        g.add_edge(1, 3, 1);
+        g.add_edge(0, 2, 2);
+        g.add_edge(2, 3, 2);
+        let mut d = Dijkstra::new(4);
+        let path = d
+            .calc_path_with_node_weights(&g, 0, 3, &[100, 0, 0, 5])
+            .unwrap();
+        assert_eq!(&vec![0, 1, 3], path.get_nodes());
+        assert_eq!(1 + 1 + 5, path.get_weight());
+        let path = d
+            .calc_path_with_node_weights(&g, 0, 3, &[0, 3, 0, 0])
+            .unwrap();
+        assert_eq!(&vec![0, 2, 3], path.get_nodes());
+        assert_eq!(4, path.get_weight());
+        assert_eq!(
+            0,
+            d.calc_path_with_node_weights(&g, 1, 1, &[7; 4])
+                .unwrap()
+                .get_weight()
+        );
+        // the regular search is not affected
+        assert_eq!(2, d.calc_path(&g, 0, 3).unwrap().get_weight());
+    }
+
+    #[test]
+    fn time_dependent() {
+        // 0 -> 1 -> 3
+        //  \-> 2 -/
+        let mut g = PreparationGraph::new(4);
+        g.add_edge_with_payload(0, 1, 1, 0);
+        g.add_edge_with_payload(1, 3, 1, 1);
+        g.add_edge_with_payload(0, 2, 2, 2);
+        g.add_edge_with_payload(2, 3, 2, 3);
+        // the edge 1->3 is congested between 10 and 20, leaving it takes until 20 at least
+        let weight_fn = |edge: u64, time: Weight| match edge {
+            1 if (10..20).contains(&time) => 20 - time + 1,
+            0 | 1 => 1,
+            _ => 2,
+        };
+        let mut d = Dijkstra::new(4);
+        let path = d.calc_path_time_dependent(&g, 0, 3, 0, weight_fn).unwrap();
+        assert_eq!(&vec![0, 1, 3], path.get_nodes());
+        assert_eq!(2, path.get_weight());
+        let path = d.calc_path_time_dependent(&g, 0, 3, 9, weight_fn).unwrap();
+        assert_eq!(&vec![0, 2, 3], path.get_nodes());
+        assert_eq!(4, path.get_weight());
+        let path = d.calc_path_time_dependent(&g, 0, 3, 18, weight_fn).unwrap();
+        assert_eq!(&vec![0, 1, 3], path.get_nodes());
+        assert_eq!(1 + 2, path.get_weight());
+        assert_eq!(None, d.calc_path_time_dependent(&g, 3, 0, 0, weight_fn));
+        // the regular search is not affected
+        assert_eq!(2, d.calc_path(&g, 0, 3).unwrap().get_weight());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid start node")]
+    fn empty_graph() {
+        // there are no valid node ids, so every query is invalid
+        let g = PreparationGraph::new(0);
+        let mut d = Dijkstra::new(0);
+        d.calc_path(&g, 0, 0);
+    }
+
+    #[test]
+    fn single_node() {
+        let g = PreparationGraph::new(1);
+        let mut d = Dijkstra::new(1);
+        assert_eq!(&vec![0], d.calc_path(&g, 0, 0).unwrap().get_nodes());
+        assert_eq!(vec![Some(0)], d.calc_tree(&g, 0));
+        assert_eq!(1, d.calc_reachable_within(&g, 0, 0).len());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid end node")]
+    fn single_node_invalid_end() {
+        let g = PreparationGraph::new(1);
+        let mut d = Dijkstra::new(1);
+        d.calc_path(&g, 0, 1);
+    }
+
+    #[test]
+    fn tree() {
+        // 0 -> 1 -> 2    3
+        let mut g = PreparationGraph::new(4);
+        g.add_edge(0, 1, 3);
+        g.add_edge(1, 2, 4);
+        let mut d = Dijkstra::new(g.get_num_nodes());
+        assert_eq!(vec![Some(0), Some(3), Some(7), None], d.calc_tree(&g, 0));
+        assert_eq!(vec![None, Some(0), Some(4), None], d.calc_tree(&g, 1));
+        d.set_max_weight(5);
+        assert_eq!(vec![Some(0), Some(3), None, None], d.calc_tree(&g, 0));
+    }
+
+    #[test]
+    fn reachable_within() {
+        // 0 -> 1 -> 2 -> 3
+        //       \-> 4
+        let mut g = PreparationGraph::new(5);
+        g.add_edge(0, 1, 2);
+        g.add_edge(1, 2, 3);
+        g.add_edge(2, 3, 1);
+        g.add_edge(1, 4, 4);
+        let mut d = Dijkstra::new(5);
+        d.calc_path(&g, 0, 3);
+        let reachable = d.calc_reachable_within(&g, 0, 5);
+        assert_eq!(3, reachable.len());
+        assert_eq!(Some(&0), reachable.get(&0));
+        assert_eq!(Some(&2), reachable.get(&1));
+        assert_eq!(Some(&5), reachable.get(&2));
+        assert_eq!(1, d.calc_reachable_within(&g, 0, 1).len());
+        assert_eq!(5, d.calc_reachable_within(&g, 0, 6).len());
+        assert_eq!(2, d.calc_reachable_within(&g, 2, 100).len());
+        // the search can still be continued
+        assert_eq!(6, d.calc_path(&g, 0, 4).unwrap().get_weight());
+    }
+
     #[test]
     fn run_multiple() {
         // 0 -> 1 -> 2
@@ -296,13 +1185,35 @@ mod tests {
         assert_path(&mut d, &g, 3, 10, 3, vec![3, 8, 9, 10]);
     }
 
+    #[test]
+    fn dial() {
+        let input_graph = random_input_graph(200, 2.5, 30, 3);
+        let g = PreparationGraph::from_input_graph(&input_graph);
+        let n = g.get_num_nodes();
+        let mut heap = Dijkstra::new(n);
+        let mut dial = DijkstraDial::with_queue(n, 0);
+        let mut rng = StdRng::seed_from_u64(5);
+        for i in 0..300 {
+            let s = rng.gen_range(0, n);
+            let t = rng.gen_range(0, n);
+            if i % 3 == 0 {
+                // like in the witness searches
+                let max_weight = rng.gen_range(0, 60);
+                heap.set_max_weight(max_weight);
+                dial.set_max_weight(max_weight);
+            }
+            let expected = heap.calc_path(&g, s, t);
+            assert_eq!(expected, dial.calc_path(&g, s, t));
+        }
+    }
+
     fn assert_no_path(
         dijkstra: &mut Dijkstra,
         graph: &PreparationGraph,
         source: NodeId,
         target: NodeId,
     ) {
-        assert_eq!(dijkstra.calc_path(&graph, source, target), None);
+        assert_eq!(dijkstra.calc_path(graph, source, target), None);
     }
 
     fn assert_path(
@@ -314,7 +1225,7 @@ mod tests {
         nodes: Vec<NodeId>,
     ) {
         assert_eq!(
-            dijkstra.calc_path(&graph, source, target),
+            dijkstra.calc_path(graph, source, target),
             Some(ShortestPath::new(source, target, weight, nodes))
         );
     }
@@ -0,0 +1,177 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied.  See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::io::{self, BufRead, Write};
+
+use crate::constants::{NodeId, Weight};
+use crate::preparation_graph::PreparationGraph;
+
+/// Writes the given graph in the DIMACS shortest path format, i.e. a `p sp <nodes> <arcs>` header
+/// followed by one `a <from> <to> <weight>` line per edge. DIMACS node ids start at one, so all
+/// node ids are incremented by one. Isolated nodes are included in the node count of the header.
+pub fn export_dimacs<W: Write>(graph: &PreparationGraph, w: W) -> io::Result<()> {
+    let mut w = io::BufWriter::new(w);
+    let num_arcs: usize = graph.out_edges.iter().map(|edges| edges.len()).sum();
+    writeln!(w, "p sp {} {}", graph.get_num_nodes(), num_arcs)?;
+    for (from, edges) in graph.out_edges.iter().enumerate() {
+        for arc in edges {
+            writeln!(w, "a {} {} {}", from + 1, arc.adj_node + 1, arc.weight)?;
+        }
+    }
+    w.flush()
+}
+
+/// Reads a graph in the DIMACS shortest path format as written by `export_dimacs()`. The node
+/// count is taken from the `p sp` header and the (one-based) node ids are converted to zero-based
+/// ids. Comment lines starting with `c` are ignored.
+pub fn import_dimacs<R: BufRead>(r: R) -> Result<PreparationGraph, String> {
+    read_dimacs(r, false)
+}
+
+/// Like `import_dimacs()`, but adds each arc in both directions, for files that list every edge of
+/// an undirected graph only once. See `PreparationGraph::add_bidirectional_edge()`.
+pub fn import_dimacs_undirected<R: BufRead>(r: R) -> Result<PreparationGraph, String> {
+    read_dimacs(r, true)
+}
+
+fn read_dimacs<R: BufRead>(r: R, undirected: bool) -> Result<PreparationGraph, String> {
+    let mut graph: Option<PreparationGraph> = None;
+    for (i, line) in r.lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        let entries: Vec<&str> = line.split_whitespace().collect();
+        match entries.first() {
+            None | Some(&"c") => {}
+            Some(&"p") => {
+                if graph.is_some() {
+                    return Err(format!("Duplicate problem line in line {}", i + 1));
+                }
+                if entries.len() != 4 || entries[1] != "sp" {
+                    return Err(format!("Invalid problem line in line {}: {}", i + 1, line));
+                }
+                let num_nodes = parse_entry(entries[2], i)?;
+                graph = Some(PreparationGraph::new(num_nodes));
+            }
+            Some(&"a") => {
+                let graph = graph
+                    .as_mut()
+                    .ok_or_else(|| format!("Arc before problem line in line {}", i + 1))?;
+                if entries.len() != 4 {
+                    return Err(format!("Invalid arc in line {}: {}", i + 1, line));
+                }
+                let from = parse_node(entries[1], graph.get_num_nodes(), i)?;
+                let to = parse_node(entries[2], graph.get_num_nodes(), i)?;
+                let weight: Weight = parse_entry(entries[3], i)?;
+                if undirected {
+                    graph.add_bidirectional_edge(from, to, weight);
+                } else {
+                    graph.add_edge(from, to, weight);
+                }
+            }
+            Some(_) => return Err(format!("Invalid line {}: {}", i + 1, line)),
+        }
+    }
+    graph.ok_or_else(|| String::from("Missing problem line"))
+}
+
+fn parse_entry(entry: &str, line: usize) -> Result<usize, String> {
+    entry
+        .parse::<usize>()
+        .map_err(|_| format!("Invalid number in line {}: {}", line + 1, entry))
+}
+
+fn parse_node(entry: &str, num_nodes: usize, line: usize) -> Result<NodeId, String> {
+    let node = parse_entry(entry, line)?;
+    if node == 0 || node > num_nodes {
+        return Err(format!("Invalid node id in line {}: {}", line + 1, node));
+    }
+    Ok(node - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::random_input_graph;
+
+    use super::*;
+
+    #[test]
+    fn export_dimacs_with_isolated_nodes() {
+        let mut g = PreparationGraph::new(5);
+        g.add_edge(0, 1, 3);
+        g.add_edge(1, 3, 7);
+        let mut out = Vec::new();
+        export_dimacs(&g, &mut out).unwrap();
+        assert_eq!(
+            "p sp 5 2\na 1 2 3\na 2 4 7\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let g = random_input_graph(100, 2.5, 30, 4);
+        let g = PreparationGraph::from_input_graph(&g);
+        let mut first = Vec::new();
+        export_dimacs(&g, &mut first).unwrap();
+        let imported = import_dimacs(first.as_slice()).unwrap();
+        assert_eq!(g.get_num_nodes(), imported.get_num_nodes());
+        for node in 0..g.get_num_nodes() {
+            assert_eq!(arcs(&g, node), arcs(&imported, node));
+        }
+        let mut second = Vec::new();
+        export_dimacs(&imported, &mut second).unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn import_errors() {
+        let import = |s: &str| import_dimacs(s.as_bytes()).err();
+        assert_eq!(Some("Missing problem line".to_string()), import("c only\n"));
+        assert_eq!(
+            Some("Arc before problem line in line 1".to_string()),
+            import("a 1 2 3\np sp 2 1\n")
+        );
+        assert_eq!(
+            Some("Invalid node id in line 2: 3".to_string()),
+            import("p sp 2 1\na 1 3 3\n")
+        );
+        assert_eq!(
+            Some("Invalid node id in line 2: 0".to_string()),
+            import("p sp 2 1\na 0 1 3\n")
+        );
+    }
+
+    #[test]
+    fn import_undirected() {
+        let input = "p sp 3 2\na 1 2 3\na 2 3 5\n";
+        let directed = import_dimacs(input.as_bytes()).unwrap();
+        assert_eq!(None, directed.get_edge_weight(1, 0));
+        let undirected = import_dimacs_undirected(input.as_bytes()).unwrap();
+        assert_eq!(vec![(1, 3)], arcs(&undirected, 0));
+        assert_eq!(vec![(0, 3), (2, 5)], arcs(&undirected, 1));
+        assert_eq!(vec![(1, 5)], arcs(&undirected, 2));
+    }
+
+    fn arcs(graph: &PreparationGraph, node: NodeId) -> Vec<(NodeId, Weight)> {
+        graph
+            .get_out_edges(node)
+            .iter()
+            .map(|arc| (arc.adj_node, arc.weight))
+            .collect()
+    }
+}
@@ -17,12 +17,42 @@
  * under the License.
  */
 
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::Read;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bincode::Options;
+
 use serde::Deserialize;
 use serde::Serialize;
 
 use crate::constants::Weight;
-use crate::constants::{EdgeId, NodeId, INVALID_EDGE};
+use crate::constants::{add_weights, EdgeId, NodeId, INVALID_EDGE};
+use crate::csr_graph::CsrGraph;
+use crate::fast_graph_builder::FastGraphBuilder;
+use crate::input_graph::InputGraph;
+use crate::preparation_graph::PreparationGraph;
+use crate::search_graph::SearchGraph;
+use crate::valid_flags::ValidFlags;
+
+/// A summary of the size of a `FastGraph`, see `FastGraph::stats()`. The edge counts include
+/// the edges of both the forward and the backward graph, and the degrees only count the (upward)
+/// edges stored at each node.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GraphStats {
+    pub num_nodes: usize,
+    pub num_edges: usize,
+    pub num_shortcuts: usize,
+    pub max_out_degree: usize,
+    pub max_in_degree: usize,
+    /// The highest rank, or `None` if the graph has no nodes
+    pub max_rank: Option<usize>,
+}
 
+/// The prepared graph, see `prepare()`. The edges are stored by the rank of their nodes, but the
+/// nodes keep the ids of the `InputGraph`, so no translation of node ids is needed.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct FastGraph {
     num_nodes: usize,
@@ -32,6 +62,141 @@ pub struct FastGraph {
 
     pub(crate) edges_bwd: Vec<FastGraphEdge>,
     pub(crate) first_edge_ids_bwd: Vec<EdgeId>,
+
+    // graphs saved before the payloads were added have no payloads, which count as zero
+    #[serde(default)]
+    pub(crate) payloads_fwd: Vec<u64>,
+    #[serde(default)]
+    pub(crate) payloads_bwd: Vec<u64>,
+
+    /// The number of nodes that were not contracted, see `prepare_up_to()`. These nodes have the
+    /// highest ranks, and the edges between them are kept in both directions.
+    #[serde(default)]
+    pub(crate) num_core_nodes: usize,
+
+    /// Identifies this graph, see `get_generation()`. It is not saved, so every loaded graph gets
+    /// a new one.
+    #[serde(skip, default = "next_generation")]
+    generation: usize,
+
+    /// The shortcuts replacing each edge, built when they are first needed by
+    /// `increase_edge_weight()`
+    #[serde(skip)]
+    shortcut_parents: Option<ShortcutParents>,
+}
+
+/// The last generation handed out to a graph, see `FastGraph::get_generation()`
+static LAST_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+fn next_generation() -> usize {
+    LAST_GENERATION.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// The shortcuts that directly replace each edge, see `FastGraph::find_replacing_shortcuts()`.
+/// The shortcuts replacing the forward edge `e` are `fwd[first_fwd[e]..first_fwd[e + 1]]`, and
+/// likewise for the backward edges.
+#[derive(Debug)]
+struct ShortcutParents {
+    first_fwd: Vec<usize>,
+    fwd: Vec<(EdgeId, bool)>,
+    first_bwd: Vec<usize>,
+    bwd: Vec<(EdgeId, bool)>,
+}
+
+impl ShortcutParents {
+    fn new(graph: &FastGraph) -> Self {
+        let fwd = graph
+            .edges_fwd
+            .iter()
+            .enumerate()
+            .map(|(id, e)| (id, true, e));
+        let bwd = graph
+            .edges_bwd
+            .iter()
+            .enumerate()
+            .map(|(id, e)| (id, false, e));
+        let shortcuts: Vec<_> = fwd.chain(bwd).filter(|(_, _, e)| e.is_shortcut()).collect();
+        // every shortcut replaces a backward (in) edge and a forward (out) edge
+        let (first_fwd, fwd) = ShortcutParents::group_by_edge(
+            graph.edges_fwd.len(),
+            shortcuts
+                .iter()
+                .map(|&(id, fwd, e)| (e.replaced_out_edge, (id, fwd))),
+        );
+        let (first_bwd, bwd) = ShortcutParents::group_by_edge(
+            graph.edges_bwd.len(),
+            shortcuts
+                .iter()
+                .map(|&(id, fwd, e)| (e.replaced_in_edge, (id, fwd))),
+        );
+        ShortcutParents {
+            first_fwd,
+            fwd,
+            first_bwd,
+            bwd,
+        }
+    }
+
+    fn group_by_edge<I>(num_edges: usize, pairs: I) -> (Vec<usize>, Vec<(EdgeId, bool)>)
+    where
+        I: Iterator<Item = (EdgeId, (EdgeId, bool))> + Clone,
+    {
+        let mut first = vec![0; num_edges + 1];
+        for (edge, _) in pairs.clone() {
+            first[edge + 1] += 1;
+        }
+        for e in 0..num_edges {
+            first[e + 1] += first[e];
+        }
+        let mut next = first.clone();
+        let mut shortcuts = vec![(INVALID_EDGE, false); first[num_edges]];
+        for (edge, shortcut) in pairs {
+            shortcuts[next[edge]] = shortcut;
+            next[edge] += 1;
+        }
+        (first, shortcuts)
+    }
+
+    fn get(&self, edge: (EdgeId, bool)) -> &[(EdgeId, bool)] {
+        match edge {
+            (e, true) => &self.fwd[self.first_fwd[e]..self.first_fwd[e + 1]],
+            (e, false) => &self.bwd[self.first_bwd[e]..self.first_bwd[e + 1]],
+        }
+    }
+
+    fn memory_usage_bytes(&self) -> usize {
+        (self.first_fwd.capacity() + self.first_bwd.capacity()) * size_of::<usize>()
+            + (self.fwd.capacity() + self.bwd.capacity()) * size_of::<(EdgeId, bool)>()
+    }
+}
+
+/// The layout of `FastGraph` before the payloads were added, see `FastGraph::deserialize_from()`
+#[derive(Deserialize)]
+struct FastGraphWithoutPayloads {
+    num_nodes: usize,
+    ranks: Vec<usize>,
+    edges_fwd: Vec<FastGraphEdge>,
+    first_edge_ids_fwd: Vec<EdgeId>,
+    edges_bwd: Vec<FastGraphEdge>,
+    first_edge_ids_bwd: Vec<EdgeId>,
+}
+
+impl From<FastGraphWithoutPayloads> for FastGraph {
+    fn from(graph: FastGraphWithoutPayloads) -> Self {
+        FastGraph {
+            num_nodes: graph.num_nodes,
+            ranks: graph.ranks,
+            edges_fwd: graph.edges_fwd,
+            first_edge_ids_fwd: graph.first_edge_ids_fwd,
+            edges_bwd: graph.edges_bwd,
+            first_edge_ids_bwd: graph.first_edge_ids_bwd,
+            payloads_fwd: vec![],
+            payloads_bwd: vec![],
+            num_core_nodes: 0,
+            generation: next_generation(),
+            shortcut_parents: None,
+        }
+    }
 }
 
 impl FastGraph {
@@ -43,9 +208,62 @@ impl FastGraph {
             first_edge_ids_fwd: vec![0; num_nodes + 1],
             edges_bwd: vec![],
             first_edge_ids_bwd: vec![0; num_nodes + 1],
+            payloads_fwd: vec![],
+            payloads_bwd: vec![],
+            num_core_nodes: 0,
+            generation: next_generation(),
+            shortcut_parents: None,
         }
     }
 
+    /// Builds a graph from its parts, e.g. for tests that need a graph with a specific structure.
+    /// `ranks[node]` is the rank of each node. The edges of each direction must be grouped by the
+    /// rank of their base node, such that the edges of the node with rank `r` are
+    /// `edges[first_edge_ids[r]..first_edge_ids[r + 1]]`, so `first_edge_ids` must be monotonic
+    /// and contain one more entry than there are nodes. The forward edges must point to nodes
+    /// with a higher rank, and the backward edges must come from nodes with a higher rank. All
+    /// edges get a zero payload. Returns an error if the graph is not valid, see `validate()`.
+    #[cfg(any(test, feature = "test-utils"))]
+    pub fn from_parts(
+        ranks: Vec<usize>,
+        edges_fwd: Vec<FastGraphEdge>,
+        first_edge_ids_fwd: Vec<EdgeId>,
+        edges_bwd: Vec<FastGraphEdge>,
+        first_edge_ids_bwd: Vec<EdgeId>,
+    ) -> Result<Self, String> {
+        let graph = FastGraph {
+            num_nodes: ranks.len(),
+            ranks,
+            payloads_fwd: vec![0; edges_fwd.len()],
+            payloads_bwd: vec![0; edges_bwd.len()],
+            edges_fwd,
+            first_edge_ids_fwd,
+            edges_bwd,
+            first_edge_ids_bwd,
+            num_core_nodes: 0,
+            generation: next_generation(),
+            shortcut_parents: None,
+        };
+        graph.validate()?;
+        Ok(graph)
+    }
+
+    /// Reads a graph written by `save_to_disk()`. Graphs that were saved before the edge payloads
+    /// were added are still accepted, all their payloads are zero.
+    pub(crate) fn deserialize_from<R: Read>(mut reader: R) -> bincode::Result<FastGraph> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let error = match bincode::deserialize(&bytes) {
+            Ok(graph) => return Ok(graph),
+            Err(error) => error,
+        };
+        bincode::options()
+            .with_fixint_encoding()
+            .deserialize::<FastGraphWithoutPayloads>(&bytes)
+            .map(FastGraph::from)
+            .map_err(|_| error)
+    }
+
     pub fn get_node_ordering(&self) -> Vec<NodeId> {
         let mut ordering = vec![0; self.ranks.len()];
         for i in 0..self.ranks.len() {
@@ -54,10 +272,61 @@ impl FastGraph {
         ordering
     }
 
+    /// Returns a number that is different for every graph that was prepared or loaded and that
+    /// changes whenever the graph is modified, e.g. by `increase_edge_weight()`. Two graphs with
+    /// the same generation are the same graph in the same state, so results calculated on one of
+    /// them are valid for the other, see `CachedCalculator`.
+    pub(crate) fn get_generation(&self) -> usize {
+        self.generation
+    }
+
     pub fn get_num_nodes(&self) -> usize {
         self.num_nodes
     }
 
+    /// Returns the number of nodes that were not contracted, i.e. the size of the core of graphs
+    /// prepared using `prepare_up_to()`, `prepare_excluding()` or
+    /// `Params::max_shortcuts_per_node`. The core nodes have the highest ranks.
+    pub fn get_num_core_nodes(&self) -> usize {
+        self.num_core_nodes
+    }
+
+    /// Returns the rank of the given node, i.e. its position in the node ordering
+    pub fn get_rank(&self, node: NodeId) -> usize {
+        self.ranks[node]
+    }
+
+    /// Returns the ranks of all nodes, indexed by the node ids
+    pub fn get_ranks(&self) -> &[usize] {
+        &self.ranks
+    }
+
+    /// Returns a mask containing the given nodes and all nodes that can be reached from them
+    /// using the upward edges of the graph in either direction, i.e. the upward search spaces of
+    /// the given nodes. Queries between the given nodes yield the same weights with and without
+    /// this mask, see `PathCalculator::calc_path_masked()`.
+    pub fn calc_upward_closed_mask(&self, nodes: &[NodeId]) -> ValidFlags {
+        let mut mask = ValidFlags::new(self.num_nodes);
+        let mut stack = vec![];
+        for &node in nodes {
+            if !mask.is_valid(node) {
+                mask.set_valid(node);
+                stack.push(node);
+            }
+        }
+        while let Some(node) = stack.pop() {
+            let fwd = self.edges_fwd[self.begin_out_edges(node)..self.end_out_edges(node)].iter();
+            let bwd = self.edges_bwd[self.begin_in_edges(node)..self.end_in_edges(node)].iter();
+            for edge in fwd.chain(bwd) {
+                if !mask.is_valid(edge.adj_node) {
+                    mask.set_valid(edge.adj_node);
+                    stack.push(edge.adj_node);
+                }
+            }
+        }
+        mask
+    }
+
     pub fn get_num_out_edges(&self) -> usize {
         self.edges_fwd.len()
     }
@@ -66,12 +335,103 @@ impl FastGraph {
         self.edges_bwd.len()
     }
 
+    /// Returns the total number of edges (including shortcuts) of the forward and the backward
+    /// graph.
+    pub fn get_num_edges(&self) -> usize {
+        self.get_num_out_edges() + self.get_num_in_edges()
+    }
+
+    /// Returns the number of (upward) edges leaving the given node. The plain
+    /// `get_num_out_edges()` returns this number for all nodes combined.
+    pub fn get_num_out_edges_of(&self, node: NodeId) -> usize {
+        self.end_out_edges(node) - self.begin_out_edges(node)
+    }
+
+    /// Returns the number of (upward) edges entering the given node. The plain
+    /// `get_num_in_edges()` returns this number for all nodes combined.
+    pub fn get_num_in_edges_of(&self, node: NodeId) -> usize {
+        self.end_in_edges(node) - self.begin_in_edges(node)
+    }
+
+    /// Returns a summary of the size of this graph, e.g. for logging
+    pub fn stats(&self) -> GraphStats {
+        GraphStats {
+            num_nodes: self.num_nodes,
+            num_edges: self.get_num_edges(),
+            num_shortcuts: self.iter_shortcuts().count(),
+            max_out_degree: (0..self.num_nodes)
+                .map(|node| self.get_num_out_edges_of(node))
+                .max()
+                .unwrap_or(0),
+            max_in_degree: (0..self.num_nodes)
+                .map(|node| self.get_num_in_edges_of(node))
+                .max()
+                .unwrap_or(0),
+            max_rank: self.ranks.iter().max().copied(),
+        }
+    }
+
+    /// Returns the (approximate) number of bytes of heap and stack memory used by this graph,
+    /// based on the allocated capacities of the internal vectors.
+    pub fn memory_usage_bytes(&self) -> usize {
+        size_of::<FastGraph>()
+            + self.ranks.capacity() * size_of::<usize>()
+            + (self.edges_fwd.capacity() + self.edges_bwd.capacity()) * size_of::<FastGraphEdge>()
+            + (self.first_edge_ids_fwd.capacity() + self.first_edge_ids_bwd.capacity())
+                * size_of::<EdgeId>()
+            + (self.payloads_fwd.capacity() + self.payloads_bwd.capacity()) * size_of::<u64>()
+            + self
+                .shortcut_parents
+                .as_ref()
+                .map_or(0, |parents| parents.memory_usage_bytes())
+    }
+
+    /// Releases the excess capacity of the internal vectors. This is done automatically at the
+    /// end of the preparation, but might still be useful for graphs that were built differently.
+    pub fn shrink_to_fit(&mut self) {
+        self.ranks.shrink_to_fit();
+        self.edges_fwd.shrink_to_fit();
+        self.first_edge_ids_fwd.shrink_to_fit();
+        self.edges_bwd.shrink_to_fit();
+        self.first_edge_ids_bwd.shrink_to_fit();
+        self.payloads_fwd.shrink_to_fit();
+        self.payloads_bwd.shrink_to_fit();
+    }
+
+    /// Reads through all the arrays used by the queries to pull them into the cache (or page
+    /// them in if the graph was mapped into memory) before the first queries are run. The
+    /// returned checksum has no meaning, but using it makes sure the reads are not optimized away.
+    pub fn prewarm(&self) -> usize {
+        let mut checksum: usize = 0;
+        for ids in &[
+            &self.ranks,
+            &self.first_edge_ids_fwd,
+            &self.first_edge_ids_bwd,
+        ] {
+            for id in ids.iter() {
+                checksum = checksum.wrapping_add(read(id));
+            }
+        }
+        for edge in self.edges_fwd.iter().chain(self.edges_bwd.iter()) {
+            checksum = checksum
+                .wrapping_add(read(&edge.adj_node))
+                .wrapping_add(read(&edge.weight));
+        }
+        checksum
+    }
+
     pub fn begin_in_edges(&self, node: NodeId) -> usize {
         self.first_edge_ids_bwd[self.ranks[node]]
     }
 
     pub fn end_in_edges(&self, node: NodeId) -> usize {
-        self.first_edge_ids_bwd[self.ranks[node] + 1]
+        let end = self.first_edge_ids_bwd[self.ranks[node] + 1];
+        debug_assert!(
+            self.begin_in_edges(node) <= end && end <= self.edges_bwd.len(),
+            "invalid in edge range for node {}, are the first edge ids monotonic?",
+            node
+        );
+        end
     }
 
     pub fn begin_out_edges(&self, node: NodeId) -> usize {
@@ -79,8 +439,726 @@ impl FastGraph {
     }
 
     pub fn end_out_edges(&self, node: NodeId) -> usize {
-        self.first_edge_ids_fwd[self.ranks[node] + 1]
+        let end = self.first_edge_ids_fwd[self.ranks[node] + 1];
+        debug_assert!(
+            self.begin_out_edges(node) <= end && end <= self.edges_fwd.len(),
+            "invalid out edge range for node {}, are the first edge ids monotonic?",
+            node
+        );
+        end
+    }
+
+    /// Returns the base edges the given forward edge consists of (only the edge itself if it is
+    /// not a shortcut), in the order they are traversed. Each base edge is given by its id and a
+    /// flag that is true for edges of the forward graph and false for edges of the backward graph.
+    pub fn unpack_shortcut_fwd(&self, edge_id: EdgeId) -> Vec<(EdgeId, bool)> {
+        let mut result = vec![];
+        self.unpack_fwd(&mut result, edge_id, false, &|_, id, fwd| (id, fwd));
+        result
+    }
+
+    /// Like `unpack_shortcut_fwd()`, but for an edge of the backward graph
+    pub fn unpack_shortcut_bwd(&self, edge_id: EdgeId) -> Vec<(EdgeId, bool)> {
+        let mut result = vec![];
+        self.unpack_bwd(&mut result, edge_id, false, &|_, id, fwd| (id, fwd));
+        result
+    }
+
+    /// Returns the number of distinct nodes each node is connected to by (non-shortcut) edges,
+    /// regardless of the edge direction. Edges that were replaced by a shortcut with a lower weight
+    /// during the preparation are not considered, because they are not available anymore.
+    pub fn calc_num_base_neighbors(&self) -> Vec<usize> {
+        let mut pairs: Vec<(NodeId, NodeId)> = self
+            .edges_fwd
+            .iter()
+            .chain(self.edges_bwd.iter())
+            .filter(|e| !e.is_shortcut())
+            .map(|e| (e.base_node.min(e.adj_node), e.base_node.max(e.adj_node)))
+            .collect();
+        pairs.sort_unstable();
+        pairs.dedup();
+        let mut result = vec![0; self.num_nodes];
+        for (a, b) in pairs {
+            result[a] += 1;
+            result[b] += 1;
+        }
+        result
+    }
+
+    /// Returns the lowest weight of the (non-shortcut) edges from `from` to `to`, or `None` if
+    /// there is no such edge.
+    pub(crate) fn get_base_edge_weight(&self, from: NodeId, to: NodeId) -> Option<Weight> {
+        // the edge is stored at the node with the lower rank
+        let (candidates, adj) = if self.ranks[from] < self.ranks[to] {
+            let ids = self.begin_out_edges(from)..self.end_out_edges(from);
+            (&self.edges_fwd[ids], to)
+        } else {
+            let ids = self.begin_in_edges(to)..self.end_in_edges(to);
+            (&self.edges_bwd[ids], from)
+        };
+        candidates
+            .iter()
+            .filter(|e| e.adj_node == adj && !e.is_shortcut())
+            .map(|e| e.weight)
+            .min()
+    }
+
+    /// Returns the (from, to) nodes of the given edge, which is identified like in
+    /// `unpack_shortcut_fwd()`. The edges between core nodes are stored twice, so this is how the
+    /// two copies of such an edge can be matched.
+    pub(crate) fn get_edge_nodes(&self, edge: (EdgeId, bool)) -> (NodeId, NodeId) {
+        match edge {
+            (e, true) => (self.edges_fwd[e].base_node, self.edges_fwd[e].adj_node),
+            (e, false) => (self.edges_bwd[e].adj_node, self.edges_bwd[e].base_node),
+        }
+    }
+
+    /// Returns the base edges leaving `node` as (base edge, adjacent node, weight) tuples. The
+    /// edges leading to nodes with a lower rank are stored at these nodes, so this needs to scan
+    /// all backward edges.
+    pub(crate) fn get_base_out_edges(&self, node: NodeId) -> Vec<((EdgeId, bool), NodeId, Weight)> {
+        let fwd = (self.begin_out_edges(node)..self.end_out_edges(node))
+            .filter(|&e| !self.edges_fwd[e].is_shortcut())
+            .map(|e| {
+                (
+                    (e, true),
+                    self.edges_fwd[e].adj_node,
+                    self.edges_fwd[e].weight,
+                )
+            });
+        let bwd = (0..self.edges_bwd.len())
+            .filter(|&e| self.edges_bwd[e].adj_node == node && !self.edges_bwd[e].is_shortcut())
+            .map(|e| {
+                (
+                    (e, false),
+                    self.edges_bwd[e].base_node,
+                    self.edges_bwd[e].weight,
+                )
+            });
+        fwd.chain(bwd).collect()
+    }
+
+    /// Returns the base edges leading to `node` as (base edge, adjacent node, weight) tuples,
+    /// see `get_base_out_edges()`.
+    pub(crate) fn get_base_in_edges(&self, node: NodeId) -> Vec<((EdgeId, bool), NodeId, Weight)> {
+        let bwd = (self.begin_in_edges(node)..self.end_in_edges(node))
+            .filter(|&e| !self.edges_bwd[e].is_shortcut())
+            .map(|e| {
+                (
+                    (e, false),
+                    self.edges_bwd[e].adj_node,
+                    self.edges_bwd[e].weight,
+                )
+            });
+        let fwd = (0..self.edges_fwd.len())
+            .filter(|&e| self.edges_fwd[e].adj_node == node && !self.edges_fwd[e].is_shortcut())
+            .map(|e| {
+                (
+                    (e, true),
+                    self.edges_fwd[e].base_node,
+                    self.edges_fwd[e].weight,
+                )
+            });
+        bwd.chain(fwd).collect()
+    }
+
+    /// Returns all the edges of this graph that are not shortcuts as (from, to, weight) tuples.
+    /// Like for `calc_num_base_neighbors()` the edges that were replaced by cheaper shortcuts
+    /// during the preparation are missing.
+    pub fn iter_base_edges(&self) -> impl Iterator<Item = (NodeId, NodeId, Weight)> + '_ {
+        self.iter_edges(false)
+    }
+
+    /// Returns all the shortcuts of this graph as (from, to, weight) tuples
+    pub fn iter_shortcuts(&self) -> impl Iterator<Item = (NodeId, NodeId, Weight)> + '_ {
+        self.iter_edges(true)
+    }
+
+    fn iter_edges(&self, shortcuts: bool) -> impl Iterator<Item = (NodeId, NodeId, Weight)> + '_ {
+        let fwd = self
+            .edges_fwd
+            .iter()
+            .filter(move |e| e.is_shortcut() == shortcuts)
+            .map(|e| (e.base_node, e.adj_node, e.weight));
+        // the backward edges point from the adjacent node to the base node
+        let bwd = self
+            .edges_bwd
+            .iter()
+            .filter(move |e| e.is_shortcut() == shortcuts)
+            .map(|e| (e.adj_node, e.base_node, e.weight));
+        fwd.chain(bwd)
+    }
+
+    /// Reconstructs the graph that was prepared from the (non-shortcut) edges of this graph,
+    /// including their payloads. Edges that were replaced by cheaper shortcuts during the
+    /// preparation are missing, see `iter_base_edges()`, but the shortest path weights are the
+    /// same as for the original graph. Use `to_input_graph()` to prepare the graph again, e.g.
+    /// with a different node ordering.
+    pub fn to_preparation_graph(&self) -> PreparationGraph {
+        let mut graph = PreparationGraph::new(self.num_nodes);
+        for (from, to, weight, payload) in self.collect_base_edges() {
+            graph.add_edge_with_payload(from, to, weight, payload);
+        }
+        graph
+    }
+
+    /// Like `to_preparation_graph()`, but returns a (frozen) `InputGraph` that can be passed to
+    /// `prepare()` and the other preparation functions
+    pub fn to_input_graph(&self) -> InputGraph {
+        InputGraph::from_edges_with_payload(self.num_nodes, self.collect_base_edges())
+    }
+
+    /// Returns the base edges as (from, to, weight, payload) tuples, sorted by their nodes. The
+    /// edges between core nodes are stored in both edge lists, so we only keep one edge for each
+    /// pair of nodes.
+    fn collect_base_edges(&self) -> Vec<(NodeId, NodeId, Weight, u64)> {
+        let fwd = self
+            .edges_fwd
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| !e.is_shortcut())
+            .map(|(id, e)| {
+                (
+                    e.base_node,
+                    e.adj_node,
+                    e.weight,
+                    self.get_payload(id, true),
+                )
+            });
+        // the backward edges point from the adjacent node to the base node
+        let bwd = self
+            .edges_bwd
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| !e.is_shortcut())
+            .map(|(id, e)| {
+                (
+                    e.adj_node,
+                    e.base_node,
+                    e.weight,
+                    self.get_payload(id, false),
+                )
+            });
+        let mut edges: Vec<_> = fwd.chain(bwd).collect();
+        edges.sort_unstable();
+        edges.dedup_by_key(|&mut (from, to, _, _)| (from, to));
+        edges
+    }
+
+    /// Returns the graph in the Graphviz DOT format. The nodes are labeled with their id and rank
+    /// and colored from blue (lowest rank) to red (highest rank), base edges are drawn solid and
+    /// shortcuts dashed. If coordinates are given they are used as fixed node positions, which
+    /// works with `neato -n`.
+    #[cfg(feature = "dot")]
+    pub fn to_dot(&self, coords: Option<&[(f64, f64)]>) -> String {
+        use std::fmt::Write;
+        if let Some(coords) = coords {
+            assert_eq!(
+                self.num_nodes,
+                coords.len(),
+                "there must be one coordinate per node"
+            );
+        }
+        let mut result = String::from("digraph {\n");
+        for node in 0..self.num_nodes {
+            let rank = self.ranks[node];
+            // hue 2/3 is blue and 0 is red
+            let hue = 2.0 / 3.0 * (1.0 - rank as f64 / (self.num_nodes - 1).max(1) as f64);
+            write!(
+                result,
+                "  {} [label=\"{} ({})\", style=filled, fillcolor=\"{:.3} 0.5 1.0\"",
+                node, node, rank, hue
+            )
+            .unwrap();
+            if let Some(coords) = coords {
+                write!(result, ", pos=\"{},{}!\"", coords[node].0, coords[node].1).unwrap();
+            }
+            result.push_str("]\n");
+        }
+        for (from, to, weight) in self.iter_base_edges() {
+            writeln!(result, "  {} -> {} [label=\"{}\"]", from, to, weight).unwrap();
+        }
+        for (from, to, weight) in self.iter_shortcuts() {
+            writeln!(
+                result,
+                "  {} -> {} [label=\"{}\", style=dashed]",
+                from, to, weight
+            )
+            .unwrap();
+        }
+        result.push_str("}\n");
+        result
+    }
+
+    /// Returns the payload of the given base edge, or `None` if the edge is a shortcut. The edge
+    /// is identified like in `unpack_shortcut_fwd()`, i.e. by its id and a flag that is true for
+    /// edges of the forward graph.
+    pub fn edge_payload(&self, base_edge: (EdgeId, bool)) -> Option<u64> {
+        let (edge_id, fwd) = base_edge;
+        let edges = if fwd {
+            &self.edges_fwd
+        } else {
+            &self.edges_bwd
+        };
+        if edges[edge_id].is_shortcut() {
+            None
+        } else {
+            Some(self.get_payload(edge_id, fwd))
+        }
+    }
+
+    /// Returns the payload of the given edge, which is zero for shortcuts and for graphs that were
+    /// saved before the payloads were added
+    pub(crate) fn get_payload(&self, edge_id: EdgeId, fwd: bool) -> u64 {
+        let payloads = if fwd {
+            &self.payloads_fwd
+        } else {
+            &self.payloads_bwd
+        };
+        payloads.get(edge_id).copied().unwrap_or(0)
+    }
+
+    /// Maps a base edge, as returned by `unpack_shortcut_fwd()`, to the id of the edge in the
+    /// caller's own data. The edge ids are not derived from the order of the `add_edge()` calls,
+    /// because `InputGraph::freeze()` sorts the edges and removes duplicates. Instead, the id has
+    /// to be given as payload, i.e. by adding the edges using
+    /// `InputGraph::add_edge_with_payload(from, to, weight, id)`. Panics if the edge is a shortcut.
+    pub fn original_edge_id(&self, base_edge: (EdgeId, bool)) -> usize {
+        self.edge_payload(base_edge)
+            .expect("shortcuts have no original edge id") as usize
+    }
+
+    /// Returns all edges (including shortcuts) that lead from a node to a node with a higher
+    /// rank. Together with `downward_graph()` this can be used to implement other query
+    /// algorithms, e.g. a forward search on the upward graph followed by a sweep over the
+    /// downward graph in order of decreasing rank yields the weights from one node to all others.
+    /// This only holds if all nodes were contracted, i.e. not for graphs with a core, see
+    /// `prepare_up_to()`.
+    pub fn upward_graph(&self) -> SearchGraph {
+        let edges = self
+            .edges_fwd
+            .iter()
+            .filter(|e| self.ranks[e.adj_node] > self.ranks[e.base_node])
+            .map(|e| (e.base_node, e.adj_node, e.weight));
+        SearchGraph::from_edges(self.num_nodes, edges)
+    }
+
+    /// Returns all edges (including shortcuts) that lead from a node to a node with a lower rank,
+    /// see `upward_graph()`
+    pub fn downward_graph(&self) -> SearchGraph {
+        // the backward edges are stored at their (lower ranked) target node
+        let edges = self
+            .edges_bwd
+            .iter()
+            .filter(|e| self.ranks[e.adj_node] > self.ranks[e.base_node])
+            .map(|e| (e.adj_node, e.base_node, e.weight));
+        SearchGraph::from_edges(self.num_nodes, edges)
+    }
+
+    /// Converts the edges of this graph to plain `u32` arrays, see `CsrGraph`
+    pub fn to_csr(&self) -> Result<CsrGraph, String> {
+        CsrGraph::from_fast_graph(self)
     }
+
+    /// Checks the internal consistency of this graph, which can be useful after deserializing a
+    /// graph or building one by hand. Returns a description of the first problem that was found.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.ranks.len() != self.num_nodes {
+            return Err(format!(
+                "there are {} ranks, but {} nodes",
+                self.ranks.len(),
+                self.num_nodes
+            ));
+        }
+        let mut seen = vec![false; self.num_nodes];
+        for &rank in &self.ranks {
+            if rank >= self.num_nodes || seen[rank] {
+                return Err(format!(
+                    "ranks are not a permutation, invalid rank: {}",
+                    rank
+                ));
+            }
+            seen[rank] = true;
+        }
+        if self.num_core_nodes > self.num_nodes {
+            return Err(format!(
+                "there are {} core nodes, but only {} nodes",
+                self.num_core_nodes, self.num_nodes
+            ));
+        }
+        let ordering = self.get_node_ordering();
+        let first_core_rank = self.num_nodes - self.num_core_nodes;
+        FastGraph::validate_edges(
+            "forward",
+            &ordering,
+            &self.ranks,
+            first_core_rank,
+            &self.edges_fwd,
+            &self.first_edge_ids_fwd,
+            &self.payloads_fwd,
+        )?;
+        FastGraph::validate_edges(
+            "backward",
+            &ordering,
+            &self.ranks,
+            first_core_rank,
+            &self.edges_bwd,
+            &self.first_edge_ids_bwd,
+            &self.payloads_bwd,
+        )?;
+        self.validate_shortcuts("forward", &self.edges_fwd, |e| (e.base_node, e.adj_node))?;
+        self.validate_shortcuts("backward", &self.edges_bwd, |e| (e.adj_node, e.base_node))
+    }
+
+    fn validate_edges(
+        name: &str,
+        ordering: &[NodeId],
+        ranks: &[usize],
+        first_core_rank: usize,
+        edges: &[FastGraphEdge],
+        first_edge_ids: &[EdgeId],
+        payloads: &[u64],
+    ) -> Result<(), String> {
+        if first_edge_ids.len() != ordering.len() + 1 {
+            return Err(format!(
+                "there are {} first {} edge ids, but expected {}",
+                first_edge_ids.len(),
+                name,
+                ordering.len() + 1
+            ));
+        }
+        if first_edge_ids[0] != 0 || first_edge_ids[ordering.len()] != edges.len() {
+            return Err(format!(
+                "first {} edge ids must start at zero and end at the number of edges",
+                name
+            ));
+        }
+        if !payloads.is_empty() && payloads.len() != edges.len() {
+            return Err(format!(
+                "there are {} {} edges, but {} payloads",
+                edges.len(),
+                name,
+                payloads.len()
+            ));
+        }
+        if first_edge_ids.windows(2).any(|w| w[0] > w[1]) {
+            return Err(format!("first {} edge ids are not monotonic", name));
+        }
+        for (rank, &node) in ordering.iter().enumerate() {
+            let begin = first_edge_ids[rank];
+            for (i, edge) in edges[begin..first_edge_ids[rank + 1]].iter().enumerate() {
+                if edge.base_node != node {
+                    return Err(format!(
+                        "{} edge {} has base node {}, but is stored at node {}",
+                        name,
+                        begin + i,
+                        edge.base_node,
+                        node
+                    ));
+                }
+                if edge.adj_node >= ordering.len() {
+                    return Err(format!(
+                        "{} edge {} leads to an invalid node",
+                        name,
+                        begin + i
+                    ));
+                }
+                // the edges between core nodes can also lead to nodes with a lower rank
+                let adj_rank = ranks[edge.adj_node];
+                let is_core_edge = rank >= first_core_rank && adj_rank >= first_core_rank;
+                if adj_rank == rank || (adj_rank < rank && !is_core_edge) {
+                    return Err(format!(
+                        "{} edge {} does not lead to a node with higher rank",
+                        name,
+                        begin + i
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that each shortcut replaces an in-edge and an out-edge of its center node which
+    /// connect the same nodes as the shortcut. `from_to` returns the nodes an edge connects.
+    fn validate_shortcuts<F>(
+        &self,
+        name: &str,
+        edges: &[FastGraphEdge],
+        from_to: F,
+    ) -> Result<(), String>
+    where
+        F: Fn(&FastGraphEdge) -> (NodeId, NodeId),
+    {
+        for (edge_id, edge) in edges.iter().enumerate() {
+            let in_edge = edge.replaced_in_edge;
+            let out_edge = edge.replaced_out_edge;
+            if in_edge == INVALID_EDGE && out_edge == INVALID_EDGE {
+                continue;
+            }
+            if in_edge >= self.edges_bwd.len() || out_edge >= self.edges_fwd.len() {
+                return Err(format!(
+                    "{} edge {} references replaced edges that do not exist",
+                    name, edge_id
+                ));
+            }
+            let (from, to) = from_to(edge);
+            let in_edge = &self.edges_bwd[in_edge];
+            let out_edge = &self.edges_fwd[out_edge];
+            if in_edge.base_node != out_edge.base_node
+                || in_edge.adj_node != from
+                || out_edge.adj_node != to
+            {
+                return Err(format!(
+                    "{} edge {} does not match the edges it replaces",
+                    name, edge_id
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Updates this graph after edges were added to the graph it was prepared from, or some of its
+    /// edges got cheaper. `input` is the changed graph and `dirty` must contain both nodes of every
+    /// added or changed edge. The edges whose weight was increased or that were removed are not
+    /// supported, use `prepare_with_order()` for these.
+    ///
+    /// The witness searches of a node can run through any node with a higher rank, so all nodes
+    /// starting from the lowest rank of the dirty nodes are contracted again, in the same order.
+    /// The shortcuts of the nodes with lower ranks are kept. The result has the same shortest path
+    /// weights as a new preparation of `input`, but it is only faster if the dirty nodes have high
+    /// ranks. Panics if `input` has a different number of nodes or a dirty node is invalid.
+    pub fn recontract_nodes(&mut self, input: &PreparationGraph, dirty: &[NodeId]) {
+        let graph = std::mem::replace(self, FastGraph::new(0));
+        *self = FastGraphBuilder::recontract(graph, input, dirty);
+        self.generation = next_generation();
+        self.shortcut_parents = None;
+    }
+
+    /// Increases the weight of the given base edge, e.g. to model a temporary slowdown, and
+    /// updates the weights of all shortcuts that (indirectly) replace this edge. The base edge is
+    /// identified like in `unpack_shortcut_fwd()`. Returns the number of updated shortcuts. Only
+    /// these shortcuts are visited, but the first call builds an index of the shortcuts replacing
+    /// each edge, which takes time linear in the number of edges.
+    ///
+    /// This is much faster than preparing the graph again, but the shortcuts themselves are not
+    /// changed. The queries still return valid paths with correct weights, but not necessarily
+    /// the shortest ones: a shortcut that was omitted during the preparation, because there was a
+    /// witness path using this edge, might be needed with the new weight. Use
+    /// `prepare_with_order()` to get exact results for the new weights.
+    pub fn increase_edge_weight(&mut self, base_edge: (EdgeId, bool), new_weight: Weight) -> usize {
+        let (edge_id, fwd) = base_edge;
+        let edges = if fwd {
+            &self.edges_fwd
+        } else {
+            &self.edges_bwd
+        };
+        assert!(
+            edge_id < edges.len() && !edges[edge_id].is_shortcut(),
+            "only the weights of base edges can be increased, invalid edge: {:?}",
+            base_edge
+        );
+        let edge = &edges[edge_id];
+        assert!(
+            new_weight >= edge.weight,
+            "the new weight {} is smaller than the current weight {}",
+            new_weight,
+            edge.weight
+        );
+        let (from, to) = if fwd {
+            (edge.base_node, edge.adj_node)
+        } else {
+            (edge.adj_node, edge.base_node)
+        };
+        let old_weight = edge.weight;
+        self.generation = next_generation();
+        // edges between two nodes of the core are stored in both directions, see `prepare_up_to()`
+        let copy = if fwd {
+            (self.begin_in_edges(to)..self.end_in_edges(to))
+                .find(|&e| {
+                    let e = &self.edges_bwd[e];
+                    e.adj_node == from && !e.is_shortcut() && e.weight == old_weight
+                })
+                .map(|e| (e, false))
+        } else {
+            (self.begin_out_edges(from)..self.end_out_edges(from))
+                .find(|&e| {
+                    let e = &self.edges_fwd[e];
+                    e.adj_node == to && !e.is_shortcut() && e.weight == old_weight
+                })
+                .map(|e| (e, true))
+        };
+        let changed: Vec<_> = std::iter::once(base_edge).chain(copy).collect();
+        for &(e, fwd) in &changed {
+            if fwd {
+                self.edges_fwd[e].weight = new_weight;
+            } else {
+                self.edges_bwd[e].weight = new_weight;
+            }
+        }
+        let shortcuts = self.find_replacing_shortcuts(&changed);
+        for &(id, fwd) in &shortcuts {
+            let edge = if fwd {
+                &self.edges_fwd[id]
+            } else {
+                &self.edges_bwd[id]
+            };
+            let weight = add_weights(
+                self.edges_bwd[edge.replaced_in_edge].weight,
+                self.edges_fwd[edge.replaced_out_edge].weight,
+            );
+            if fwd {
+                self.edges_fwd[id].weight = weight;
+            } else {
+                self.edges_bwd[id].weight = weight;
+            }
+        }
+        shortcuts.len()
+    }
+
+    /// Returns the shortcuts that (indirectly) replace any of the given edges, ordered such that
+    /// every shortcut comes after the shortcuts it replaces. Only these shortcuts are visited.
+    fn find_replacing_shortcuts(&mut self, edges: &[(EdgeId, bool)]) -> Vec<(EdgeId, bool)> {
+        if self.shortcut_parents.is_none() {
+            self.shortcut_parents = Some(ShortcutParents::new(self));
+        }
+        let parents = self.shortcut_parents.as_ref().unwrap();
+        let center_rank = |(id, fwd): (EdgeId, bool)| {
+            if fwd {
+                self.get_center_rank(&self.edges_fwd[id])
+            } else {
+                self.get_center_rank(&self.edges_bwd[id])
+            }
+        };
+        // the center node of a shortcut has a lower rank than the center nodes of all shortcuts the
+        // shortcut is part of, so we walk upwards by taking the shortcuts in this order. A shortcut
+        // is only pushed for the edges it replaces, which come first, so duplicates come in a row
+        let mut heap = BinaryHeap::new();
+        for &edge in edges {
+            heap.extend(
+                parents
+                    .get(edge)
+                    .iter()
+                    .map(|&s| Reverse((center_rank(s), s))),
+            );
+        }
+        let mut result: Vec<(EdgeId, bool)> = vec![];
+        while let Some(Reverse((_, shortcut))) = heap.pop() {
+            if result.last() == Some(&shortcut) {
+                continue;
+            }
+            result.push(shortcut);
+            heap.extend(
+                parents
+                    .get(shortcut)
+                    .iter()
+                    .map(|&s| Reverse((center_rank(s), s))),
+            );
+        }
+        result
+    }
+
+    /// Checks that the weight of every shortcut equals the sum of the weights of the two edges it
+    /// replaces, i.e. that unpacking a shortcut does not change the weight of a path. Returns the
+    /// shortcuts that violate this as (edge id, forward) tuples. Shortcuts that reference edges
+    /// that do not exist are reported as well, use `validate()` to check the graph structure.
+    pub fn check_shortcut_symmetry(&self) -> Result<(), Vec<(EdgeId, bool)>> {
+        let mut invalid = vec![];
+        let edges = self
+            .edges_fwd
+            .iter()
+            .enumerate()
+            .map(|(id, e)| (id, true, e))
+            .chain(
+                self.edges_bwd
+                    .iter()
+                    .enumerate()
+                    .map(|(id, e)| (id, false, e)),
+            );
+        for (edge_id, fwd, edge) in edges {
+            if !edge.is_shortcut() {
+                continue;
+            }
+            let weights = self
+                .edges_bwd
+                .get(edge.replaced_in_edge)
+                .zip(self.edges_fwd.get(edge.replaced_out_edge))
+                .map(|(in_edge, out_edge)| add_weights(in_edge.weight, out_edge.weight));
+            if weights != Some(edge.weight) {
+                invalid.push((edge_id, fwd));
+            }
+        }
+        if invalid.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid)
+        }
+    }
+
+    pub(crate) fn unpack_fwd<T, F>(&self, items: &mut Vec<T>, edge_id: EdgeId, reverse: bool, f: &F)
+    where
+        F: Fn(&FastGraph, EdgeId, bool) -> T,
+    {
+        self.unpack_down_to(items, (edge_id, true), reverse, 0, f);
+    }
+
+    pub(crate) fn unpack_bwd<T, F>(&self, items: &mut Vec<T>, edge_id: EdgeId, reverse: bool, f: &F)
+    where
+        F: Fn(&FastGraph, EdgeId, bool) -> T,
+    {
+        self.unpack_down_to(items, (edge_id, false), reverse, 0, f);
+    }
+
+    /// Unpacks the given edge, which is given as (edge id, forward) tuple, and adds an item for
+    /// each of the resulting edges using `f`, in reverse order if `reverse` is true. Shortcuts
+    /// whose center node has a rank below `min_rank` are not unpacked. Since the center node of a
+    /// shortcut has a lower rank than the center nodes of the shortcuts it replaces, these would
+    /// only yield nodes with even lower ranks. The shortcuts can be nested very deeply, so this
+    /// uses an explicit stack instead of recursion.
+    pub(crate) fn unpack_down_to<T, F>(
+        &self,
+        items: &mut Vec<T>,
+        edge: (EdgeId, bool),
+        reverse: bool,
+        min_rank: usize,
+        f: &F,
+    ) where
+        F: Fn(&FastGraph, EdgeId, bool) -> T,
+    {
+        // the edges still to be unpacked, the next one is at the end
+        let mut stack = vec![edge];
+        while let Some((edge_id, fwd)) = stack.pop() {
+            let edge = if fwd {
+                &self.edges_fwd[edge_id]
+            } else {
+                &self.edges_bwd[edge_id]
+            };
+            if !edge.is_shortcut() || (min_rank > 0 && self.get_center_rank(edge) < min_rank) {
+                items.push(f(self, edge_id, fwd));
+                continue;
+            }
+            let in_edge = (edge.replaced_in_edge, false);
+            let out_edge = (edge.replaced_out_edge, true);
+            if reverse {
+                stack.push(in_edge);
+                stack.push(out_edge);
+            } else {
+                stack.push(out_edge);
+                stack.push(in_edge);
+            }
+        }
+    }
+
+    /// Returns the rank of the node the given shortcut skips, which is where the edges it
+    /// replaces are stored
+    fn get_center_rank(&self, shortcut: &FastGraphEdge) -> usize {
+        self.ranks[self.edges_bwd[shortcut.replaced_in_edge].base_node]
+    }
+}
+
+/// Reads the given value such that the compiler cannot skip the read
+fn read(value: &usize) -> usize {
+    // safe, because references are always valid and aligned
+    unsafe { std::ptr::read_volatile(value) }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -119,6 +1197,609 @@ impl FastGraphEdge {
                 || (self.replaced_in_edge != INVALID_EDGE
                     && self.replaced_out_edge != INVALID_EDGE)
         );
-        return self.replaced_in_edge != INVALID_EDGE;
+        self.replaced_in_edge != INVALID_EDGE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BinaryHeap;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use crate::constants::WEIGHT_MAX;
+    use crate::dijkstra::Dijkstra;
+    use crate::heap_item::HeapItem;
+    use crate::path_calculator::PathCalculator;
+    use crate::test_utils::{assert_query_equivalent, random_input_graph};
+    use crate::{prepare, prepare_up_to, prepare_with_order};
+
+    use super::*;
+
+    #[test]
+    fn re_prepare() {
+        let g = random_input_graph(200, 2.5, 30, 9);
+        let n = g.get_num_nodes();
+        for fast_graph in [prepare(&g), prepare_up_to(&g, 0.5)] {
+            let input_graph = fast_graph.to_input_graph();
+            assert_eq!(n, input_graph.get_num_nodes());
+            assert_query_equivalent(&fast_graph, &prepare(&input_graph), 300, 1);
+            let order: Vec<NodeId> = (0..n).rev().collect();
+            let reordered = prepare_with_order(&input_graph, &order).unwrap();
+            assert_query_equivalent(&fast_graph, &reordered, 300, 2);
+
+            let preparation_graph = fast_graph.to_preparation_graph();
+            assert_eq!(n, preparation_graph.get_num_nodes());
+            let mut dijkstra = Dijkstra::new(n);
+            let mut calc = PathCalculator::new(n);
+            let mut rng = StdRng::seed_from_u64(3);
+            for _ in 0..300 {
+                let s = rng.gen_range(0, n);
+                let t = rng.gen_range(0, n);
+                assert_eq!(
+                    calc.calc_weight(&fast_graph, s, t),
+                    dijkstra
+                        .calc_path(&preparation_graph, s, t)
+                        .map(|p| p.get_weight())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn re_prepare_keeps_payloads_and_isolated_nodes() {
+        // node 4 has no edges, and 0 -> 2 is cheaper than the shortcut via node 1
+        let edges = vec![(0, 1, 3, 10), (1, 2, 4, 11), (2, 0, 5, 12), (0, 2, 6, 13)];
+        let g = InputGraph::from_edges_with_payload(5, edges);
+        let fast_graph = prepare_with_order(&g, &[1, 0, 2, 3, 4]).unwrap();
+        let input_graph = fast_graph.to_input_graph();
+        assert_eq!(5, input_graph.get_num_nodes());
+        assert_eq!(
+            g.unit_test_output_string(),
+            input_graph.unit_test_output_string()
+        );
+        let payloads: Vec<u64> = input_graph.get_edges().iter().map(|e| e.payload).collect();
+        assert_eq!(vec![10, 13, 11, 12], payloads);
+    }
+
+    #[test]
+    fn upward_and_downward_graph() {
+        let g = random_input_graph(150, 2.5, 20, 8);
+        let n = g.get_num_nodes();
+        let fast_graph = prepare(&g);
+        let ranks = fast_graph.get_ranks();
+        let upward = fast_graph.upward_graph();
+        let downward = fast_graph.downward_graph();
+        assert_eq!(n, upward.get_num_nodes());
+        assert_eq!(fast_graph.get_num_out_edges(), upward.get_num_edges());
+        assert_eq!(fast_graph.get_num_in_edges(), downward.get_num_edges());
+        for node in 0..n {
+            assert!(upward
+                .get_out_edges(node)
+                .all(|(adj, _)| ranks[adj] > ranks[node]));
+            assert!(downward
+                .get_out_edges(node)
+                .all(|(adj, _)| ranks[adj] < ranks[node]));
+        }
+        // one-to-all queries using the two graphs must yield the same weights as the queries
+        let mut calc = PathCalculator::new(n);
+        for start in 0..n {
+            let mut weights = vec![WEIGHT_MAX; n];
+            weights[start] = 0;
+            let mut heap = BinaryHeap::new();
+            heap.push(HeapItem::new(0, start));
+            while let Some(curr) = heap.pop() {
+                if curr.weight > weights[curr.node_id] {
+                    continue;
+                }
+                for (adj, weight) in upward.get_out_edges(curr.node_id) {
+                    if curr.weight + weight < weights[adj] {
+                        weights[adj] = curr.weight + weight;
+                        heap.push(HeapItem::new(weights[adj], adj));
+                    }
+                }
+            }
+            for node in fast_graph.get_node_ordering().into_iter().rev() {
+                if weights[node] == WEIGHT_MAX {
+                    continue;
+                }
+                for (adj, weight) in downward.get_out_edges(node) {
+                    weights[adj] = weights[adj].min(weights[node] + weight);
+                }
+            }
+            for (end, &weight) in weights.iter().enumerate() {
+                let expected = calc.calc_weight(&fast_graph, start, end);
+                assert_eq!(expected.unwrap_or(WEIGHT_MAX), weight);
+            }
+        }
+    }
+
+    #[test]
+    fn recontract_nodes() {
+        let g = random_input_graph(200, 2.5, 30, 11);
+        let n = g.get_num_nodes();
+        let mut rng = StdRng::seed_from_u64(5);
+        for fraction in [1.0, 0.5] {
+            let mut fast_graph = prepare_up_to(&g, fraction);
+            // add cheap edges between some high ranked nodes
+            let dirty: Vec<NodeId> = fast_graph.get_node_ordering()[150..]
+                .iter()
+                .copied()
+                .filter(|_| rng.gen_bool(0.3))
+                .collect();
+            // the edges below the dirty nodes and the core are kept
+            let min_rank = dirty.iter().map(|&node| fast_graph.get_rank(node)).min();
+            let first_core_rank = n - fast_graph.get_num_core_nodes();
+            let num_kept_edges =
+                fast_graph.first_edge_ids_fwd[min_rank.unwrap().min(first_core_rank)];
+            let kept_edges = bincode::serialize(&fast_graph.edges_fwd[..num_kept_edges]).unwrap();
+            let mut preparation_graph = PreparationGraph::from_input_graph(&g);
+            for &from in &dirty {
+                for &to in &dirty {
+                    if from != to && rng.gen_bool(0.5) {
+                        preparation_graph.add_edge(from, to, rng.gen_range(0, 5));
+                    }
+                }
+            }
+            fast_graph.recontract_nodes(&preparation_graph, &dirty);
+            assert!(fast_graph.validate().is_ok());
+            assert_eq!(
+                kept_edges,
+                bincode::serialize(&fast_graph.edges_fwd[..num_kept_edges]).unwrap()
+            );
+
+            let mut dijkstra = Dijkstra::new(n);
+            let mut calc = PathCalculator::new(n);
+            for _ in 0..300 {
+                let s = rng.gen_range(0, n);
+                let t = rng.gen_range(0, n);
+                assert_eq!(
+                    dijkstra
+                        .calc_path(&preparation_graph, s, t)
+                        .map(|p| p.get_weight()),
+                    calc.calc_weight(&fast_graph, s, t)
+                );
+            }
+        }
+        // no dirty nodes, nothing changes
+        let mut fast_graph = prepare(&g);
+        let before = bincode::serialize(&fast_graph).unwrap();
+        fast_graph.recontract_nodes(&PreparationGraph::from_input_graph(&g), &[]);
+        assert_eq!(before, bincode::serialize(&fast_graph).unwrap());
+    }
+
+    #[test]
+    fn increase_edge_weight() {
+        // 0 -> 1 -> 2 -> 3 -> 4
+        let mut g = InputGraph::new();
+        for node in 0..4 {
+            g.add_edge(node, node + 1, 1);
+        }
+        g.freeze();
+        let mut fast_graph = prepare(&g);
+        let (base_edge, _, _) = fast_graph.get_base_out_edges(2)[0];
+        fast_graph.increase_edge_weight(base_edge, 10);
+        assert_eq!(Ok(()), fast_graph.check_shortcut_symmetry());
+        let mut calc = PathCalculator::new(5);
+        assert_eq!(Some(13), calc.calc_weight(&fast_graph, 0, 4));
+        assert_eq!(Some(11), calc.calc_weight(&fast_graph, 1, 3));
+        assert_eq!(Some(1), calc.calc_weight(&fast_graph, 0, 1));
+    }
+
+    #[test]
+    fn increase_edge_weight_random() {
+        let g = random_input_graph(100, 2.5, 20, 12);
+        let n = g.get_num_nodes();
+        let mut fast_graph = prepare(&g);
+        let mut calc = PathCalculator::new(n);
+        let mut num_updated = 0;
+        for node in (0..n).step_by(7) {
+            if let Some(&(base_edge, _, weight)) = fast_graph.get_base_out_edges(node).first() {
+                num_updated += fast_graph.increase_edge_weight(base_edge, weight + 15);
+            }
+        }
+        assert!(num_updated > 0);
+        assert_eq!(Ok(()), fast_graph.check_shortcut_symmetry());
+        // the paths might not be the shortest ones, but their weights must be right
+        for start in 0..n {
+            for end in (0..n).step_by(5) {
+                if let Some(path) = calc.calc_path(&fast_graph, start, end) {
+                    let weight: Weight = path.segment_weights(&fast_graph).iter().sum();
+                    assert_eq!(path.get_weight(), weight);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn increase_edge_weight_visits_only_replacing_shortcuts() {
+        let g = random_input_graph(100, 2.5, 20, 13);
+        let mut fast_graph = prepare(&g);
+        let shortcuts: Vec<_> = (0..fast_graph.edges_fwd.len())
+            .map(|e| (e, true))
+            .chain((0..fast_graph.edges_bwd.len()).map(|e| (e, false)))
+            .filter(|&(e, fwd)| match fwd {
+                true => fast_graph.edges_fwd[e].is_shortcut(),
+                false => fast_graph.edges_bwd[e].is_shortcut(),
+            })
+            .collect();
+        let mut num_visited = 0;
+        for node in 0..g.get_num_nodes() {
+            for (base_edge, _, _) in fast_graph.get_base_out_edges(node) {
+                let mut expected: Vec<_> = shortcuts
+                    .iter()
+                    .copied()
+                    .filter(|&(e, fwd)| match fwd {
+                        true => fast_graph.unpack_shortcut_fwd(e).contains(&base_edge),
+                        false => fast_graph.unpack_shortcut_bwd(e).contains(&base_edge),
+                    })
+                    .collect();
+                let mut visited = fast_graph.find_replacing_shortcuts(&[base_edge]);
+                // every shortcut must come after the shortcuts it replaces
+                let center_ranks: Vec<_> = visited
+                    .iter()
+                    .map(|&(e, fwd)| match fwd {
+                        true => fast_graph.get_center_rank(&fast_graph.edges_fwd[e]),
+                        false => fast_graph.get_center_rank(&fast_graph.edges_bwd[e]),
+                    })
+                    .collect();
+                assert!(center_ranks.windows(2).all(|w| w[0] <= w[1]));
+                num_visited += visited.len();
+                expected.sort_unstable();
+                visited.sort_unstable();
+                assert_eq!(expected, visited);
+            }
+        }
+        assert!(num_visited > 0);
+    }
+
+    #[test]
+    fn validate_prepared_graph() {
+        // 0 -> 1 -> 2 -> 3
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 3);
+        g.add_edge(1, 2, 4);
+        g.add_edge(2, 3, 1);
+        g.freeze();
+        let fast_graph = prepare(&g);
+        assert_eq!(Ok(()), fast_graph.validate());
+    }
+
+    #[test]
+    fn validate_hand_built_graph() {
+        // 0 -> 1 -> 2, with node 1 contracted first
+        let mut g = FastGraph::new(3);
+        g.ranks = vec![1, 0, 2];
+        g.edges_fwd
+            .push(FastGraphEdge::new(1, 2, 4, INVALID_EDGE, INVALID_EDGE));
+        g.edges_fwd.push(FastGraphEdge::new(0, 2, 7, 0, 0));
+        g.edges_bwd
+            .push(FastGraphEdge::new(1, 0, 3, INVALID_EDGE, INVALID_EDGE));
+        g.first_edge_ids_fwd = vec![0, 1, 2, 2];
+        g.first_edge_ids_bwd = vec![0, 1, 1, 1];
+        g.payloads_fwd = vec![0, 0];
+        g.payloads_bwd = vec![0];
+        assert_eq!(Ok(()), g.validate());
+
+        g.first_edge_ids_bwd = vec![0, 1, 0, 1];
+        assert!(g.validate().is_err());
+        g.first_edge_ids_bwd = vec![0, 1, 1, 1];
+
+        g.edges_fwd[1].replaced_in_edge = 5;
+        assert!(g.validate().is_err());
+        g.edges_fwd[1].replaced_in_edge = 0;
+
+        g.edges_fwd[1].adj_node = 1;
+        assert!(g.validate().is_err());
+        g.edges_fwd[1].adj_node = 2;
+
+        g.ranks = vec![1, 1, 2];
+        assert!(g.validate().is_err());
+    }
+
+    #[test]
+    fn from_parts() {
+        // 0 -> 1 -> 2, with node 1 contracted first
+        let edges_fwd = || {
+            vec![
+                FastGraphEdge::new(1, 2, 4, INVALID_EDGE, INVALID_EDGE),
+                FastGraphEdge::new(0, 2, 7, 0, 0),
+            ]
+        };
+        let edges_bwd = || vec![FastGraphEdge::new(1, 0, 3, INVALID_EDGE, INVALID_EDGE)];
+        let g = FastGraph::from_parts(
+            vec![1, 0, 2],
+            edges_fwd(),
+            vec![0, 1, 2, 2],
+            edges_bwd(),
+            vec![0, 1, 1, 1],
+        )
+        .unwrap();
+        assert_eq!(3, g.get_num_nodes());
+        assert_eq!(vec![1, 0, 2], g.get_node_ordering());
+        let path = crate::calc_path(&g, 0, 2).unwrap();
+        assert_eq!(&vec![0, 1, 2], path.get_nodes());
+        assert_eq!(7, path.get_weight());
+
+        let result = FastGraph::from_parts(
+            vec![1, 0, 2],
+            edges_fwd(),
+            vec![0, 2, 1, 2],
+            edges_bwd(),
+            vec![0, 1, 1, 1],
+        );
+        assert_eq!(
+            Some("first forward edge ids are not monotonic".to_string()),
+            result.err()
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "invalid out edge range for node 0")]
+    fn non_monotonic_first_edge_ids() {
+        let mut g = FastGraph::new(2);
+        g.ranks = vec![0, 1];
+        g.edges_fwd
+            .push(FastGraphEdge::new(0, 1, 3, INVALID_EDGE, INVALID_EDGE));
+        g.first_edge_ids_fwd = vec![1, 0, 1];
+        g.end_out_edges(0);
+    }
+
+    #[test]
+    fn memory_usage() {
+        let g = FastGraph::new(10);
+        // ranks, first_edge_ids_fwd and first_edge_ids_bwd
+        let expected = size_of::<FastGraph>() + (10 + 11 + 11) * size_of::<usize>();
+        assert_eq!(expected, g.memory_usage_bytes());
+        let mut g = g;
+        g.edges_fwd
+            .push(FastGraphEdge::new(0, 1, 3, INVALID_EDGE, INVALID_EDGE));
+        assert!(g.memory_usage_bytes() >= expected + size_of::<FastGraphEdge>());
+    }
+
+    #[test]
+    fn iter_edges() {
+        // 0 -> 1 -> 2 -> 3
+        //  \--------/
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 3);
+        g.add_edge(1, 2, 4);
+        g.add_edge(2, 3, 1);
+        g.add_edge(0, 2, 9);
+        g.freeze();
+        let fast_graph = prepare_with_order(&g, &[1, 2, 0, 3]).unwrap();
+        let mut base_edges: Vec<_> = fast_graph.iter_base_edges().collect();
+        base_edges.sort_unstable();
+        assert_eq!(vec![(0, 1, 3), (1, 2, 4), (2, 3, 1)], base_edges);
+        // the edge 0->2 was replaced by the shortcut via node 1
+        let mut shortcuts: Vec<_> = fast_graph.iter_shortcuts().collect();
+        shortcuts.sort_unstable();
+        assert_eq!(vec![(0, 2, 7), (0, 3, 8)], shortcuts);
+    }
+
+    #[test]
+    fn edge_counts() {
+        // 0 -> 1 -> 2 -> 3
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 3);
+        g.add_edge(1, 2, 4);
+        g.add_edge(2, 3, 1);
+        g.freeze();
+        let fast_graph = prepare_with_order(&g, &[1, 2, 0, 3]).unwrap();
+        // the shortcuts 0->2 and 0->3 are added when contracting nodes 1 and 2
+        let out_edges: Vec<_> = (0..4).map(|n| fast_graph.get_num_out_edges_of(n)).collect();
+        let in_edges: Vec<_> = (0..4).map(|n| fast_graph.get_num_in_edges_of(n)).collect();
+        assert_eq!(vec![1, 1, 1, 0], out_edges);
+        assert_eq!(vec![0, 1, 1, 0], in_edges);
+        assert_eq!(3, fast_graph.get_num_out_edges());
+        assert_eq!(2, fast_graph.get_num_in_edges());
+        assert_eq!(5, fast_graph.get_num_edges());
+    }
+
+    #[test]
+    fn stats() {
+        // 0 -> 1 -> 2, contracting node 1 first yields the shortcut 0->2
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 3);
+        g.add_edge(1, 2, 4);
+        g.freeze();
+        let fast_graph = prepare_with_order(&g, &[1, 0, 2]).unwrap();
+        assert_eq!(
+            GraphStats {
+                num_nodes: 3,
+                num_edges: 3,
+                num_shortcuts: 1,
+                max_out_degree: 1,
+                max_in_degree: 1,
+                max_rank: Some(2),
+            },
+            fast_graph.stats()
+        );
+        assert_eq!(None, FastGraph::new(0).stats().max_rank);
+    }
+
+    #[test]
+    fn check_shortcut_symmetry() {
+        // 0 -> 1 -> 2, with node 1 contracted first
+        let edges_bwd = || vec![FastGraphEdge::new(1, 0, 3, INVALID_EDGE, INVALID_EDGE)];
+        let g = FastGraph::from_parts(
+            vec![1, 0, 2],
+            vec![
+                FastGraphEdge::new(1, 2, 4, INVALID_EDGE, INVALID_EDGE),
+                FastGraphEdge::new(0, 2, 7, 0, 0),
+            ],
+            vec![0, 1, 2, 2],
+            edges_bwd(),
+            vec![0, 1, 1, 1],
+        )
+        .unwrap();
+        assert_eq!(Ok(()), g.check_shortcut_symmetry());
+        let g = FastGraph::from_parts(
+            vec![1, 0, 2],
+            vec![
+                FastGraphEdge::new(1, 2, 4, INVALID_EDGE, INVALID_EDGE),
+                FastGraphEdge::new(0, 2, 6, 0, 0),
+            ],
+            vec![0, 1, 2, 2],
+            edges_bwd(),
+            vec![0, 1, 1, 1],
+        )
+        .unwrap();
+        assert_eq!(Err(vec![(1, true)]), g.check_shortcut_symmetry());
+
+        let g = InputGraph::from_edges(20, (0..19).map(|n| (n, n + 1, n as Weight + 1)));
+        let fast_graph = prepare(&g);
+        assert!(fast_graph.iter_shortcuts().count() > 0);
+        assert_eq!(Ok(()), fast_graph.check_shortcut_symmetry());
+    }
+
+    #[cfg(feature = "dot")]
+    #[test]
+    fn to_dot() {
+        // 0 -> 1 -> 2, contracting node 1 first yields the shortcut 0->2
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 3);
+        g.add_edge(1, 2, 4);
+        g.freeze();
+        let fast_graph = prepare_with_order(&g, &[1, 0, 2]).unwrap();
+        let expected = "digraph {
+  0 [label=\"0 (1)\", style=filled, fillcolor=\"0.333 0.5 1.0\"]
+  1 [label=\"1 (0)\", style=filled, fillcolor=\"0.667 0.5 1.0\"]
+  2 [label=\"2 (2)\", style=filled, fillcolor=\"0.000 0.5 1.0\"]
+  1 -> 2 [label=\"4\"]
+  0 -> 1 [label=\"3\"]
+  0 -> 2 [label=\"7\", style=dashed]
+}
+";
+        assert_eq!(expected, fast_graph.to_dot(None));
+        let coords = [(0.0, 0.0), (1.5, 0.0), (3.0, 1.0)];
+        assert!(fast_graph.to_dot(Some(&coords)).contains(
+            "  1 [label=\"1 (0)\", style=filled, fillcolor=\"0.667 0.5 1.0\", pos=\"1.5,0!\"]"
+        ));
+    }
+
+    #[test]
+    fn original_edge_ids() {
+        // a 3x3 grid with some diagonals, the edges are added in an arbitrary order
+        let edges = vec![
+            (4, 5, 2),
+            (0, 1, 3),
+            (8, 7, 1),
+            (1, 2, 2),
+            (3, 4, 4),
+            (6, 7, 1),
+            (0, 3, 2),
+            (5, 8, 3),
+            (1, 4, 1),
+            (2, 5, 2),
+            (3, 6, 3),
+            (4, 7, 2),
+            (0, 4, 5),
+            (4, 8, 4),
+        ];
+        let mut g = InputGraph::new();
+        for (id, &(from, to, weight)) in edges.iter().enumerate() {
+            g.add_edge_with_payload(from, to, weight, id as u64);
+            g.add_edge_with_payload(to, from, weight, (edges.len() + id) as u64);
+        }
+        g.freeze();
+        // contracting the center first forces some shortcuts
+        let fast_graph = prepare_with_order(&g, &[4, 1, 3, 5, 7, 0, 2, 6, 8]).unwrap();
+        assert!(fast_graph.iter_shortcuts().count() > 0);
+        let original = |id: usize| {
+            let (from, to, weight) = edges[id % edges.len()];
+            if id < edges.len() {
+                (from, to, weight)
+            } else {
+                (to, from, weight)
+            }
+        };
+        for fwd in &[true, false] {
+            let edges = if *fwd {
+                &fast_graph.edges_fwd
+            } else {
+                &fast_graph.edges_bwd
+            };
+            for (edge_id, edge) in edges.iter().enumerate() {
+                let base_edges = if *fwd {
+                    fast_graph.unpack_shortcut_fwd(edge_id)
+                } else {
+                    fast_graph.unpack_shortcut_bwd(edge_id)
+                };
+                let ids: Vec<usize> = base_edges
+                    .into_iter()
+                    .map(|e| fast_graph.original_edge_id(e))
+                    .collect();
+                // the original edges form a path with the same end points and weight
+                let (from, to) = if *fwd {
+                    (edge.base_node, edge.adj_node)
+                } else {
+                    (edge.adj_node, edge.base_node)
+                };
+                assert_eq!(from, original(ids[0]).0);
+                assert_eq!(to, original(*ids.last().unwrap()).1);
+                for pair in ids.windows(2) {
+                    assert_eq!(original(pair[0]).1, original(pair[1]).0);
+                }
+                let weight: Weight = ids.iter().map(|&id| original(id).2).sum();
+                assert_eq!(edge.weight, weight);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "shortcuts have no original edge id")]
+    fn original_edge_id_of_shortcut() {
+        // 0 -> 1 -> 2
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 3);
+        g.add_edge(1, 2, 4);
+        g.freeze();
+        let fast_graph = prepare_with_order(&g, &[1, 0, 2]).unwrap();
+        let shortcut = (0..fast_graph.get_num_out_edges())
+            .find(|&e| fast_graph.edges_fwd[e].is_shortcut())
+            .unwrap();
+        fast_graph.original_edge_id((shortcut, true));
+    }
+
+    #[test]
+    fn prewarm() {
+        // 0 -> 1 -> 2
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 3);
+        g.add_edge(1, 2, 4);
+        g.freeze();
+        let fast_graph = prepare_with_order(&g, &[0, 1, 2]).unwrap();
+        // both edges point upwards, so there are only forward edges
+        // ranks 0+1+2, first edge ids 0+1+2+2 and 0+0+0+0, adj nodes 1+2 and weights 3+4
+        assert_eq!(3 + 5 + 3 + 7, fast_graph.prewarm());
+    }
+
+    #[test]
+    fn shrink_to_fit() {
+        let mut g = FastGraph::new(2);
+        let before = g.memory_usage_bytes();
+        g.edges_fwd.reserve(100);
+        assert!(g.memory_usage_bytes() > before);
+        g.shrink_to_fit();
+        assert_eq!(before, g.memory_usage_bytes());
+    }
+
+    #[test]
+    fn prepared_graph_has_no_excess_capacity() {
+        let mut g = InputGraph::new();
+        for i in 0..50 {
+            g.add_edge_bidir(i, i + 1, 1);
+        }
+        g.freeze();
+        let fast_graph = prepare(&g);
+        assert_eq!(fast_graph.edges_fwd.len(), fast_graph.edges_fwd.capacity());
+        assert_eq!(fast_graph.edges_bwd.len(), fast_graph.edges_bwd.capacity());
+        assert_eq!(
+            fast_graph.payloads_fwd.len(),
+            fast_graph.payloads_fwd.capacity()
+        );
     }
 }
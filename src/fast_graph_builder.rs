@@ -17,26 +17,30 @@
  * under the License.
  */
 
-use std::cmp::max;
+use std::cmp::{max, min, Reverse};
 use std::collections::BTreeSet;
 
 use priority_queue::PriorityQueue;
 
-use crate::constants::Weight;
 use crate::constants::{EdgeId, NodeId, INVALID_EDGE, INVALID_NODE};
+#[cfg(feature = "witness-report")]
+use crate::contraction_report::ContractionReport;
 use crate::fast_graph::FastGraphEdge;
 
-use super::dijkstra::Dijkstra;
+use super::dijkstra::{Dijkstra, DijkstraDial};
 use super::fast_graph::FastGraph;
 use super::input_graph::InputGraph;
-use super::preparation_graph::PreparationGraph;
+use super::preparation_graph::{Arc, PreparationGraph};
 use crate::node_contractor;
+use crate::queue::Queue;
 
 pub struct FastGraphBuilder {
     fast_graph: FastGraph,
     num_nodes: usize,
     center_nodes_fwd: Vec<NodeId>,
     center_nodes_bwd: Vec<NodeId>,
+    #[cfg(feature = "witness-report")]
+    report: ContractionReport,
 }
 
 impl FastGraphBuilder {
@@ -46,6 +50,8 @@ impl FastGraphBuilder {
             num_nodes: input_graph.get_num_nodes(),
             center_nodes_fwd: vec![],
             center_nodes_bwd: vec![],
+            #[cfg(feature = "witness-report")]
+            report: ContractionReport::new(),
         }
     }
 
@@ -54,132 +60,456 @@ impl FastGraphBuilder {
     }
 
     pub fn build_with_params(input_graph: &InputGraph, params: &Params) -> FastGraph {
+        let mut queue = NodeQueue::default();
+        if params.dial_witness_search {
+            let mut dijkstra = DijkstraDial::with_queue(input_graph.get_num_nodes(), 0);
+            FastGraphBuilder::build_with_scratch(input_graph, params, &mut dijkstra, &mut queue)
+        } else {
+            let mut dijkstra = Dijkstra::new(input_graph.get_num_nodes());
+            FastGraphBuilder::build_with_scratch(input_graph, params, &mut dijkstra, &mut queue)
+        }
+    }
+
+    /// Prepares all the given graphs, but unlike calling `build()` for each of them the witness
+    /// search and the priority queue are only allocated once, which is faster for many small
+    /// graphs.
+    pub fn build_many(input_graphs: &[InputGraph], params: &Params) -> Vec<FastGraph> {
+        if params.dial_witness_search {
+            FastGraphBuilder::build_many_with_scratch(
+                input_graphs,
+                params,
+                DijkstraDial::with_queue(0, 0),
+            )
+        } else {
+            FastGraphBuilder::build_many_with_scratch(input_graphs, params, Dijkstra::new(0))
+        }
+    }
+
+    fn build_many_with_scratch<Q: Queue>(
+        input_graphs: &[InputGraph],
+        params: &Params,
+        mut dijkstra: Dijkstra<Q>,
+    ) -> Vec<FastGraph> {
+        let mut queue = NodeQueue::default();
+        input_graphs
+            .iter()
+            .map(|input_graph| {
+                dijkstra.reset(input_graph.get_num_nodes());
+                queue.clear();
+                FastGraphBuilder::build_with_scratch(input_graph, params, &mut dijkstra, &mut queue)
+            })
+            .collect()
+    }
+
+    fn build_with_scratch<Q: Queue>(
+        input_graph: &InputGraph,
+        params: &Params,
+        dijkstra: &mut Dijkstra<Q>,
+        queue: &mut NodeQueue,
+    ) -> FastGraph {
+        #[cfg(feature = "tracing")]
+        let span = FastGraphBuilder::prepare_span(input_graph);
+        let mut builder = FastGraphBuilder::new(input_graph);
+        let num_nodes = builder.num_nodes;
+        builder.run_contraction(input_graph, params, dijkstra, queue, num_nodes, &[]);
+        #[cfg(feature = "tracing")]
+        FastGraphBuilder::record_num_edges(&span, &builder.fast_graph);
+        builder.fast_graph
+    }
+
+    /// Like `build_with_params()`, but also returns a report of all witness searches run during
+    /// the preparation
+    #[cfg(feature = "witness-report")]
+    pub fn build_with_report(
+        input_graph: &InputGraph,
+        params: &Params,
+    ) -> (FastGraph, ContractionReport) {
+        let mut queue = NodeQueue::default();
+        let mut builder = FastGraphBuilder::new(input_graph);
+        let num_nodes = builder.num_nodes;
+        builder.run_contraction_with_params(input_graph, params, &mut queue, num_nodes, &[]);
+        (builder.fast_graph, builder.report)
+    }
+
+    /// Like `build_with_params()`, but only contracts the given fraction of the nodes. The
+    /// remaining nodes form the 'core' of the graph: they get the highest ranks and keep all their
+    /// edges in both the forward and backward edge lists, so the queries run a plain
+    /// (bidirectional) Dijkstra search once they reach the core.
+    pub fn build_up_to(input_graph: &InputGraph, params: &Params, fraction: f32) -> FastGraph {
+        assert!(
+            (0.0..=1.0).contains(&fraction),
+            "the fraction of contracted nodes must be in [0, 1], but was {}",
+            fraction
+        );
+        let mut queue = NodeQueue::default();
         let mut builder = FastGraphBuilder::new(input_graph);
-        builder.run_contraction(input_graph, params);
+        let num_contracted = (fraction * builder.num_nodes as f32) as usize;
+        builder.run_contraction_with_params(input_graph, params, &mut queue, num_contracted, &[]);
         builder.fast_graph
     }
 
+    /// Like `build_with_params()`, but never contracts the given nodes. They form the 'core' of
+    /// the graph, just like the remaining nodes of `build_up_to()`. Returns an error if any of the
+    /// nodes is not a valid node id.
+    pub fn build_excluding(
+        input_graph: &InputGraph,
+        params: &Params,
+        never_contract: &[NodeId],
+    ) -> Result<FastGraph, String> {
+        if let Some(&node) = never_contract
+            .iter()
+            .find(|&&node| node >= input_graph.get_num_nodes())
+        {
+            return Err(format!("Invalid node id: {}", node));
+        }
+        let mut core = never_contract.to_vec();
+        core.sort_unstable();
+        core.dedup();
+        #[cfg(feature = "tracing")]
+        let span = FastGraphBuilder::prepare_span(input_graph);
+        let mut queue = NodeQueue::default();
+        let mut builder = FastGraphBuilder::new(input_graph);
+        let num_contracted = builder.num_nodes - core.len();
+        builder.run_contraction_with_params(input_graph, params, &mut queue, num_contracted, &core);
+        #[cfg(feature = "tracing")]
+        FastGraphBuilder::record_num_edges(&span, &builder.fast_graph);
+        Ok(builder.fast_graph)
+    }
+
     pub fn build_with_order(
         input_graph: &InputGraph,
-        order: &Vec<NodeId>,
+        order: &[NodeId],
     ) -> Result<FastGraph, String> {
         if input_graph.get_num_nodes() != order.len() {
             return Err(String::from(
                 "The given order must have as many nodes as the input graph",
             ));
         }
+        #[cfg(feature = "tracing")]
+        let span = FastGraphBuilder::prepare_span(input_graph);
         let mut builder = FastGraphBuilder::new(input_graph);
         builder.run_contraction_with_order(input_graph, order);
+        #[cfg(feature = "tracing")]
+        FastGraphBuilder::record_num_edges(&span, &builder.fast_graph);
         Ok(builder.fast_graph)
     }
 
-    fn run_contraction(&mut self, input_graph: &InputGraph, params: &Params) {
-        let mut preparation_graph = PreparationGraph::from_input_graph(input_graph);
-        let mut dijkstra = Dijkstra::new(self.num_nodes);
-        let mut levels = vec![0; self.num_nodes];
-        let mut queue = PriorityQueue::new();
-        for node in 0..self.num_nodes {
-            let priority = -node_contractor::calc_relevance(
-                &mut preparation_graph,
+    #[cfg(feature = "tracing")]
+    fn prepare_span(input_graph: &InputGraph) -> tracing::span::EnteredSpan {
+        tracing::info_span!(
+            "prepare",
+            num_nodes = input_graph.get_num_nodes(),
+            num_edges = input_graph.get_num_edges(),
+            num_out_edges = tracing::field::Empty,
+            num_in_edges = tracing::field::Empty,
+        )
+        .entered()
+    }
+
+    #[cfg(feature = "tracing")]
+    fn record_num_edges(span: &tracing::span::EnteredSpan, fast_graph: &FastGraph) {
+        span.record("num_out_edges", fast_graph.get_num_out_edges());
+        span.record("num_in_edges", fast_graph.get_num_in_edges());
+    }
+
+    /// Like `run_contraction()`, but creates the witness search according to the given params
+    fn run_contraction_with_params(
+        &mut self,
+        input_graph: &InputGraph,
+        params: &Params,
+        queue: &mut NodeQueue,
+        num_contracted: usize,
+        core: &[NodeId],
+    ) {
+        if params.dial_witness_search {
+            let mut dijkstra = DijkstraDial::with_queue(self.num_nodes, 0);
+            self.run_contraction(
+                input_graph,
                 params,
                 &mut dijkstra,
-                node,
-                0,
+                queue,
+                num_contracted,
+                core,
+            );
+        } else {
+            let mut dijkstra = Dijkstra::new(self.num_nodes);
+            self.run_contraction(
+                input_graph,
+                params,
+                &mut dijkstra,
+                queue,
+                num_contracted,
+                core,
             );
-            queue.push(node, priority as Weight);
+        }
+    }
+
+    /// Contracts the first `num_contracted` nodes taken from the queue. The remaining nodes of the
+    /// queue, the given `core` nodes, which are never put into the queue, and the nodes that
+    /// would need too many shortcuts (see `Params::max_shortcuts_per_node`) form the core.
+    fn run_contraction<Q: Queue>(
+        &mut self,
+        input_graph: &InputGraph,
+        params: &Params,
+        dijkstra: &mut Dijkstra<Q>,
+        queue: &mut NodeQueue,
+        num_contracted: usize,
+        core: &[NodeId],
+    ) {
+        let mut preparation_graph = PreparationGraph::from_input_graph(input_graph);
+        let mut levels = vec![0; self.num_nodes];
+        let mut is_core = vec![false; self.num_nodes];
+        core.iter().for_each(|&node| is_core[node] = true);
+        let mut core_nodes = core.to_vec();
+        for node in (0..self.num_nodes).filter(|&node| !is_core[node]) {
+            let priority = calc_priority(&mut preparation_graph, params, dijkstra, node, 0);
+            queue.push(node, priority);
         }
         let mut rank = 0;
-        while !queue.is_empty() {
-            let node = queue.pop().unwrap().0;
-            let mut neighbors = BTreeSet::new();
-            for out_edge in &preparation_graph.out_edges[node] {
-                neighbors.insert(out_edge.adj_node);
-                self.fast_graph.edges_fwd.push(FastGraphEdge::new(
-                    node,
-                    out_edge.adj_node,
-                    out_edge.weight,
-                    INVALID_EDGE,
-                    INVALID_EDGE,
-                ));
-                self.center_nodes_fwd.push(out_edge.center_node);
-            }
-            self.fast_graph.first_edge_ids_fwd[rank + 1] = self.fast_graph.get_num_out_edges();
-
-            for in_edge in &preparation_graph.in_edges[node] {
-                neighbors.insert(in_edge.adj_node);
-                self.fast_graph.edges_bwd.push(FastGraphEdge::new(
-                    node,
-                    in_edge.adj_node,
-                    in_edge.weight,
-                    INVALID_EDGE,
-                    INVALID_EDGE,
-                ));
-                self.center_nodes_bwd.push(in_edge.center_node)
+        loop {
+            // the order of the core nodes does not matter, because they are not contracted
+            let (node, contract) = if queue.is_empty() {
+                match core_nodes.pop() {
+                    Some(node) => (node, false),
+                    None => break,
+                }
+            } else if rank < num_contracted {
+                let node = pop_node(queue, &mut preparation_graph, params, dijkstra, &levels);
+                if params.max_shortcuts_per_node.map_or(false, |max| {
+                    node_contractor::count_shortcuts(&mut preparation_graph, dijkstra, node) > max
+                }) {
+                    is_core[node] = true;
+                    core_nodes.push(node);
+                    continue;
+                }
+                (node, true)
+            } else {
+                (queue.pop().unwrap().0, false)
+            };
+            self.push_edges(&preparation_graph, node, rank);
+            rank += 1;
+            if !contract {
+                self.fast_graph.num_core_nodes += 1;
+                continue;
             }
-            self.fast_graph.first_edge_ids_bwd[rank + 1] = self.fast_graph.get_num_in_edges();
-
-            self.fast_graph.ranks[rank] = node;
-            node_contractor::contract_node(&mut preparation_graph, &mut dijkstra, node);
+            let neighbors: BTreeSet<NodeId> = preparation_graph.out_edges[node]
+                .iter()
+                .chain(&preparation_graph.in_edges[node])
+                .map(|edge| edge.adj_node)
+                .collect();
+            self.contract_node(&mut preparation_graph, dijkstra, node);
             for neighbor in neighbors {
                 levels[neighbor] = max(levels[neighbor], levels[node] + 1);
-                let priority = -node_contractor::calc_relevance(
+                if params.lazy_updates || is_core[neighbor] {
+                    continue;
+                }
+                let priority = calc_priority(
                     &mut preparation_graph,
                     params,
-                    &mut dijkstra,
+                    dijkstra,
                     neighbor,
                     levels[neighbor],
-                ) as Weight;
+                );
                 queue.change_priority(&neighbor, priority);
             }
-            //            println!("contracted node {} / {}, num edges fwd: {}, num edges bwd: {}", rank+1, self.num_nodes, self.fast_graph.get_num_out_edges(), self.fast_graph.get_num_in_edges());
-            rank += 1;
+            //            println!("contracted node {} / {}, num edges fwd: {}, num edges bwd: {}", rank, self.num_nodes, self.fast_graph.get_num_out_edges(), self.fast_graph.get_num_in_edges());
         }
         self.finish_contraction();
     }
 
-    fn run_contraction_with_order(&mut self, input_graph: &InputGraph, order: &Vec<NodeId>) {
+    fn run_contraction_with_order(&mut self, input_graph: &InputGraph, order: &[NodeId]) {
         let mut preparation_graph = PreparationGraph::from_input_graph(input_graph);
         let mut dijkstra = Dijkstra::new(self.num_nodes);
-        for rank in 0..order.len() {
-            let node = order[rank];
+        for (rank, &node) in order.iter().enumerate() {
             if node >= self.num_nodes {
                 panic!("Order contains invalid node id: {}", node);
             }
-            for out_edge in &preparation_graph.out_edges[node] {
-                self.fast_graph.edges_fwd.push(FastGraphEdge::new(
-                    node,
-                    out_edge.adj_node,
-                    out_edge.weight,
-                    INVALID_EDGE,
-                    INVALID_EDGE,
-                ));
-                self.center_nodes_fwd.push(out_edge.center_node);
+            self.push_edges(&preparation_graph, node, rank);
+            self.contract_node(&mut preparation_graph, &mut dijkstra, node);
+            //            println!("contracted node {} / {}, num edges fwd: {}, num edges bwd: {}", rank+1, self.num_nodes, self.fast_graph.get_num_out_edges(), self.fast_graph.get_num_in_edges());
+        }
+        self.finish_contraction();
+    }
+
+    /// Contracts the nodes again, starting from the lowest rank of the given dirty nodes. The
+    /// edges stored at the nodes with lower ranks are kept. See `FastGraph::recontract_nodes()`.
+    pub fn recontract(
+        fast_graph: FastGraph,
+        input_graph: &PreparationGraph,
+        dirty: &[NodeId],
+    ) -> FastGraph {
+        let num_nodes = fast_graph.get_num_nodes();
+        assert_eq!(
+            num_nodes,
+            input_graph.get_num_nodes(),
+            "the input graph must have as many nodes as the prepared graph"
+        );
+        if let Some(&node) = dirty.iter().find(|&&node| node >= num_nodes) {
+            panic!("Invalid node id: {}", node);
+        }
+        let min_rank = match dirty.iter().map(|&node| fast_graph.get_rank(node)).min() {
+            // the core nodes are connected to all their neighbors in the core, regardless of
+            // their ranks, so we always rebuild the whole core
+            Some(min_rank) => min(min_rank, num_nodes - fast_graph.get_num_core_nodes()),
+            None => return fast_graph,
+        };
+        let order = fast_graph.get_node_ordering();
+        let preparation_graph =
+            FastGraphBuilder::create_remaining_graph(&fast_graph, input_graph, &order, min_rank);
+
+        let mut builder = FastGraphBuilder {
+            center_nodes_fwd: (0..fast_graph.first_edge_ids_fwd[min_rank])
+                .map(|e| FastGraphBuilder::get_center_node(&fast_graph, &fast_graph.edges_fwd[e]))
+                .collect(),
+            center_nodes_bwd: (0..fast_graph.first_edge_ids_bwd[min_rank])
+                .map(|e| FastGraphBuilder::get_center_node(&fast_graph, &fast_graph.edges_bwd[e]))
+                .collect(),
+            fast_graph,
+            num_nodes,
+            #[cfg(feature = "witness-report")]
+            report: ContractionReport::new(),
+        };
+        let graph = &mut builder.fast_graph;
+        // graphs saved before the payloads were added have none
+        graph.payloads_fwd.resize(graph.edges_fwd.len(), 0);
+        graph.payloads_bwd.resize(graph.edges_bwd.len(), 0);
+        graph.edges_fwd.truncate(graph.first_edge_ids_fwd[min_rank]);
+        graph
+            .payloads_fwd
+            .truncate(graph.first_edge_ids_fwd[min_rank]);
+        graph.edges_bwd.truncate(graph.first_edge_ids_bwd[min_rank]);
+        graph
+            .payloads_bwd
+            .truncate(graph.first_edge_ids_bwd[min_rank]);
+        graph.ranks.clone_from(&order);
+
+        builder.run_recontraction(preparation_graph, &order, min_rank);
+        builder.fast_graph
+    }
+
+    /// Builds the graph that remains after contracting the nodes with a rank below `min_rank`:
+    /// the edges of the input graph between the remaining nodes and the shortcuts between them
+    /// that skip a contracted node.
+    fn create_remaining_graph(
+        fast_graph: &FastGraph,
+        input_graph: &PreparationGraph,
+        order: &[NodeId],
+        min_rank: usize,
+    ) -> PreparationGraph {
+        let is_remaining = |node: NodeId| fast_graph.get_rank(node) >= min_rank;
+        let mut input_edges: Vec<(NodeId, &Arc)> = order[min_rank..]
+            .iter()
+            .flat_map(|&node| input_graph.out_edges[node].iter().map(move |e| (node, e)))
+            .filter(|(_, e)| is_remaining(e.adj_node))
+            .collect();
+        // only keep the cheapest of parallel edges, like `InputGraph::freeze()` does
+        input_edges.sort_by_key(|(_, e)| e.weight);
+        let mut graph = PreparationGraph::new(fast_graph.get_num_nodes());
+        for (from, edge) in input_edges {
+            if !graph.contains_edge(from, edge.adj_node) {
+                graph.add_edge_with_payload(from, edge.adj_node, edge.weight, edge.payload);
             }
-            self.fast_graph.first_edge_ids_fwd[rank + 1] = self.fast_graph.get_num_out_edges();
-
-            for in_edge in &preparation_graph.in_edges[node] {
-                self.fast_graph.edges_bwd.push(FastGraphEdge::new(
-                    node,
-                    in_edge.adj_node,
-                    in_edge.weight,
-                    INVALID_EDGE,
-                    INVALID_EDGE,
-                ));
-                self.center_nodes_bwd.push(in_edge.center_node)
+        }
+        for &node in &order[min_rank..] {
+            let out_edges = (fast_graph.begin_out_edges(node)..fast_graph.end_out_edges(node))
+                .map(|e| &fast_graph.edges_fwd[e])
+                .map(|e| (e.base_node, e.adj_node, e));
+            let in_edges = (fast_graph.begin_in_edges(node)..fast_graph.end_in_edges(node))
+                .map(|e| &fast_graph.edges_bwd[e])
+                .map(|e| (e.adj_node, e.base_node, e));
+            for (from, to, edge) in out_edges.chain(in_edges) {
+                let center = FastGraphBuilder::get_center_node(fast_graph, edge);
+                if center != INVALID_NODE
+                    && fast_graph.get_rank(center) < min_rank
+                    && is_remaining(from)
+                    && is_remaining(to)
+                {
+                    graph.add_or_reduce_edge(from, to, edge.weight, center);
+                }
             }
-            self.fast_graph.first_edge_ids_bwd[rank + 1] = self.fast_graph.get_num_in_edges();
+        }
+        graph
+    }
 
-            self.fast_graph.ranks[rank] = node;
-            node_contractor::contract_node(&mut preparation_graph, &mut dijkstra, node);
-            //            println!("contracted node {} / {}, num edges fwd: {}, num edges bwd: {}", rank+1, self.num_nodes, self.fast_graph.get_num_out_edges(), self.fast_graph.get_num_in_edges());
+    /// Continues the contraction in the given order, starting at `min_rank`. Like for
+    /// `run_contraction()` the core nodes are not contracted.
+    fn run_recontraction(
+        &mut self,
+        mut preparation_graph: PreparationGraph,
+        order: &[NodeId],
+        min_rank: usize,
+    ) {
+        let first_core_rank = self.num_nodes - self.fast_graph.get_num_core_nodes();
+        let mut dijkstra = Dijkstra::new(self.num_nodes);
+        for (rank, &node) in order.iter().enumerate().skip(min_rank) {
+            self.push_edges(&preparation_graph, node, rank);
+            if rank < first_core_rank {
+                self.contract_node(&mut preparation_graph, &mut dijkstra, node);
+            }
         }
         self.finish_contraction();
     }
 
+    fn get_center_node(fast_graph: &FastGraph, edge: &FastGraphEdge) -> NodeId {
+        if edge.is_shortcut() {
+            fast_graph.edges_bwd[edge.replaced_in_edge].base_node
+        } else {
+            INVALID_NODE
+        }
+    }
+
+    /// Adds the current edges of the given node, which gets the given rank
+    fn push_edges(&mut self, preparation_graph: &PreparationGraph, node: NodeId, rank: usize) {
+        for out_edge in &preparation_graph.out_edges[node] {
+            self.fast_graph.edges_fwd.push(FastGraphEdge::new(
+                node,
+                out_edge.adj_node,
+                out_edge.weight,
+                INVALID_EDGE,
+                INVALID_EDGE,
+            ));
+            self.center_nodes_fwd.push(out_edge.center_node);
+            self.fast_graph.payloads_fwd.push(out_edge.payload);
+        }
+        self.fast_graph.first_edge_ids_fwd[rank + 1] = self.fast_graph.get_num_out_edges();
+
+        for in_edge in &preparation_graph.in_edges[node] {
+            self.fast_graph.edges_bwd.push(FastGraphEdge::new(
+                node,
+                in_edge.adj_node,
+                in_edge.weight,
+                INVALID_EDGE,
+                INVALID_EDGE,
+            ));
+            self.center_nodes_bwd.push(in_edge.center_node);
+            self.fast_graph.payloads_bwd.push(in_edge.payload);
+        }
+        self.fast_graph.first_edge_ids_bwd[rank + 1] = self.fast_graph.get_num_in_edges();
+
+        self.fast_graph.ranks[rank] = node;
+    }
+
+    fn contract_node<Q: Queue>(
+        &mut self,
+        preparation_graph: &mut PreparationGraph,
+        dijkstra: &mut Dijkstra<Q>,
+        node: NodeId,
+    ) {
+        #[cfg(feature = "witness-report")]
+        node_contractor::contract_node_with_report(
+            preparation_graph,
+            dijkstra,
+            node,
+            &mut self.report,
+        );
+        #[cfg(not(feature = "witness-report"))]
+        node_contractor::contract_node(preparation_graph, dijkstra, node);
+    }
+
     fn finish_contraction(&mut self) {
         let ranks_copy = self.fast_graph.ranks.clone();
-        for i in 0..ranks_copy.len() {
-            self.fast_graph.ranks[ranks_copy[i]] = i;
+        for (i, &node) in ranks_copy.iter().enumerate() {
+            self.fast_graph.ranks[node] = i;
         }
 
         for i in 0..self.num_nodes {
@@ -210,6 +540,7 @@ impl FastGraphBuilder {
                 }
             }
         }
+        self.fast_graph.shrink_to_fit();
     }
 
     fn get_out_edge_id(&self, node: NodeId, adj_node: NodeId) -> EdgeId {
@@ -234,6 +565,19 @@ impl FastGraphBuilder {
 pub struct Params {
     pub hierarchy_depth_factor: f32,
     pub edge_quotient_factor: f32,
+    /// If true the priorities of the neighbors of a contracted node are not updated right away,
+    /// but only once a node reaches the top of the queue, which usually speeds up the preparation
+    pub lazy_updates: bool,
+    /// If true the witness searches use Dial's bucket queue instead of a binary heap, see
+    /// `DijkstraDial`. This can be faster when the edge weights are small integers.
+    pub dial_witness_search: bool,
+    /// If set, nodes whose contraction would add more than this number of shortcuts are not
+    /// contracted, but become part of the 'core' of the graph like the remaining nodes of
+    /// `FastGraphBuilder::build_up_to()`. This limits the number of shortcuts and thus the memory
+    /// needed for the preparation and the prepared graph. The queries still return shortest
+    /// paths, but they run a plain Dijkstra search within the core, so they get slower the more
+    /// nodes are kept uncontracted.
+    pub max_shortcuts_per_node: Option<usize>,
 }
 
 impl Params {
@@ -241,20 +585,77 @@ impl Params {
         Params {
             hierarchy_depth_factor: ratio,
             edge_quotient_factor: 1.0,
+            lazy_updates: false,
+            dial_witness_search: false,
+            max_shortcuts_per_node: None,
         }
     }
+}
 
-    pub fn default() -> Self {
+/// The priority used for the contraction queue, nodes with higher priority are contracted first.
+/// Ties are broken by the node id, so the node ordering does not depend on the internals of the
+/// queue and the prepared graph is reproducible.
+fn calc_priority<Q: Queue>(
+    graph: &mut PreparationGraph,
+    params: &Params,
+    dijkstra: &mut Dijkstra<Q>,
+    node: NodeId,
+    level: usize,
+) -> Priority {
+    let relevance = node_contractor::calc_relevance(graph, params, dijkstra, node, level);
+    (-(relevance as i64), Reverse(node))
+}
+
+type Priority = (i64, Reverse<NodeId>);
+
+/// The queue of the nodes that are not contracted yet. The `fx-hash` feature replaces the default
+/// SipHash of its index map, which is slow for integer keys.
+type NodeQueue = PriorityQueue<NodeId, Priority, NodeHasher>;
+
+#[cfg(not(feature = "fx-hash"))]
+type NodeHasher = std::collections::hash_map::RandomState;
+
+#[cfg(feature = "fx-hash")]
+type NodeHasher = std::hash::BuildHasherDefault<crate::fx_hasher::FxHasher>;
+
+impl Default for Params {
+    fn default() -> Self {
         Params::new(0.1)
     }
 }
 
+/// Returns the next node that should be contracted. With lazy updates the priority of the
+/// node at the top of the queue is recalculated and the node is only returned if it still has
+/// the highest priority, otherwise it is put back into the queue.
+fn pop_node<Q: Queue>(
+    queue: &mut NodeQueue,
+    preparation_graph: &mut PreparationGraph,
+    params: &Params,
+    dijkstra: &mut Dijkstra<Q>,
+    levels: &[usize],
+) -> NodeId {
+    loop {
+        let node = queue.pop().unwrap().0;
+        if !params.lazy_updates {
+            return node;
+        }
+        let priority = calc_priority(preparation_graph, params, dijkstra, node, levels[node]);
+        match queue.peek() {
+            Some((_, &next_priority)) if priority < next_priority => {
+                queue.push(node, priority);
+            }
+            _ => return node,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::constants::Weight;
     use crate::shortest_path::ShortestPath;
 
     use super::*;
-    use crate::{calc_path, prepare_with_order};
+    use crate::{calc_path, calc_weight, create_calculator, prepare, prepare_with_order};
 
     #[test]
     fn calc_path_linear_bwd_only() {
@@ -263,7 +664,7 @@ mod tests {
         g.add_edge(2, 0, 9);
         g.add_edge(0, 1, 49);
         g.freeze();
-        let fast_graph = prepare_with_order(&g, &vec![0, 1, 2]).unwrap();
+        let fast_graph = prepare_with_order(&g, &[0, 1, 2]).unwrap();
         assert_path(&fast_graph, 2, 1, 58, vec![2, 0, 1]);
     }
 
@@ -274,8 +675,9 @@ mod tests {
         g.add_edge(1, 0, 9);
         g.add_edge(0, 2, 49);
         g.freeze();
-        let fast_graph = prepare_with_order(&g, &vec![0, 1, 2]).unwrap();
+        let fast_graph = prepare_with_order(&g, &[0, 1, 2]).unwrap();
         assert_path(&fast_graph, 1, 2, 58, vec![1, 0, 2]);
+        assert_eq!(None, calc_weight(&fast_graph, 2, 1));
     }
 
     #[test]
@@ -291,7 +693,7 @@ mod tests {
         g.add_edge(0, 4, 2);
         g.freeze();
 
-        let fast_graph = prepare_with_order(&g, &vec![0, 1, 2, 3, 4]).unwrap();
+        let fast_graph = prepare_with_order(&g, &[0, 1, 2, 3, 4]).unwrap();
         assert_path(&fast_graph, 0, 4, 2, vec![0, 4]);
         assert_path(&fast_graph, 4, 0, 16, vec![4, 3, 2, 1, 0]);
         assert_path(&fast_graph, 1, 4, 7, vec![1, 0, 4]);
@@ -315,8 +717,164 @@ mod tests {
         g.add_edge(4, 2, 4);
         g.freeze();
 
-        let fast_graph = prepare_with_order(&g, &vec![0, 1, 2, 3, 4]).unwrap();
+        let fast_graph = prepare_with_order(&g, &[0, 1, 2, 3, 4]).unwrap();
         assert_path(&fast_graph, 4, 3, 15, vec![4, 2, 1, 3]);
+        assert_eq!(Some(15), calc_weight(&fast_graph, 4, 3));
+        assert_eq!(Some(0), calc_weight(&fast_graph, 4, 4));
+    }
+
+    #[test]
+    fn calc_path_unchecked() {
+        // 0 -> 1 -> 2
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 3);
+        g.add_edge(1, 2, 4);
+        g.freeze();
+        let fast_graph = prepare_with_order(&g, &[1, 0, 2]).unwrap();
+        let mut calc = create_calculator(&fast_graph);
+        for source in 0..3 {
+            for target in 0..3 {
+                assert_eq!(
+                    calc.calc_path(&fast_graph, source, target),
+                    calc.calc_path_unchecked(&fast_graph, source, target)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unpack_shortcut() {
+        // 0 -> 1 -> 2
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 3);
+        g.add_edge(1, 2, 4);
+        g.freeze();
+        let fast_graph = prepare_with_order(&g, &[1, 0, 2]).unwrap();
+        // contracting node 1 yields the shortcut 0 -> 2
+        let shortcut = fast_graph.begin_out_edges(0);
+        assert!(fast_graph.edges_fwd[shortcut].is_shortcut());
+        assert_eq!(7, fast_graph.edges_fwd[shortcut].weight);
+        let base_edges = fast_graph.unpack_shortcut_fwd(shortcut);
+        assert_eq!(2, base_edges.len());
+        // 0 -> 1 is stored as an in-edge of node 1, 1 -> 2 as an out-edge of node 1
+        assert_eq!((fast_graph.begin_in_edges(1), false), base_edges[0]);
+        assert_eq!((fast_graph.begin_out_edges(1), true), base_edges[1]);
+        // base edges unpack to themselves
+        assert_eq!(
+            vec![base_edges[1]],
+            fast_graph.unpack_shortcut_fwd(base_edges[1].0)
+        );
+        assert_eq!(
+            vec![base_edges[0]],
+            fast_graph.unpack_shortcut_bwd(base_edges[0].0)
+        );
+    }
+
+    #[test]
+    fn calc_path_with_payloads() {
+        // 0 - 1 - 2 - 3
+        let mut g = InputGraph::new();
+        g.add_edge_with_payload(0, 1, 5, 10);
+        g.add_edge_with_payload(1, 2, 3, 12);
+        g.add_edge_with_payload(2, 3, 2, 23);
+        g.add_edge_with_payload(3, 2, 2, 32);
+        g.add_edge_with_payload(2, 1, 3, 21);
+        g.add_edge_with_payload(1, 0, 5, 1);
+        g.freeze();
+        let fast_graph = prepare_with_order(&g, &[1, 2, 0, 3]).unwrap();
+        let mut calc = create_calculator(&fast_graph);
+        let (path, payloads) = calc.calc_path_with_payloads(&fast_graph, 0, 3).unwrap();
+        assert_eq!(&vec![0, 1, 2, 3], path.get_nodes());
+        assert_eq!(vec![10, 12, 23], payloads);
+        let (path, payloads) = calc.calc_path_with_payloads(&fast_graph, 3, 0).unwrap();
+        assert_eq!(&vec![3, 2, 1, 0], path.get_nodes());
+        assert_eq!(vec![32, 21, 1], payloads);
+        let (_, payloads) = calc.calc_path_with_payloads(&fast_graph, 2, 2).unwrap();
+        assert!(payloads.is_empty());
+    }
+
+    #[test]
+    fn calc_path_via() {
+        // 0 -> 1 -> 2 -> 3
+        //  \        ^
+        //   \-> 4 -/
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 2);
+        g.add_edge(1, 2, 3);
+        g.add_edge(2, 3, 1);
+        g.add_edge(0, 4, 1);
+        g.add_edge(4, 2, 1);
+        g.freeze();
+        let fast_graph = prepare(&g);
+        let mut calc = create_calculator(&fast_graph);
+        assert_eq!(3, calc_weight(&fast_graph, 0, 3).unwrap());
+        let path = calc.calc_path_via(&fast_graph, 0, 1, 3).unwrap();
+        let first = calc_weight(&fast_graph, 0, 1).unwrap();
+        let second = calc_weight(&fast_graph, 1, 3).unwrap();
+        assert_eq!(first + second, path.get_weight());
+        assert_eq!(ShortestPath::new(0, 3, 6, vec![0, 1, 2, 3]), path);
+        assert_eq!(&vec![0, 1, 2, 3], path.get_nodes());
+        // via the start or end node is the same as the shortest path
+        let path = calc.calc_path_via(&fast_graph, 0, 0, 3).unwrap();
+        assert_eq!(&vec![0, 4, 2, 3], path.get_nodes());
+        let path = calc.calc_path_via(&fast_graph, 0, 3, 3).unwrap();
+        assert_eq!(&vec![0, 4, 2, 3], path.get_nodes());
+        // one of the legs is missing
+        assert_eq!(None, calc.calc_path_via(&fast_graph, 1, 4, 3));
+        assert_eq!(None, calc.calc_path_via(&fast_graph, 4, 3, 0));
+    }
+
+    #[test]
+    fn calc_path_through() {
+        // 0 -> 1 -> 2 -> 3 -> 4
+        // ^--------------/
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 1);
+        g.add_edge(1, 2, 2);
+        g.add_edge(2, 3, 3);
+        g.add_edge(3, 4, 4);
+        g.add_edge(3, 0, 5);
+        g.freeze();
+        let fast_graph = prepare(&g);
+        let mut calc = create_calculator(&fast_graph);
+        let path = calc.calc_path_through(&fast_graph, &[2, 1, 3]).unwrap();
+        assert_eq!(&vec![2, 3, 0, 1, 2, 3], path.get_nodes());
+        assert_eq!(14, path.get_weight());
+        assert_eq!((2, 3), (path.get_source(), path.get_target()));
+        // repeated waypoints
+        let path = calc.calc_path_through(&fast_graph, &[1, 1, 2, 2]).unwrap();
+        assert_eq!(&vec![1, 2], path.get_nodes());
+        assert_eq!(2, path.get_weight());
+        let path = calc.calc_path_through(&fast_graph, &[4]).unwrap();
+        assert_eq!(ShortestPath::singular(4), path);
+        // the third leg fails
+        assert_eq!(
+            Err(2),
+            calc.calc_path_through(&fast_graph, &[0, 1, 4, 0, 1])
+        );
+    }
+
+    #[test]
+    fn reproducible_node_ordering() {
+        // 0 - 1 - 2
+        // |   |   |
+        // 3 - 4 - 5
+        // |   |   |
+        // 6 - 7 - 8
+        let mut g = InputGraph::new();
+        for row in 0..3 {
+            for col in 0..2 {
+                g.add_edge_bidir(3 * row + col, 3 * row + col + 1, 1);
+                g.add_edge_bidir(3 * col + row, 3 * col + row + 3, 1);
+            }
+        }
+        g.freeze();
+        let fast_graph = FastGraphBuilder::build(&g);
+        // many nodes have the same priority here, these are contracted in the order of their ids
+        assert_eq!(
+            vec![0, 2, 6, 8, 1, 3, 5, 7, 4],
+            fast_graph.get_node_ordering()
+        );
     }
 
     fn assert_path(
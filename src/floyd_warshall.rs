@@ -66,7 +66,7 @@ impl FloydWarshall {
     }
 
     pub fn calc_weight(&self, source: NodeId, target: NodeId) -> Weight {
-        return self.matrix[source * self.num_nodes + target];
+        self.matrix[source * self.num_nodes + target]
     }
 }
 
@@ -27,13 +27,31 @@ use rand::Rng;
 use serde::{Deserialize, Serialize};
 
 use crate::constants::NodeId;
-use crate::constants::Weight;
+use crate::constants::{add_weights, Weight, WEIGHT_MAX};
 
+/// The graph given by the user. Edges can be added in any order, and `freeze()` cleans them up
+/// before the graph is used: the edges are sorted, loops are skipped and of multiple edges
+/// between the same nodes only the one with the lowest weight is kept. The number of nodes is
+/// determined by the largest node id.
 #[derive(Serialize, Deserialize)]
 pub struct InputGraph {
     edges: Vec<Edge>,
     num_nodes: usize,
     frozen: bool,
+    #[serde(skip, default = "default_max_weight")]
+    max_weight: Weight,
+}
+
+/// Larger weights are most likely the result of an integer underflow and would lead to overflows
+/// when they are added up anyway.
+fn default_max_weight() -> Weight {
+    WEIGHT_MAX / 2
+}
+
+impl Default for InputGraph {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl InputGraph {
@@ -42,6 +60,7 @@ impl InputGraph {
             edges: Vec::new(),
             num_nodes: 0,
             frozen: false,
+            max_weight: default_max_weight(),
         }
     }
 
@@ -53,12 +72,70 @@ impl InputGraph {
         InputGraph::read_from_file(filename)
     }
 
+    /// Creates a frozen graph with `num_nodes` nodes and the given edges. Unlike `add_edge()`
+    /// this allows nodes without edges at the end of the id range. Panics if an edge refers to a
+    /// node id that is not smaller than `num_nodes`.
+    pub fn from_edges<I>(num_nodes: usize, edges: I) -> Self
+    where
+        I: IntoIterator<Item = (NodeId, NodeId, Weight)>,
+    {
+        let edges = edges
+            .into_iter()
+            .map(|(from, to, weight)| (from, to, weight, 0));
+        InputGraph::from_edges_with_payload(num_nodes, edges)
+    }
+
+    /// Like `from_edges()`, but with a payload for each edge
+    pub(crate) fn from_edges_with_payload<I>(num_nodes: usize, edges: I) -> Self
+    where
+        I: IntoIterator<Item = (NodeId, NodeId, Weight, u64)>,
+    {
+        let edges = edges.into_iter();
+        let mut graph = InputGraph::new();
+        graph.edges.reserve(edges.size_hint().0);
+        for (from, to, weight, payload) in edges {
+            assert!(
+                from < num_nodes && to < num_nodes,
+                "invalid edge {} -> {}, node ids must be smaller than {}",
+                from,
+                to,
+                num_nodes
+            );
+            graph.add_edge_with_payload(from, to, weight, payload);
+        }
+        graph.num_nodes = num_nodes;
+        graph.freeze();
+        graph
+    }
+
+    /// Adds an edge and returns the number of edges that were added. Loops (edges from a node to
+    /// itself) are skipped and zero is returned, because they are never part of a shortest path.
     pub fn add_edge(&mut self, from: NodeId, to: NodeId, weight: Weight) -> usize {
-        self.do_add_edge(from, to, weight, false)
+        self.do_add_edge(from, to, weight, 0, false)
     }
 
     pub fn add_edge_bidir(&mut self, from: NodeId, to: NodeId, weight: Weight) -> usize {
-        self.do_add_edge(from, to, weight, true)
+        self.do_add_edge(from, to, weight, 0, true)
+    }
+
+    /// Like `add_edge()`, but attaches a user defined payload (e.g. the id of the edge in some
+    /// external data source) to the edge. The payload is kept in the prepared graph and can be
+    /// retrieved for the edges of a shortest path. Edges added without a payload use zero.
+    pub fn add_edge_with_payload(
+        &mut self,
+        from: NodeId,
+        to: NodeId,
+        weight: Weight,
+        payload: u64,
+    ) -> usize {
+        self.do_add_edge(from, to, weight, payload, false)
+    }
+
+    /// Sets the largest edge weight that is accepted when adding edges, adding heavier edges
+    /// causes a panic. This helps to detect broken input data early. By default only weights
+    /// that are so large that they can only be the result of an integer underflow are rejected.
+    pub fn set_max_weight(&mut self, max_weight: Weight) {
+        self.max_weight = max_weight;
     }
 
     pub fn get_edges(&self) -> &Vec<Edge> {
@@ -89,12 +166,46 @@ impl InputGraph {
         self.frozen = false;
     }
 
+    /// Returns a (frozen) copy of this graph where the cost of entering a node, e.g. the waiting
+    /// time at a traffic light, is added to the weight of every edge leading to it. Preparing
+    /// this graph yields shortest paths that charge the cost of every node they enter exactly
+    /// once, including the target but not the start node. The costs cannot be added at query
+    /// time instead, because shortcuts skip the nodes they were created for.
+    pub fn with_node_costs(&self, node_costs: &[Weight]) -> InputGraph {
+        self.check_frozen();
+        assert_eq!(
+            self.num_nodes,
+            node_costs.len(),
+            "there must be one cost per node"
+        );
+        let edges = self
+            .edges
+            .iter()
+            .map(|e| {
+                let weight = add_weights(e.weight, node_costs[e.to]);
+                if weight > self.max_weight {
+                    panic!(
+                        "Edge weight exceeds the maximum weight {}. from: {}, to: {}, weight: {}",
+                        self.max_weight, e.from, e.to, weight
+                    );
+                }
+                Edge::with_payload(e.from, e.to, weight, e.payload)
+            })
+            .collect();
+        InputGraph {
+            edges,
+            num_nodes: self.num_nodes,
+            frozen: true,
+            max_weight: self.max_weight,
+        }
+    }
+
     fn sort(&mut self) {
-        &self.edges.sort_by(|a, b| {
+        self.edges.sort_by(|a, b| {
             a.from
                 .cmp(&b.from)
                 .then(a.to.cmp(&b.to))
-                .then(a.weight.cmp(&&b.weight))
+                .then(a.weight.cmp(&b.weight))
         });
     }
 
@@ -111,13 +222,12 @@ impl InputGraph {
     }
 
     pub fn unit_test_output_string(&self) -> String {
-        return self
-            .edges
+        self.edges
             .iter()
             .map(|e| e.unit_test_output_string())
             .collect::<Vec<String>>()
             .join("\n")
-            + "\n";
+            + "\n"
     }
 
     fn check_frozen(&self) {
@@ -126,30 +236,42 @@ impl InputGraph {
         }
     }
 
-    fn do_add_edge(&mut self, from: NodeId, to: NodeId, weight: Weight, bidir: bool) -> usize {
+    fn do_add_edge(
+        &mut self,
+        from: NodeId,
+        to: NodeId,
+        weight: Weight,
+        payload: u64,
+        bidir: bool,
+    ) -> usize {
         if self.frozen {
             panic!("Graph is frozen already, for further changes first use thaw()");
         }
-        if from == to {
-            warn!(
-                "Loop edges are not allowed. Skipped edge! from: {}, to: {}, weight: {}",
-                from, to, weight
+        if weight > self.max_weight {
+            panic!(
+                "Edge weight exceeds the maximum weight {}. from: {}, to: {}, weight: {}",
+                self.max_weight, from, to, weight
             );
-            return 0;
         }
-        if weight < 1 {
+        if from == to {
             warn!(
-                "Zero weight edges are not allowed. Skipped edge! from: {}, to: {}, weight: {}",
+                "Loop edges are not allowed. Skipped edge! from: {}, to: {}, weight: {}",
                 from, to, weight
             );
             return 0;
         }
         self.num_nodes = cmp::max(self.num_nodes, cmp::max(from, to) + 1);
-        self.edges.push(Edge::new(from, to, weight));
+        self.edges
+            .push(Edge::with_payload(from, to, weight, payload));
+        if bidir {
+            self.edges
+                .push(Edge::with_payload(to, from, weight, payload));
+        }
         if bidir {
-            self.edges.push(Edge::new(to, from, weight));
+            2
+        } else {
+            1
         }
-        return if bidir { 2 } else { 1 };
     }
 
     /// Builds a random graph, mostly used for testing purposes
@@ -179,7 +301,7 @@ impl InputGraph {
         let file = File::open(filename).unwrap();
         let reader = BufReader::new(file);
         let mut g = InputGraph::new();
-        for (_index, line) in reader.lines().enumerate() {
+        for line in reader.lines() {
             let s: String = line.unwrap();
             if !s.starts_with("a") {
                 continue;
@@ -205,20 +327,34 @@ impl fmt::Debug for InputGraph {
     }
 }
 
+/// An edge of the `InputGraph`. Use `Edge::new()` or `Edge::with_payload()` to create one, because
+/// fields might be added in the future.
 #[derive(Serialize, Deserialize, Debug)]
+#[non_exhaustive]
 pub struct Edge {
     pub from: NodeId,
     pub to: NodeId,
     pub weight: Weight,
+    #[serde(default)]
+    pub payload: u64,
 }
 
 impl Edge {
     pub fn new(from: NodeId, to: NodeId, weight: Weight) -> Edge {
-        Edge { from, to, weight }
+        Edge::with_payload(from, to, weight, 0)
+    }
+
+    pub fn with_payload(from: NodeId, to: NodeId, weight: Weight, payload: u64) -> Edge {
+        Edge {
+            from,
+            to,
+            weight,
+            payload,
+        }
     }
 
     pub fn unit_test_output_string(&self) -> String {
-        return format!("g.add_edge({}, {}, {});", self.from, self.to, self.weight);
+        format!("g.add_edge({}, {}, {});", self.from, self.to, self.weight)
     }
 }
 
@@ -226,6 +362,36 @@ impl Edge {
 mod tests {
     use super::*;
 
+    #[test]
+    fn with_node_costs() {
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 3);
+        g.add_edge_with_payload(1, 2, 4, 7);
+        g.add_edge(2, 0, 1);
+        g.freeze();
+        let g = g.with_node_costs(&[10, 20, 30]);
+        assert_eq!(
+            "g.add_edge(0, 1, 23);\ng.add_edge(1, 2, 34);\ng.add_edge(2, 0, 11);\n",
+            g.unit_test_output_string()
+        );
+        assert_eq!(7, g.get_edges()[1].payload);
+        assert_eq!(3, g.get_num_nodes());
+    }
+
+    #[test]
+    fn from_edges() {
+        let g = InputGraph::from_edges(5, vec![(0, 1, 3), (1, 2, 4), (0, 1, 2)]);
+        assert_eq!(5, g.get_num_nodes());
+        assert_eq!(2, g.get_num_edges());
+        assert_eq!(2, g.get_edges()[0].weight);
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_edges_invalid_node() {
+        InputGraph::from_edges(2, vec![(0, 2, 3)]);
+    }
+
     #[test]
     #[should_panic]
     fn panic_if_not_frozen_get_edges() {
@@ -287,20 +453,40 @@ mod tests {
     fn skips_loops() {
         let mut g = InputGraph::new();
         g.add_edge(0, 1, 3);
-        g.add_edge(4, 4, 2);
+        assert_eq!(0, g.add_edge(4, 4, 2));
+        assert_eq!(0, g.add_edge_bidir(3, 3, 1));
         g.add_edge(2, 5, 4);
         g.freeze();
         assert_eq!(2, g.get_num_edges());
+        // the loops do not count towards the number of nodes either
+        assert_eq!(6, g.get_num_nodes());
     }
 
     #[test]
-    fn skips_zero_weight_edges() {
+    #[should_panic(expected = "exceeds the maximum weight")]
+    fn panic_if_weight_underflowed() {
+        let (a, b): (Weight, Weight) = (3, 5);
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, a.wrapping_sub(b));
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds the maximum weight")]
+    fn panic_if_weight_exceeds_max_weight() {
+        let mut g = InputGraph::new();
+        g.set_max_weight(100);
+        g.add_edge(0, 1, 100);
+        g.add_edge(1, 2, 101);
+    }
+
+    #[test]
+    fn keeps_zero_weight_edges() {
         let mut g = InputGraph::new();
         g.add_edge(0, 1, 5);
         g.add_edge(1, 2, 0);
         g.add_edge(2, 3, 3);
         g.freeze();
-        assert_eq!(2, g.get_num_edges());
+        assert_eq!(3, g.get_num_edges());
     }
 
     #[test]
@@ -324,6 +510,21 @@ mod tests {
         assert_eq!(vec![2, 3, 5, 9], weights);
     }
 
+    #[test]
+    fn keeps_payload_of_lowest_weight_edge() {
+        let mut g = InputGraph::new();
+        g.add_edge_with_payload(0, 1, 7, 100);
+        g.add_edge_with_payload(0, 1, 3, 101);
+        g.add_edge(1, 2, 4);
+        g.freeze();
+        let payloads = g
+            .get_edges()
+            .iter()
+            .map(|e| e.payload)
+            .collect::<Vec<u64>>();
+        assert_eq!(vec![101, 0], payloads);
+    }
+
     #[test]
     fn skips_duplicate_edges_more() {
         let mut g = InputGraph::new();
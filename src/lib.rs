@@ -21,56 +21,148 @@
 extern crate log;
 
 use std::error::Error;
+use std::fmt;
 use std::fs::File;
+use std::io::Read;
 
+pub use crate::a_star::haversine_distance;
+pub use crate::a_star::AStar;
+pub use crate::alt::Alt;
+pub use crate::cached_calculator::CachedCalculator;
+#[cfg(feature = "concurrency")]
+pub use crate::calculator_pool::{CalculatorPool, PooledCalculator};
 pub use crate::constants::*;
-pub use crate::fast_graph::FastGraph;
+#[cfg(feature = "witness-report")]
+pub use crate::contraction_report::{ContractionReport, NodeContractionStats, WitnessSearch};
+pub use crate::diameter::{estimate_diameter, node_eccentricity};
+pub use crate::dijkstra::{Dijkstra, DijkstraDial};
+pub use crate::dimacs::{export_dimacs, import_dimacs, import_dimacs_undirected};
+#[cfg(feature = "test-utils")]
+pub use crate::fast_graph::FastGraphEdge;
+pub use crate::fast_graph::{FastGraph, GraphStats};
 pub use crate::fast_graph_builder::FastGraphBuilder;
 pub use crate::fast_graph_builder::Params;
 pub use crate::input_graph::Edge;
 pub use crate::input_graph::InputGraph;
-pub use crate::path_calculator::PathCalculator;
-pub use crate::shortest_path::ShortestPath;
+#[cfg(feature = "spatial")]
+pub use crate::node_index::NodeIndex;
+pub use crate::path_calculator::{Cancelled, MeetingNodeTieBreak, PathCalculator};
+pub use crate::preparation_graph::PreparationGraph;
+pub use crate::queue::{BinaryHeapQueue, DialQueue};
+pub use crate::search_space_report::{benchmark_query_search_space, SearchSpaceReport};
+pub use crate::shortest_path::{NoPathReason, Segment, ShortestPath};
+pub use crate::valid_flags::ValidFlags;
 
+mod a_star;
+mod alt;
+mod cached_calculator;
+#[cfg(feature = "concurrency")]
+mod calculator_pool;
 mod constants;
+#[cfg(feature = "witness-report")]
+mod contraction_report;
+mod csr_graph;
+mod diameter;
 mod dijkstra;
+mod dimacs;
 mod fast_graph;
 mod fast_graph_builder;
 #[cfg(test)]
 mod floyd_warshall;
+#[cfg(feature = "fx-hash")]
+mod fx_hasher;
 mod heap_item;
 mod input_graph;
 mod node_contractor;
+#[cfg(feature = "spatial")]
+mod node_index;
 mod path_calculator;
+#[cfg(feature = "prefetch")]
+mod prefetch;
 mod preparation_graph;
+mod queue;
+mod search_graph;
+mod search_space_report;
 mod shortest_path;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
 mod valid_flags;
 
-/// Prepares the given `InputGraph` for fast shortest path calculations.
+/// Prepares the given `InputGraph` for fast shortest path calculations. The node ordering chosen
+/// during the preparation is kept in the resulting graph and can be retrieved using
+/// `get_node_ordering()` to re-run the preparation with `prepare_with_order()` later.
+/// The nodes are not renumbered: although the edges are stored in the order of the node ranks,
+/// all queries take and return the node ids of the `InputGraph`.
 pub fn prepare(input_graph: &InputGraph) -> FastGraph {
-    return FastGraphBuilder::build(input_graph);
+    FastGraphBuilder::build(input_graph)
+}
+
+/// Like `prepare()`, but takes the edges directly, e.g. from an iterator over some external data
+/// source, see `InputGraph::from_edges()`.
+pub fn prepare_from_edges<I>(num_nodes: usize, edges: I) -> FastGraph
+where
+    I: IntoIterator<Item = (NodeId, NodeId, Weight)>,
+{
+    FastGraphBuilder::build(&InputGraph::from_edges(num_nodes, edges))
 }
 
 /// Like `prepare()`, but allows specifying some parameters used for the graph preparation.
 pub fn prepare_with_params(input_graph: &InputGraph, params: &Params) -> FastGraph {
-    return FastGraphBuilder::build_with_params(input_graph, params);
+    FastGraphBuilder::build_with_params(input_graph, params)
+}
+
+/// Like `prepare()`, but also returns a report of all witness searches run during the
+/// preparation, which can be used to find out why a certain shortcut was (not) added. Recording
+/// the report slows down the preparation, so this is only meant for debugging.
+#[cfg(feature = "witness-report")]
+pub fn prepare_with_report(input_graph: &InputGraph) -> (FastGraph, ContractionReport) {
+    FastGraphBuilder::build_with_report(input_graph, &Params::default())
+}
+
+/// Like `prepare()`, but only contracts the given fraction (between 0 and 1) of the nodes and
+/// leaves the remaining nodes uncontracted as the 'core' of the graph. Queries on such a graph
+/// return the same results, but run a plain Dijkstra search within the core, so they are slower
+/// while the preparation is faster. This is mostly useful for experiments.
+pub fn prepare_up_to(input_graph: &InputGraph, fraction: f32) -> FastGraph {
+    FastGraphBuilder::build_up_to(input_graph, &Params::default(), fraction)
+}
+
+/// Like `prepare()`, but never contracts the given nodes, e.g. hub nodes that are used as
+/// via-points by many queries. These nodes get the highest ranks and form the 'core' of the
+/// graph, see `prepare_up_to()`. Returns an error if any of the nodes is not a valid node id.
+pub fn prepare_excluding(
+    input_graph: &InputGraph,
+    never_contract: &[NodeId],
+) -> Result<FastGraph, String> {
+    FastGraphBuilder::build_excluding(input_graph, &Params::default(), never_contract)
+}
+
+/// Prepares all the given input graphs, with the same results as calling `prepare()` for each
+/// of them, but reuses the memory needed for the preparation, which is faster for many small
+/// graphs.
+pub fn prepare_many(input_graphs: &[InputGraph]) -> Vec<FastGraph> {
+    FastGraphBuilder::build_many(input_graphs, &Params::default())
 }
 
 /// Prepares the given input graph using a fixed node ordering, which can be any permutation
 /// of the node ids. This can be used to speed up the graph preparation if you have done
 /// it for a similar graph with an equal number of nodes. For example if you have changed some
 /// of the edge weights only.
-pub fn prepare_with_order(
-    input_graph: &InputGraph,
-    order: &Vec<NodeId>,
-) -> Result<FastGraph, String> {
-    return FastGraphBuilder::build_with_order(input_graph, order);
+pub fn prepare_with_order(input_graph: &InputGraph, order: &[NodeId]) -> Result<FastGraph, String> {
+    FastGraphBuilder::build_with_order(input_graph, order)
 }
 
 /// Calculates the shortest path from `source` to `target`.
 pub fn calc_path(fast_graph: &FastGraph, source: NodeId, target: NodeId) -> Option<ShortestPath> {
     let mut calc = PathCalculator::new(fast_graph.get_num_nodes());
-    return calc.calc_path(fast_graph, source, target);
+    calc.calc_path(fast_graph, source, target)
+}
+
+/// Calculates the weight of the shortest path from `source` to `target`. This is a convenience
+/// function for a single query, for repeated queries use a `PathCalculator` instead.
+pub fn calc_weight(fast_graph: &FastGraph, source: NodeId, target: NodeId) -> Option<Weight> {
+    let mut calc = PathCalculator::new(fast_graph.get_num_nodes());
+    calc.calc_weight(fast_graph, source, target)
 }
 
 /// Creates a `PathCalculator` that can be used to run many shortest path calculations in a row.
@@ -95,7 +187,51 @@ pub fn save_to_disk(fast_graph: &FastGraph, file_name: &str) -> Result<(), Box<d
 /// Restores a prepared graph from disk
 pub fn load_from_disk(file_name: &str) -> Result<FastGraph, Box<dyn Error>> {
     let file = File::open(file_name)?;
-    Ok(bincode::deserialize_from(file)?)
+    Ok(FastGraph::deserialize_from(file)?)
+}
+
+/// Restores a prepared graph from the given reader, e.g. a file written by `save_to_disk()`, and
+/// checks that it is consistent using `FastGraph::validate()` and
+/// `FastGraph::check_shortcut_symmetry()`. Use this for data that might be corrupt, because
+/// queries on an invalid graph can panic or return wrong paths.
+pub fn load_validated<R: Read>(reader: R) -> Result<FastGraph, LoadError> {
+    let fast_graph = FastGraph::deserialize_from(reader).map_err(LoadError::Deserialize)?;
+    fast_graph.validate().map_err(LoadError::Invalid)?;
+    fast_graph.check_shortcut_symmetry().map_err(|edges| {
+        LoadError::Invalid(format!(
+            "the weights of {} shortcuts do not match the edges they replace, e.g. {:?}",
+            edges.len(),
+            edges[0]
+        ))
+    })?;
+    Ok(fast_graph)
+}
+
+/// The error returned by `load_validated()`
+#[derive(Debug)]
+pub enum LoadError {
+    /// The data could not be read or is not a serialized `FastGraph`
+    Deserialize(bincode::Error),
+    /// The data was read successfully, but the graph is not consistent
+    Invalid(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Deserialize(e) => write!(f, "could not deserialize the graph: {}", e),
+            LoadError::Invalid(e) => write!(f, "invalid graph: {}", e),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Deserialize(e) => Some(e),
+            LoadError::Invalid(_) => None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -112,6 +248,9 @@ mod tests {
     use crate::floyd_warshall::FloydWarshall;
     use crate::path_calculator::PathCalculator;
     use crate::preparation_graph::PreparationGraph;
+    use crate::test_utils::{
+        assert_query_equivalent, check_same_weights_as_dijkstra, random_input_graph,
+    };
 
     use super::*;
 
@@ -132,6 +271,7 @@ mod tests {
         let input_graph = InputGraph::random(&mut rng, NUM_NODES, MEAN_DEGREE);
         debug!("random graph: \n {:?}", input_graph);
         let fast_graph = prepare(&input_graph);
+        assert_eq!(Ok(()), fast_graph.validate());
         let mut path_calculator = create_calculator(&fast_graph);
 
         let dijkstra_graph = PreparationGraph::from_input_graph(&input_graph);
@@ -151,6 +291,12 @@ mod tests {
                 .calc_path(&dijkstra_graph, source, target)
                 .unwrap_or(ShortestPath::none(source, target));
             let weight_fast = path_fast.get_weight();
+            assert_eq!(
+                path_calculator
+                    .calc_weight(&fast_graph, source, target)
+                    .unwrap_or(WEIGHT_MAX),
+                weight_fast
+            );
             let weight_dijkstra = path_dijkstra.get_weight();
             let weight_fw = fw.calc_weight(source, target);
             assert_eq!(
@@ -170,18 +316,285 @@ mod tests {
             }
         }
         if num_different_paths as f32 > 0.1 * NUM_QUERIES as f32 {
-            assert!(
-                false,
-                format!(
-                    "too many different paths: {}, out of {}, a few different paths can be expected \
-                    because of unambiguous shortest paths, but if there are too many something is \
-                    wrong",
-                    num_different_paths, NUM_QUERIES
-                )
+            panic!(
+                "too many different paths: {}, out of {}, a few different paths can be expected \
+                because of unambiguous shortest paths, but if there are too many something is \
+                wrong",
+                num_different_paths, NUM_QUERIES
             );
         }
     }
 
+    #[test]
+    fn routing_with_zero_weight_edges() {
+        const NUM_NODES: usize = 30;
+        const NUM_EDGES: usize = 60;
+        let mut rng = create_rng_with_seed(310);
+        for _ in 0..20 {
+            let mut input_graph = InputGraph::new();
+            for _ in 0..NUM_EDGES {
+                // lots of zero weight edges, including zero weight cycles
+                let from = rng.gen_range(0, NUM_NODES);
+                let to = rng.gen_range(0, NUM_NODES);
+                input_graph.add_edge(from, to, rng.gen_range(0, 3));
+            }
+            input_graph.freeze();
+            let fast_graph = prepare(&input_graph);
+            let mut path_calculator = create_calculator(&fast_graph);
+            let mut fw = FloydWarshall::new(input_graph.get_num_nodes());
+            fw.prepare(&input_graph);
+            for source in 0..input_graph.get_num_nodes() {
+                for target in 0..input_graph.get_num_nodes() {
+                    let weight_fast = path_calculator
+                        .calc_path(&fast_graph, source, target)
+                        .map(|p| p.get_weight())
+                        .unwrap_or(WEIGHT_MAX);
+                    assert_eq!(
+                        fw.calc_weight(source, target),
+                        weight_fast,
+                        "\nNo agreement for routing query from: {} to: {}\n Failing graph:\n{:?}",
+                        source,
+                        target,
+                        input_graph
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn routing_with_node_costs() {
+        const NUM_NODES: usize = 30;
+        let mut rng = create_rng_with_seed(340);
+        for _ in 0..20 {
+            let input_graph = InputGraph::random(&mut rng, NUM_NODES, 2.0);
+            let num_nodes = input_graph.get_num_nodes();
+            let node_costs: Vec<Weight> = (0..num_nodes).map(|_| rng.gen_range(0, 20)).collect();
+            let fast_graph = prepare(&input_graph.with_node_costs(&node_costs));
+            let mut path_calculator = create_calculator(&fast_graph);
+            let preparation_graph = PreparationGraph::from_input_graph(&input_graph);
+            let mut dijkstra = Dijkstra::new(num_nodes);
+            for source in 0..num_nodes {
+                // Bellman-Ford on the original graph that charges the node costs on entry
+                let mut weights = vec![WEIGHT_MAX; num_nodes];
+                weights[source] = 0;
+                let mut changed = true;
+                while changed {
+                    changed = false;
+                    for e in input_graph.get_edges() {
+                        if weights[e.from] == WEIGHT_MAX {
+                            continue;
+                        }
+                        let weight = weights[e.from] + e.weight + node_costs[e.to];
+                        if weight < weights[e.to] {
+                            weights[e.to] = weight;
+                            changed = true;
+                        }
+                    }
+                }
+                for (target, &weight) in weights.iter().enumerate() {
+                    let weight_fast = path_calculator
+                        .calc_weight(&fast_graph, source, target)
+                        .unwrap_or(WEIGHT_MAX);
+                    let expected = if source == target { 0 } else { weight };
+                    assert_eq!(
+                        expected, weight_fast,
+                        "\nNo agreement for routing query from: {} to: {}\n Failing graph:\n{:?}",
+                        source, target, input_graph
+                    );
+                    let weight_dijkstra = dijkstra
+                        .calc_path_with_node_weights(
+                            &preparation_graph,
+                            source,
+                            target,
+                            &node_costs,
+                        )
+                        .map_or(WEIGHT_MAX, |p| p.get_weight());
+                    assert_eq!(expected, weight_dijkstra);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn routing_with_core() {
+        let g = random_input_graph(200, 2.5, 20, 4);
+        let full = prepare(&g);
+        for &fraction in &[0.0, 0.5, 0.9, 1.0] {
+            let fast_graph = prepare_up_to(&g, fraction);
+            check_same_weights_as_dijkstra(&g, &fast_graph, 300, 5).unwrap();
+            assert!(fast_graph.validate().is_ok());
+            if fraction < 1.0 {
+                assert!(fast_graph.get_num_out_edges() > full.get_num_out_edges());
+                assert!(fast_graph.get_num_core_nodes() > 0);
+            } else {
+                assert_eq!(0, fast_graph.get_num_core_nodes());
+            }
+        }
+        // the unpacked paths must consist of edges of the input graph
+        let core = prepare_up_to(&g, 0.5);
+        let preparation_graph = PreparationGraph::from_input_graph(&g);
+        let mut calc = create_calculator(&core);
+        for (s, t) in [(0, 199), (17, 42), (150, 3)] {
+            if let Some(path) = calc.calc_path(&core, s, t) {
+                let weight: Weight = path
+                    .get_nodes()
+                    .windows(2)
+                    .map(|w| preparation_graph.get_edge_weight(w[0], w[1]).unwrap())
+                    .sum();
+                assert_eq!(path.get_weight(), weight);
+            }
+        }
+    }
+
+    #[test]
+    fn routing_with_max_shortcuts_per_node() {
+        let g = random_input_graph(200, 3.0, 20, 13);
+        let num_shortcuts = prepare(&g).stats().num_shortcuts;
+        for &max in &[0, 1, 3] {
+            let params = Params {
+                max_shortcuts_per_node: Some(max),
+                ..Params::default()
+            };
+            let fast_graph = prepare_with_params(&g, &params);
+            check_same_weights_as_dijkstra(&g, &fast_graph, 300, 8).unwrap();
+            assert!(fast_graph.validate().is_ok());
+            let stats = fast_graph.stats();
+            assert!(stats.num_shortcuts <= max * g.get_num_nodes());
+            assert!(stats.num_shortcuts <= num_shortcuts);
+            if max == 0 {
+                assert_eq!(0, stats.num_shortcuts);
+            }
+        }
+    }
+
+    #[test]
+    fn routing_keeps_input_node_ids() {
+        // 5 -> 3 -> 0 -> 4 -> 1 -> 2
+        let mut g = InputGraph::new();
+        g.add_edge(5, 3, 1);
+        g.add_edge(3, 0, 2);
+        g.add_edge(0, 4, 3);
+        g.add_edge(4, 1, 4);
+        g.add_edge(1, 2, 5);
+        g.freeze();
+        // the ranks differ from the node ids, but the paths must use the input ids anyway
+        for order in [vec![0, 1, 2, 3, 4, 5], vec![4, 0, 1, 5, 2, 3]].iter() {
+            let fast_graph = prepare_with_order(&g, order).unwrap();
+            let path = calc_path(&fast_graph, 5, 2).unwrap();
+            assert_eq!(&vec![5, 3, 0, 4, 1, 2], path.get_nodes());
+            assert_eq!(
+                (5, 2, 15),
+                (path.get_source(), path.get_target(), path.get_weight())
+            );
+            assert_eq!(Some(7), calc_weight(&fast_graph, 0, 1));
+            assert_eq!(None, calc_weight(&fast_graph, 1, 0));
+        }
+    }
+
+    #[test]
+    fn routing_with_excluded_nodes() {
+        let g = random_input_graph(200, 2.5, 20, 6);
+        let hubs = [3, 50, 50, 120, 199];
+        let fast_graph = prepare_excluding(&g, &hubs).unwrap();
+        check_same_weights_as_dijkstra(&g, &fast_graph, 300, 7).unwrap();
+        assert!(fast_graph.validate().is_ok());
+        assert_eq!(4, fast_graph.get_num_core_nodes());
+        // the hubs are never contracted, so they get the highest ranks
+        let mut top_ranks: Vec<usize> = hubs.iter().map(|&h| fast_graph.get_rank(h)).collect();
+        top_ranks.sort_unstable();
+        top_ranks.dedup();
+        assert_eq!(vec![196, 197, 198, 199], top_ranks);
+        assert_eq!(
+            Some(String::from("Invalid node id: 200")),
+            prepare_excluding(&g, &[5, 200]).err()
+        );
+        // excluding no nodes is the same as a normal preparation
+        assert_eq!(
+            bincode::serialize(&prepare(&g)).unwrap(),
+            bincode::serialize(&prepare_excluding(&g, &[]).unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn routing_from_edges() {
+        let edges = (0..9).map(|node| (node, node + 1, 1));
+        let fast_graph = prepare_from_edges(12, edges);
+        assert_eq!(12, fast_graph.get_num_nodes());
+        assert_eq!(Some(9), calc_weight(&fast_graph, 0, 9));
+        assert_eq!(None, calc_weight(&fast_graph, 0, 11));
+    }
+
+    #[test]
+    fn load_validated_graph() {
+        // 0 -> 1 -> 2, contracting node 1 first yields the shortcut 0->2
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 3);
+        g.add_edge(1, 2, 4);
+        g.freeze();
+        let mut fast_graph = prepare_with_order(&g, &[1, 0, 2]).unwrap();
+        let bytes = bincode::serialize(&fast_graph).unwrap();
+        let loaded = load_validated(&bytes[..]).unwrap();
+        assert_eq!(Some(7), calc_weight(&loaded, 0, 2));
+
+        let result = load_validated(&bytes[..bytes.len() / 2]);
+        assert!(matches!(result, Err(LoadError::Deserialize(_))));
+
+        let shortcut = (0..fast_graph.get_num_out_edges())
+            .find(|&e| fast_graph.edges_fwd[e].is_shortcut())
+            .unwrap();
+        fast_graph.edges_fwd[shortcut].weight = 6;
+        let bytes = bincode::serialize(&fast_graph).unwrap();
+        let error = load_validated(&bytes[..]).err().unwrap();
+        assert_eq!(
+            "invalid graph: the weights of 1 shortcuts do not match the edges they replace, e.g. (1, true)",
+            error.to_string()
+        );
+
+        fast_graph.edges_fwd[shortcut].weight = 7;
+        fast_graph.first_edge_ids_fwd.swap(1, 2);
+        let bytes = bincode::serialize(&fast_graph).unwrap();
+        let error = load_validated(&bytes[..]).err().unwrap();
+        assert_eq!(
+            "invalid graph: first forward edge ids are not monotonic",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn load_validated_core_graph() {
+        let g = random_input_graph(100, 2.5, 20, 7);
+        let fast_graph = prepare_up_to(&g, 0.5);
+        assert!(fast_graph.get_num_core_nodes() > 0);
+        let bytes = bincode::serialize(&fast_graph).unwrap();
+        let loaded = load_validated(&bytes[..]).unwrap();
+        assert_eq!(fast_graph.get_num_core_nodes(), loaded.get_num_core_nodes());
+        assert_query_equivalent(&fast_graph, &loaded, 100, 3);
+    }
+
+    #[test]
+    fn load_graph_saved_without_payloads() {
+        let g = random_input_graph(50, 2.5, 20, 4);
+        let fast_graph = prepare(&g);
+        // this is how graphs were serialized before the payloads were added
+        let bytes = bincode::serialize(&(
+            fast_graph.get_num_nodes(),
+            &fast_graph.ranks,
+            &fast_graph.edges_fwd,
+            &fast_graph.first_edge_ids_fwd,
+            &fast_graph.edges_bwd,
+            &fast_graph.first_edge_ids_bwd,
+        ))
+        .unwrap();
+        let loaded = load_validated(&bytes[..]).unwrap();
+        assert_query_equivalent(&fast_graph, &loaded, 100, 1);
+        let base_edge = (0..loaded.get_num_out_edges())
+            .find(|&e| !loaded.edges_fwd[e].is_shortcut())
+            .unwrap();
+        assert_eq!(Some(0), loaded.edge_payload((base_edge, true)));
+        assert!(load_validated(&bytes[..bytes.len() - 1]).is_err());
+    }
+
     #[test]
     fn save_to_and_load_from_disk() {
         let mut g = InputGraph::new();
@@ -215,6 +628,107 @@ mod tests {
         }
     }
 
+    #[test]
+    fn prepare_many_equals_prepare() {
+        let mut rng = create_rng();
+        let input_graphs: Vec<InputGraph> = (0..20)
+            .map(|_| {
+                let num_nodes = rng.gen_range(2, 40);
+                InputGraph::random(&mut rng, num_nodes, 2.0)
+            })
+            .collect();
+        let fast_graphs = prepare_many(&input_graphs);
+        assert_eq!(input_graphs.len(), fast_graphs.len());
+        for (input_graph, fast_graph) in input_graphs.iter().zip(&fast_graphs) {
+            assert_eq!(
+                bincode::serialize(&prepare(input_graph)).unwrap(),
+                bincode::serialize(fast_graph).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn dial_witness_search_equals_prepare() {
+        let mut rng = create_rng();
+        let params = Params {
+            dial_witness_search: true,
+            ..Params::default()
+        };
+        for _ in 0..10 {
+            let input_graph = InputGraph::random(&mut rng, 50, 2.0);
+            assert_eq!(
+                bincode::serialize(&prepare(&input_graph)).unwrap(),
+                bincode::serialize(&prepare_with_params(&input_graph, &params)).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn routing_with_lazy_updates() {
+        const NUM_NODES: usize = 50;
+        let params = Params {
+            lazy_updates: true,
+            ..Params::default()
+        };
+        let mut rng = create_rng_with_seed(321);
+        for _ in 0..20 {
+            let input_graph = InputGraph::random(&mut rng, NUM_NODES, 2.0);
+            let fast_graph = prepare_with_params(&input_graph, &params);
+            assert_eq!(Ok(()), fast_graph.validate());
+            let mut path_calculator = create_calculator(&fast_graph);
+            let mut fw = FloydWarshall::new(input_graph.get_num_nodes());
+            fw.prepare(&input_graph);
+            for source in 0..input_graph.get_num_nodes() {
+                for target in 0..input_graph.get_num_nodes() {
+                    let weight_fast = path_calculator
+                        .calc_weight(&fast_graph, source, target)
+                        .unwrap_or(WEIGHT_MAX);
+                    assert_eq!(fw.calc_weight(source, target), weight_fast);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn batch_weights_on_random_graph() {
+        const NUM_NODES: usize = 50;
+        let mut rng = create_rng();
+        let input_graph = InputGraph::random(&mut rng, NUM_NODES, 2.0);
+        let fast_graph = prepare(&input_graph);
+        let mut path_calculator = create_calculator(&fast_graph);
+        let mut fw = FloydWarshall::new(input_graph.get_num_nodes());
+        fw.prepare(&input_graph);
+        // the pairs are not ordered by source, so they need to be grouped
+        let num_nodes = input_graph.get_num_nodes();
+        let mut pairs = vec![];
+        for target in 0..num_nodes {
+            for source in 0..num_nodes {
+                pairs.push((source, target));
+            }
+        }
+        let weights = path_calculator.calc_weights_batch(&fast_graph, &pairs);
+        for (&(source, target), weight) in pairs.iter().zip(weights) {
+            assert_eq!(fw.calc_weight(source, target), weight.unwrap_or(WEIGHT_MAX));
+        }
+    }
+
+    #[test]
+    fn prepare_with_order_reproduces_heuristic_order() {
+        const NUM_NODES: usize = 50;
+        const MEAN_DEGREE: f32 = 2.0;
+
+        let mut rng = create_rng();
+        let input_graph = InputGraph::random(&mut rng, NUM_NODES, MEAN_DEGREE);
+        let fast_graph = prepare(&input_graph);
+        let order = get_node_ordering(&fast_graph);
+        let fast_graph_with_order = prepare_with_order(&input_graph, &order).unwrap();
+        assert_eq!(order, get_node_ordering(&fast_graph_with_order));
+        assert_eq!(
+            bincode::serialize(&fast_graph).unwrap(),
+            bincode::serialize(&fast_graph_with_order).unwrap()
+        );
+    }
+
     #[ignore]
     #[test]
     fn run_performance_test_dist() {
@@ -223,8 +737,23 @@ mod tests {
         run_performance_test(
             &InputGraph::from_file("meta/test_maps/bremen_dist.gr"),
             &Params::default(),
-            845493338,
-            30265,
+            846725077,
+            30104,
+        )
+    }
+
+    #[ignore]
+    #[test]
+    fn run_performance_test_dist_lazy() {
+        println!("Running performance test for Bremen dist (lazy priority updates)");
+        run_performance_test(
+            &InputGraph::from_file("meta/test_maps/bremen_dist.gr"),
+            &Params {
+                lazy_updates: true,
+                ..Params::default()
+            },
+            846725077,
+            30104,
         )
     }
 
@@ -236,8 +765,8 @@ mod tests {
         run_performance_test(
             &InputGraph::from_file("meta/test_maps/bremen_time.gr"),
             &Params::default(),
-            88104267255,
-            30265,
+            88181026917,
+            30104,
         );
     }
 
@@ -281,11 +810,131 @@ mod tests {
         do_run_performance_test(
             &mut |s, t| path_calculator.calc_path(&fast_graph, s, t),
             input_graph.get_num_nodes(),
-            845493338,
-            30265,
+            846725077,
+            30104,
         );
     }
 
+    #[ignore]
+    #[test]
+    fn run_performance_test_dist_unchecked() {
+        println!("Running performance test for Bremen dist (unchecked queries)");
+        let input_graph = InputGraph::from_file("meta/test_maps/bremen_dist.gr");
+        let mut fast_graph = FastGraph::new(1);
+        prepare_algo(
+            &mut |input_graph| fast_graph = prepare(input_graph),
+            &input_graph,
+        );
+        print_fast_graph_stats(&fast_graph);
+        let mut path_calculator = PathCalculator::new(fast_graph.get_num_nodes());
+        do_run_performance_test(
+            &mut |s, t| path_calculator.calc_path_unchecked(&fast_graph, s, t),
+            input_graph.get_num_nodes(),
+            846725077,
+            30104,
+        );
+    }
+
+    #[ignore]
+    #[test]
+    fn run_performance_test_dist_queues() {
+        println!("Running performance test for Bremen dist (binary heap vs. Dial's queue)");
+        let input_graph = InputGraph::from_file("meta/test_maps/bremen_dist.gr");
+        let fast_graph = prepare(&input_graph);
+        let num_nodes = fast_graph.get_num_nodes();
+        println!("binary heap:");
+        let mut path_calculator = PathCalculator::<BinaryHeapQueue>::with_queue(num_nodes, 0);
+        do_run_performance_test(
+            &mut |s, t| path_calculator.calc_path(&fast_graph, s, t),
+            num_nodes,
+            846725077,
+            30104,
+        );
+        println!("Dial's queue:");
+        let mut path_calculator = PathCalculator::<DialQueue>::with_queue(num_nodes, 0);
+        do_run_performance_test(
+            &mut |s, t| path_calculator.calc_path(&fast_graph, s, t),
+            num_nodes,
+            846725077,
+            30104,
+        );
+    }
+
+    #[ignore]
+    #[test]
+    fn run_performance_test_dist_batch() {
+        println!("Running performance test for Bremen dist (batch weight queries)");
+        let input_graph = InputGraph::from_file("meta/test_maps/bremen_dist.gr");
+        let fast_graph = prepare(&input_graph);
+        let num_nodes = fast_graph.get_num_nodes();
+        // 100 sources x 1000 targets
+        let mut rng = create_rng_with_seed(123);
+        let mut pairs = vec![];
+        for _ in 0..100 {
+            let source = rng.gen_range(0, num_nodes);
+            for _ in 0..1000 {
+                pairs.push((source, rng.gen_range(0, num_nodes)));
+            }
+        }
+        let mut path_calculator = PathCalculator::new(num_nodes);
+        let mut time = Stopwatch::new();
+        time.start();
+        let single: Vec<Option<Weight>> = pairs
+            .iter()
+            .map(|&(s, t)| path_calculator.calc_weight(&fast_graph, s, t))
+            .collect();
+        time.stop();
+        println!(
+            "single queries .................... {} ms",
+            time.elapsed_ms()
+        );
+        time.reset();
+        time.start();
+        let batch = path_calculator.calc_weights_batch(&fast_graph, &pairs);
+        time.stop();
+        println!(
+            "batch queries ..................... {} ms",
+            time.elapsed_ms()
+        );
+        assert_eq!(single, batch);
+    }
+
+    #[ignore]
+    #[test]
+    fn run_performance_test_add_edges() {
+        println!("Running performance test for adding one million edges to a PreparationGraph");
+        const NUM_NODES: usize = 200_000;
+        let mut rng = create_rng_with_seed(123);
+        let edges: Vec<(NodeId, NodeId, Weight)> = (0..1_000_000)
+            .map(|_| {
+                let from = rng.gen_range(0, NUM_NODES);
+                let to = rng.gen_range(0, NUM_NODES);
+                (from, to, rng.gen_range(1, 100))
+            })
+            .collect();
+        let mut time = Stopwatch::new();
+        time.start();
+        let mut single = PreparationGraph::new(NUM_NODES);
+        for &(from, to, weight) in &edges {
+            single.add_edge(from, to, weight);
+        }
+        time.stop();
+        println!(
+            "add_edge .......................... {} ms",
+            time.elapsed_ms()
+        );
+        time.reset();
+        time.start();
+        let mut bulk = PreparationGraph::new(NUM_NODES);
+        bulk.add_edges(&edges);
+        time.stop();
+        println!(
+            "add_edges ......................... {} ms",
+            time.elapsed_ms()
+        );
+        assert!(bulk.memory_usage_bytes() <= single.memory_usage_bytes());
+    }
+
     fn run_performance_test(
         input_graph: &InputGraph,
         params: &Params,
@@ -295,7 +944,7 @@ mod tests {
         let mut fast_graph = FastGraph::new(1);
         prepare_algo(
             &mut |input_graph| fast_graph = prepare_with_params(input_graph, params),
-            &input_graph,
+            input_graph,
         );
         print_fast_graph_stats(&fast_graph);
         let mut path_calculator = PathCalculator::new(fast_graph.get_num_nodes());
@@ -328,7 +977,7 @@ mod tests {
     {
         let mut time = Stopwatch::new();
         time.start();
-        preparation(&input_graph);
+        preparation(input_graph);
         time.stop();
         println!(
             "number of nodes (input graph) ..... {}",
@@ -17,57 +17,131 @@
  * under the License.
  */
 
+use crate::constants::add_weights;
 use crate::constants::NodeId;
 use crate::constants::Weight;
+#[cfg(feature = "witness-report")]
+use crate::contraction_report::{ContractionReport, WitnessSearch};
 use crate::dijkstra::Dijkstra;
 use crate::fast_graph_builder::Params;
 use crate::preparation_graph::PreparationGraph;
+use crate::queue::Queue;
+use crate::shortest_path::ShortestPath;
 
 /// removes all edges incident to `node` from the graph and adds shortcuts between all neighbors
 /// of `node` such that all shortest paths are preserved
-pub fn contract_node(graph: &mut PreparationGraph, dijkstra: &mut Dijkstra, node: NodeId) {
+#[cfg_attr(feature = "witness-report", allow(dead_code))]
+pub fn contract_node<Q: Queue>(
+    graph: &mut PreparationGraph,
+    dijkstra: &mut Dijkstra<Q>,
+    node: NodeId,
+) {
     handle_shortcuts(graph, dijkstra, node, add_shortcut);
     graph.disconnect(node);
 }
 
-pub fn calc_relevance(
+/// like `contract_node`, but records the results of all witness searches in the given report
+#[cfg(feature = "witness-report")]
+pub fn contract_node_with_report<Q: Queue>(
+    graph: &mut PreparationGraph,
+    dijkstra: &mut Dijkstra<Q>,
+    node: NodeId,
+    report: &mut ContractionReport,
+) {
+    search_witnesses(graph, dijkstra, node, |graph, shortcut, witness| {
+        let no_witness = witness.is_none();
+        report.add(WitnessSearch {
+            from: shortcut.from,
+            center_node: shortcut.center_node,
+            to: shortcut.to,
+            weight: shortcut.weight,
+            witness,
+        });
+        if no_witness {
+            add_shortcut(graph, shortcut);
+        }
+    });
+    graph.disconnect(node);
+}
+
+pub fn calc_relevance<Q: Queue>(
     graph: &mut PreparationGraph,
     params: &Params,
-    dijkstra: &mut Dijkstra,
+    dijkstra: &mut Dijkstra<Q>,
     node: NodeId,
     level: NodeId,
 ) -> f32 {
-    let mut num_shortcuts = 0;
-    handle_shortcuts(graph, dijkstra, node, |_graph, _shortcut| {
-        num_shortcuts += 1;
-    });
+    let num_shortcuts = count_shortcuts(graph, dijkstra, node);
     let num_edges = graph.get_out_edges(node).len() + graph.get_in_edges(node).len();
     let mut relevance = (params.hierarchy_depth_factor * level as f32)
         + (params.edge_quotient_factor * num_shortcuts as f32 + 1.0) / (num_edges as f32 + 1.0);
     relevance *= 1000.0;
-    return relevance;
+    relevance
+}
+
+/// returns the number of shortcuts that would be added when contracting `node`
+pub fn count_shortcuts<Q: Queue>(
+    graph: &mut PreparationGraph,
+    dijkstra: &mut Dijkstra<Q>,
+    node: NodeId,
+) -> usize {
+    let mut num_shortcuts = 0;
+    handle_shortcuts(graph, dijkstra, node, |_graph, _shortcut| {
+        num_shortcuts += 1;
+    });
+    num_shortcuts
 }
 
-pub fn handle_shortcuts<F>(
+pub fn handle_shortcuts<Q: Queue, F>(
     graph: &mut PreparationGraph,
-    dijkstra: &mut Dijkstra,
+    dijkstra: &mut Dijkstra<Q>,
     node: NodeId,
     mut handle_shortcut: F,
 ) where
     F: FnMut(&mut PreparationGraph, Shortcut),
 {
+    search_witnesses(graph, dijkstra, node, |graph, shortcut, witness| {
+        if witness.is_none() {
+            handle_shortcut(graph, shortcut)
+        }
+    });
+}
+
+/// runs a witness search for every pair of in- and out-neighbors of `node` and passes the
+/// potential shortcut along with the witness path (if one was found) to `handle_witness`
+fn search_witnesses<Q: Queue, F>(
+    graph: &mut PreparationGraph,
+    dijkstra: &mut Dijkstra<Q>,
+    node: NodeId,
+    mut handle_witness: F,
+) where
+    F: FnMut(&mut PreparationGraph, Shortcut, Option<ShortestPath>),
+{
+    #[cfg(feature = "tracing")]
+    let _span = tracing::trace_span!(
+        "witness_searches",
+        node,
+        num_in_edges = graph.in_edges[node].len(),
+        num_out_edges = graph.out_edges[node].len(),
+    )
+    .entered();
     dijkstra.avoid_node(node);
     for i in 0..graph.in_edges[node].len() {
         for j in 0..graph.out_edges[node].len() {
-            let weight = graph.in_edges[node][i].weight + graph.out_edges[node][j].weight;
+            let weight = add_weights(
+                graph.in_edges[node][i].weight,
+                graph.out_edges[node][j].weight,
+            );
             dijkstra.set_max_weight(weight);
             let in_node = graph.in_edges[node][i].adj_node;
             let out_node = graph.out_edges[node][j].adj_node;
             // todo: optimize: no need to return the full path here
             let shortest_path = dijkstra.calc_path(graph, in_node, out_node);
-            if shortest_path.is_none() {
-                handle_shortcut(graph, Shortcut::new(in_node, out_node, node, weight))
-            }
+            handle_witness(
+                graph,
+                Shortcut::new(in_node, out_node, node, weight),
+                shortest_path,
+            );
         }
     }
 }
@@ -150,7 +224,7 @@ mod tests {
         let _shortcuts = calc_shortcuts(&mut g, 1);
         // performance: there is no need for a shortcut 0->1->2, because there is already the
         // (required) shortcut 3->1->2
-        let _expected_shortcuts = vec![Shortcut::new(3, 2, 1, 2)];
+        let _expected_shortcuts = [Shortcut::new(3, 2, 1, 2)];
         // todo: handle this case for better performance (less shortcuts)
         //        assert_eq!(expected_shortcuts, handler.shortcuts);
     }
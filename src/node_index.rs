@@ -0,0 +1,211 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied.  See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use crate::constants::{NodeId, INVALID_NODE};
+
+const EARTH_RADIUS_METERS: f64 = 6_371_000.0;
+
+/// A spatial index (kd-tree) of the node coordinates that can be used to find the nodes closest to
+/// some location, e.g. to find the start and end nodes of a query given as GPS coordinates.
+/// Internally the coordinates are mapped onto the unit sphere, such that the euclidean distance
+/// between two points is ordered the same way as their great-circle distance.
+pub struct NodeIndex {
+    points: Vec<[f64; 3]>,
+    // the node ids arranged such that the median of each sub-slice is the root of its sub-tree
+    tree: Vec<NodeId>,
+}
+
+impl NodeIndex {
+    /// Builds the index for the given (latitude, longitude) coordinates in degrees, the
+    /// coordinates of node `i` are expected at index `i`.
+    pub fn new(coordinates: &[(f64, f64)]) -> Self {
+        let points: Vec<[f64; 3]> = coordinates
+            .iter()
+            .map(|&(lat, lon)| to_unit_vector(lat, lon))
+            .collect();
+        let mut tree: Vec<NodeId> = (0..coordinates.len()).collect();
+        NodeIndex::build(&points, &mut tree, 0);
+        NodeIndex { points, tree }
+    }
+
+    /// Returns the node closest to the given coordinate, or `None` if there are no nodes
+    pub fn nearest(&self, lat: f64, lon: f64) -> Option<NodeId> {
+        let target = to_unit_vector(lat, lon);
+        let mut best = (INVALID_NODE, f64::INFINITY);
+        self.find_nearest(&self.tree, 0, &target, &mut best);
+        if best.0 == INVALID_NODE {
+            None
+        } else {
+            Some(best.0)
+        }
+    }
+
+    /// Returns all nodes within the given great-circle distance (in meters) of the given
+    /// coordinate, sorted by their distance
+    pub fn within_radius(&self, lat: f64, lon: f64, radius: f64) -> Vec<NodeId> {
+        let target = to_unit_vector(lat, lon);
+        let max_dist_sq = if radius >= std::f64::consts::PI * EARTH_RADIUS_METERS {
+            f64::INFINITY
+        } else {
+            let chord = 2.0 * (radius / (2.0 * EARTH_RADIUS_METERS)).sin();
+            chord * chord
+        };
+        let mut result = vec![];
+        self.find_within(&self.tree, 0, &target, max_dist_sq, &mut result);
+        result.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        result.into_iter().map(|(node, _)| node).collect()
+    }
+
+    fn build(points: &[[f64; 3]], nodes: &mut [NodeId], depth: usize) {
+        if nodes.len() <= 1 {
+            return;
+        }
+        let axis = depth % 3;
+        let mid = nodes.len() / 2;
+        nodes.select_nth_unstable_by(mid, |&a, &b| points[a][axis].total_cmp(&points[b][axis]));
+        let (left, right) = nodes.split_at_mut(mid);
+        NodeIndex::build(points, left, depth + 1);
+        NodeIndex::build(points, &mut right[1..], depth + 1);
+    }
+
+    fn find_nearest(
+        &self,
+        nodes: &[NodeId],
+        depth: usize,
+        target: &[f64; 3],
+        best: &mut (NodeId, f64),
+    ) {
+        if nodes.is_empty() {
+            return;
+        }
+        let mid = nodes.len() / 2;
+        let node = nodes[mid];
+        let dist_sq = dist_sq(&self.points[node], target);
+        if dist_sq < best.1 {
+            *best = (node, dist_sq);
+        }
+        let axis = depth % 3;
+        let diff = target[axis] - self.points[node][axis];
+        let (near, far) = if diff < 0.0 {
+            (&nodes[..mid], &nodes[mid + 1..])
+        } else {
+            (&nodes[mid + 1..], &nodes[..mid])
+        };
+        self.find_nearest(near, depth + 1, target, best);
+        if diff * diff < best.1 {
+            self.find_nearest(far, depth + 1, target, best);
+        }
+    }
+
+    fn find_within(
+        &self,
+        nodes: &[NodeId],
+        depth: usize,
+        target: &[f64; 3],
+        max_dist_sq: f64,
+        result: &mut Vec<(NodeId, f64)>,
+    ) {
+        if nodes.is_empty() {
+            return;
+        }
+        let mid = nodes.len() / 2;
+        let node = nodes[mid];
+        let dist_sq = dist_sq(&self.points[node], target);
+        if dist_sq <= max_dist_sq {
+            result.push((node, dist_sq));
+        }
+        let axis = depth % 3;
+        let diff = target[axis] - self.points[node][axis];
+        if diff < 0.0 || diff * diff <= max_dist_sq {
+            self.find_within(&nodes[..mid], depth + 1, target, max_dist_sq, result);
+        }
+        if diff >= 0.0 || diff * diff <= max_dist_sq {
+            self.find_within(&nodes[mid + 1..], depth + 1, target, max_dist_sq, result);
+        }
+    }
+}
+
+fn to_unit_vector(lat: f64, lon: f64) -> [f64; 3] {
+    let (lat, lon) = (lat.to_radians(), lon.to_radians());
+    [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()]
+}
+
+fn dist_sq(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    (0..3).map(|i| (a[i] - b[i]) * (a[i] - b[i])).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::Rng;
+
+    use super::*;
+
+    #[test]
+    fn nearest_and_within_radius() {
+        // a small grid with 0.01 degrees (roughly 1km) between the nodes
+        // 0 1 2
+        // 3 4 5
+        let coordinates = vec![
+            (52.01, 8.00),
+            (52.01, 8.01),
+            (52.01, 8.02),
+            (52.00, 8.00),
+            (52.00, 8.01),
+            (52.00, 8.02),
+        ];
+        let index = NodeIndex::new(&coordinates);
+        assert_eq!(Some(4), index.nearest(52.001, 8.011));
+        assert_eq!(Some(2), index.nearest(53.0, 9.0));
+        assert_eq!(Some(3), index.nearest(51.0, 7.0));
+        assert_eq!(vec![4], index.within_radius(52.0, 8.01, 500.0));
+        assert_eq!(vec![4, 5, 3, 1], index.within_radius(52.0, 8.012, 1200.0));
+        assert!(index.within_radius(52.5, 8.5, 1000.0).is_empty());
+        assert_eq!(6, index.within_radius(0.0, 0.0, 1e9).len());
+    }
+
+    #[test]
+    fn empty_index() {
+        let index = NodeIndex::new(&[]);
+        assert_eq!(None, index.nearest(52.0, 8.0));
+        assert!(index.within_radius(52.0, 8.0, 1000.0).is_empty());
+    }
+
+    #[test]
+    fn nearest_matches_brute_force() {
+        let mut rng: StdRng = rand::SeedableRng::seed_from_u64(42);
+        let coordinates: Vec<(f64, f64)> = (0..500)
+            .map(|_| (rng.gen_range(-60.0, 60.0), rng.gen_range(-180.0, 180.0)))
+            .collect();
+        let index = NodeIndex::new(&coordinates);
+        for _ in 0..200 {
+            let lat = rng.gen_range(-90.0, 90.0);
+            let lon = rng.gen_range(-180.0, 180.0);
+            let target = to_unit_vector(lat, lon);
+            let expected = (0..coordinates.len())
+                .min_by(|&a, &b| {
+                    let (a, b) = (&coordinates[a], &coordinates[b]);
+                    dist_sq(&to_unit_vector(a.0, a.1), &target)
+                        .total_cmp(&dist_sq(&to_unit_vector(b.0, b.1), &target))
+                })
+                .unwrap();
+            assert_eq!(Some(expected), index.nearest(lat, lon));
+        }
+    }
+}
@@ -17,38 +17,112 @@
  * under the License.
  */
 
-use std::collections::BinaryHeap;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::constants::Weight;
 use crate::constants::INVALID_EDGE;
 use crate::constants::INVALID_NODE;
 use crate::constants::WEIGHT_MAX;
-use crate::constants::{EdgeId, NodeId};
+use crate::constants::WEIGHT_ZERO;
+use crate::constants::{add_weights, EdgeId, NodeId};
+use crate::dijkstra::Dijkstra;
 use crate::fast_graph::FastGraph;
 use crate::heap_item::HeapItem;
-use crate::shortest_path::ShortestPath;
+#[cfg(feature = "prefetch")]
+use crate::prefetch::prefetch;
+use crate::preparation_graph::PreparationGraph;
+use crate::queue::{BinaryHeapQueue, Queue};
+use crate::shortest_path::{NoPathReason, ShortestPath};
 use crate::valid_flags::ValidFlags;
 
-pub struct PathCalculator {
+/// The number of nodes taken from the heaps between two checks of the cancel flag
+const CANCEL_CHECK_INTERVAL: usize = 1024;
+
+/// Returned by the cancellable calculations when the cancel flag was set
+#[derive(Debug, PartialEq)]
+pub struct Cancelled;
+
+/// Decides which meeting node is used when the forward and the backward search meet at several
+/// nodes with the same total weight, see `PathCalculator::set_meeting_node_tie_break()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MeetingNodeTieBreak {
+    /// Keep the meeting node that was found first
+    #[default]
+    FirstFound,
+    /// Prefer the meeting node with the highest rank
+    HighestRank,
+    /// Prefer the meeting node with the lowest rank
+    LowestRank,
+    /// Prefer the meeting node with the smallest node id
+    LowestNodeId,
+}
+
+/// Calculates shortest paths on a `FastGraph`. The priority queue can be chosen with the type
+/// parameter, see `with_queue()`.
+pub struct PathCalculator<Q = BinaryHeapQueue> {
     num_nodes: usize,
     data_fwd: Vec<Data>,
     data_bwd: Vec<Data>,
     valid_flags_fwd: ValidFlags,
     valid_flags_bwd: ValidFlags,
-    heap_fwd: BinaryHeap<HeapItem>,
-    heap_bwd: BinaryHeap<HeapItem>,
+    heap_fwd: Q,
+    heap_bwd: Q,
+    last_meeting_node: NodeId,
+    num_settled: usize,
+    approximation_factor: f64,
+    meeting_node_tie_break: MeetingNodeTieBreak,
+    max_settled: usize,
+    exceeded_max_settled: bool,
+    // the plain Dijkstra search on the base edges, created by the first query that needs it
+    base_dijkstra: Option<Dijkstra>,
 }
 
 impl PathCalculator {
     pub fn new(num_nodes: usize) -> Self {
+        PathCalculator::with_capacity(num_nodes, 0)
+    }
+
+    /// Like `new()`, but reserves space for `heap_capacity` items in both the forward and the
+    /// backward heap up front, so the first queries do not need to grow them.
+    pub fn with_capacity(num_nodes: usize, heap_capacity: usize) -> Self {
+        PathCalculator::with_queue(num_nodes, heap_capacity)
+    }
+
+    /// Returns the node an (unpacked) edge starts from. `fwd` is true for edges of the forward
+    /// graph and false for edges of the backward graph.
+    fn tail_node(graph: &FastGraph, edge_id: EdgeId, fwd: bool) -> NodeId {
+        if fwd {
+            graph.edges_fwd[edge_id].base_node
+        } else {
+            graph.edges_bwd[edge_id].adj_node
+        }
+    }
+
+    fn payload(graph: &FastGraph, edge_id: EdgeId, fwd: bool) -> u64 {
+        graph.get_payload(edge_id, fwd)
+    }
+}
+
+impl<Q: Queue> PathCalculator<Q> {
+    /// Like `with_capacity()`, but uses the queue given by the type parameter, e.g.
+    /// `PathCalculator::<DialQueue>::with_queue(num_nodes, 0)`.
+    pub fn with_queue(num_nodes: usize, heap_capacity: usize) -> Self {
         PathCalculator {
             num_nodes,
             data_fwd: (0..num_nodes).map(|_i| Data::new()).collect(),
             data_bwd: (0..num_nodes).map(|_i| Data::new()).collect(),
             valid_flags_fwd: ValidFlags::new(num_nodes),
             valid_flags_bwd: ValidFlags::new(num_nodes),
-            heap_fwd: BinaryHeap::new(),
-            heap_bwd: BinaryHeap::new(),
+            heap_fwd: Q::with_capacity(heap_capacity),
+            heap_bwd: Q::with_capacity(heap_capacity),
+            last_meeting_node: INVALID_NODE,
+            num_settled: 0,
+            approximation_factor: 0.0,
+            meeting_node_tie_break: MeetingNodeTieBreak::FirstFound,
+            max_settled: usize::MAX,
+            exceeded_max_settled: false,
+            base_dijkstra: None,
         }
     }
 
@@ -58,19 +132,552 @@ impl PathCalculator {
         start: NodeId,
         end: NodeId,
     ) -> Option<ShortestPath> {
-        assert_eq!(
-            graph.get_num_nodes(),
-            self.num_nodes,
-            "given graph has invalid node count"
-        );
-        assert!(start < self.num_nodes, "invalid start node");
-        assert!(end < self.num_nodes, "invalid end node");
+        self.check_query(graph, start, end);
+        self.calc_path_unchecked(graph, start, end)
+    }
+
+    /// Like `calc_path()`, but skips checking that the graph matches this calculator and that
+    /// `start` and `end` are valid node ids. This saves a few comparisons per query when running
+    /// many queries with inputs that have already been validated. Invalid inputs lead to a panic
+    /// or a meaningless result.
+    pub fn calc_path_unchecked(
+        &mut self,
+        graph: &FastGraph,
+        start: NodeId,
+        end: NodeId,
+    ) -> Option<ShortestPath> {
+        let (weight, meeting_node) = self.calc_meeting_node(graph, start, end)?;
+        if start == end {
+            return Some(ShortestPath::singular(start));
+        }
+        let node_ids = self.extract_nodes(graph, end, meeting_node);
+        Some(ShortestPath::new(start, end, weight, node_ids))
+    }
+
+    /// Calculates the weight of the shortest path from `start` to `end`. This is faster than
+    /// `calc_path()`, because the shortcuts of the path do not need to be unpacked.
+    pub fn calc_weight(&mut self, graph: &FastGraph, start: NodeId, end: NodeId) -> Option<Weight> {
+        self.check_query(graph, start, end);
+        self.calc_meeting_node(graph, start, end)
+            .map(|(weight, _)| weight)
+    }
+
+    /// Calculates the weights of the shortest paths from `a` to `b` and from `b` to `a`, e.g. to
+    /// detect asymmetric weights. These are simply two separate searches: the search spaces
+    /// cannot be shared, because the forward search from `a` runs on different (upward) edges
+    /// than the backward search to `a`.
+    pub fn calc_round_trip_weights(
+        &mut self,
+        graph: &FastGraph,
+        a: NodeId,
+        b: NodeId,
+    ) -> (Option<Weight>, Option<Weight>) {
+        (self.calc_weight(graph, a, b), self.calc_weight(graph, b, a))
+    }
+
+    /// Calculates the shortest path weights for all the given (start, end) pairs and returns them
+    /// in the same order. The pairs are grouped by their start node and the forward search is run
+    /// only once per start node, which is faster than calling `calc_weight()` for every pair when
+    /// the same start nodes occur multiple times, e.g. when calculating a distance matrix.
+    pub fn calc_weights_batch(
+        &mut self,
+        graph: &FastGraph,
+        pairs: &[(NodeId, NodeId)],
+    ) -> Vec<Option<Weight>> {
+        self.calc_weights_batch_cancellable(graph, pairs, &AtomicBool::new(false))
+            .unwrap()
+    }
+
+    /// Calculates the weights of the shortest paths from `start` to all nodes of the graph. Nodes
+    /// that cannot be reached are `None`. After the upward search from `start` the nodes are
+    /// visited in order of decreasing rank, and since all in-edges of a node come from nodes
+    /// with a higher rank their weights are final at this point. This is much faster than
+    /// running a query to every node.
+    pub fn calc_tree(&mut self, graph: &FastGraph, start: NodeId) -> Vec<Option<Weight>> {
+        self.check_query(graph, start, start);
+        self.calc_fwd_tree(graph, start);
+        let mut weights: Vec<Weight> = (0..self.num_nodes)
+            .map(|node| self.get_weight_fwd(node))
+            .collect();
+        for &node in graph.get_node_ordering().iter().rev() {
+            for edge_id in graph.begin_in_edges(node)..graph.end_in_edges(node) {
+                let edge = &graph.edges_bwd[edge_id];
+                if weights[edge.adj_node] != WEIGHT_MAX {
+                    weights[node] =
+                        weights[node].min(add_weights(weights[edge.adj_node], edge.weight));
+                }
+            }
+        }
+        weights
+            .into_iter()
+            .map(|weight| Some(weight).filter(|&w| w != WEIGHT_MAX))
+            .collect()
+    }
+
+    /// Like `calc_path()`, but tells why no path was found, i.e. whether the two nodes are not
+    /// connected or the search was stopped by the maximum number of settled nodes, see
+    /// `set_max_settled()`.
+    pub fn calc_path_or_reason(
+        &mut self,
+        graph: &FastGraph,
+        start: NodeId,
+        end: NodeId,
+    ) -> Result<ShortestPath, NoPathReason> {
+        match self.calc_path(graph, start, end) {
+            Some(path) => Ok(path),
+            None if self.exceeded_max_settled => Err(NoPathReason::ExceededMaxSettled),
+            None => Err(NoPathReason::Disconnected),
+        }
+    }
+
+    /// Like `calc_path()`, but the searches only visit nodes that are valid in `allowed`. Note
+    /// that the graph contains shortcuts, so the returned path can still contain nodes outside of
+    /// `allowed`, and it is not necessarily the shortest path within the allowed nodes. Its
+    /// weight is only guaranteed to be that of the shortest path if `allowed` contains the upward
+    /// search spaces of `start` and `end`, see `FastGraph::calc_upward_closed_mask()`. Returns
+    /// `None` if `start` or `end` is not allowed.
+    pub fn calc_path_masked(
+        &mut self,
+        graph: &FastGraph,
+        start: NodeId,
+        end: NodeId,
+        allowed: &ValidFlags,
+    ) -> Option<ShortestPath> {
+        self.check_query(graph, start, end);
+        if !allowed.is_valid(start) || !allowed.is_valid(end) {
+            self.last_meeting_node = INVALID_NODE;
+            return None;
+        }
+        let (weight, meeting_node) = self
+            .calc_meeting_node_or_cancel(graph, start, end, None, Some(allowed))
+            .unwrap()?;
+        if start == end {
+            return Some(ShortestPath::singular(start));
+        }
+        let node_ids = self.extract_nodes(graph, end, meeting_node);
+        Some(ShortestPath::new(start, end, weight, node_ids))
+    }
+
+    /// Like `calc_path()`, but stops early if the given flag is set, e.g. by another thread
+    /// that enforces a deadline. The flag is checked regularly while searching. The calculator
+    /// can be used as usual after a calculation was cancelled.
+    pub fn calc_path_cancellable(
+        &mut self,
+        graph: &FastGraph,
+        start: NodeId,
+        end: NodeId,
+        cancel: &AtomicBool,
+    ) -> Result<Option<ShortestPath>, Cancelled> {
+        self.check_query(graph, start, end);
+        let result = self.calc_meeting_node_or_cancel(graph, start, end, Some(cancel), None)?;
+        Ok(result.map(|(weight, meeting_node)| {
+            if start == end {
+                ShortestPath::singular(start)
+            } else {
+                let node_ids = self.extract_nodes(graph, end, meeting_node);
+                ShortestPath::new(start, end, weight, node_ids)
+            }
+        }))
+    }
+
+    /// Like `calc_weights_batch()`, but stops early if the given flag is set. The flag is checked
+    /// before every single search.
+    pub fn calc_weights_batch_cancellable(
+        &mut self,
+        graph: &FastGraph,
+        pairs: &[(NodeId, NodeId)],
+        cancel: &AtomicBool,
+    ) -> Result<Vec<Option<Weight>>, Cancelled> {
+        for &(start, end) in pairs {
+            self.check_query(graph, start, end);
+        }
+        let mut order: Vec<usize> = (0..pairs.len()).collect();
+        order.sort_by_key(|&i| pairs[i].0);
+        let mut result = vec![None; pairs.len()];
+        let mut curr_start = INVALID_NODE;
+        for i in order {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(Cancelled);
+            }
+            let (start, end) = pairs[i];
+            if start != curr_start {
+                self.calc_fwd_tree(graph, start);
+                curr_start = start;
+            }
+            result[i] = self.calc_weight_from_fwd_tree(graph, end);
+        }
+        Ok(result)
+    }
+
+    /// Calculates the shortest path from `start` to `end` that passes through `via`, i.e. the
+    /// shortest paths from `start` to `via` and from `via` to `end` joined together. Returns
+    /// `None` if either of the two is missing.
+    pub fn calc_path_via(
+        &mut self,
+        graph: &FastGraph,
+        start: NodeId,
+        via: NodeId,
+        end: NodeId,
+    ) -> Option<ShortestPath> {
+        self.calc_path_through(graph, &[start, via, end]).ok()
+    }
+
+    /// Calculates the shortest path that visits all the given waypoints in the given order, by
+    /// joining the shortest paths between each two consecutive waypoints. If one of these legs
+    /// cannot be found, the index of the failed leg is returned, i.e. `Err(i)` means there is no
+    /// path from `waypoints[i]` to `waypoints[i + 1]`.
+    pub fn calc_path_through(
+        &mut self,
+        graph: &FastGraph,
+        waypoints: &[NodeId],
+    ) -> Result<ShortestPath, usize> {
+        assert!(!waypoints.is_empty(), "there must be at least one waypoint");
+        let mut result = ShortestPath::singular(waypoints[0]);
+        for (i, leg) in waypoints.windows(2).enumerate() {
+            let path = self.calc_path(graph, leg[0], leg[1]).ok_or(i)?;
+            result = result.join(path);
+        }
+        Ok(result)
+    }
+
+    /// Like `calc_path()`, but also returns the payloads of all the edges along the shortest path
+    /// in the order they are traversed, see `InputGraph::add_edge_with_payload()`.
+    pub fn calc_path_with_payloads(
+        &mut self,
+        graph: &FastGraph,
+        start: NodeId,
+        end: NodeId,
+    ) -> Option<(ShortestPath, Vec<u64>)> {
+        self.check_query(graph, start, end);
+        let (weight, meeting_node) = self.calc_meeting_node(graph, start, end)?;
+        if start == end {
+            return Some((ShortestPath::singular(start), vec![]));
+        }
+        let node_ids = self.extract_nodes(graph, end, meeting_node);
+        let payloads = self.extract_base_edges(graph, meeting_node, &PathCalculator::payload);
+        Some((ShortestPath::new(start, end, weight, node_ids), payloads))
+    }
+
+    /// Calculates the shortest path from `start` to `end` and returns the edges of the graph it
+    /// consists of without unpacking the shortcuts, i.e. the edges leading up to the node with
+    /// the highest rank of the path followed by the edges leading down to `end`. Each edge is
+    /// given by its id and a flag that is true for edges of the forward graph, see
+    /// `FastGraph::unpack_shortcut_fwd()`. The edges of a path from a node to itself are empty.
+    pub fn calc_edge_path_contracted(
+        &mut self,
+        graph: &FastGraph,
+        start: NodeId,
+        end: NodeId,
+    ) -> Option<Vec<(EdgeId, bool)>> {
+        self.check_query(graph, start, end);
+        let (_, meeting_node) = self.calc_meeting_node(graph, start, end)?;
+        Some(self.extract_contracted_edges(meeting_node))
+    }
+
+    /// Like `calc_path()`, but returns the nodes of the path as an iterator that unpacks the
+    /// shortcuts only as far as the nodes are consumed. This uses an explicit stack rather than
+    /// recursion, so the memory needed for long paths stays small and the consumer can stop
+    /// early without paying for the rest of the path.
+    pub fn calc_path_iter<'a>(
+        &mut self,
+        graph: &'a FastGraph,
+        start: NodeId,
+        end: NodeId,
+    ) -> Option<impl Iterator<Item = NodeId> + 'a> {
+        self.check_query(graph, start, end);
+        let (_, meeting_node) = self.calc_meeting_node(graph, start, end)?;
+        // the edges still to be unpacked, the next one is at the end
+        let mut stack = self.extract_contracted_edges(meeting_node);
+        stack.reverse();
+        let mut end = Some(end);
+        Some(std::iter::from_fn(move || {
+            while let Some((edge_id, fwd)) = stack.pop() {
+                let edge = if fwd {
+                    &graph.edges_fwd[edge_id]
+                } else {
+                    &graph.edges_bwd[edge_id]
+                };
+                if !edge.is_shortcut() {
+                    return Some(PathCalculator::tail_node(graph, edge_id, fwd));
+                }
+                stack.push((edge.replaced_out_edge, true));
+                stack.push((edge.replaced_in_edge, false));
+            }
+            end.take()
+        }))
+    }
+
+    /// Calculates the shortest path from `start` to `end`, but only returns `start`, `end` and the
+    /// nodes in between that have a rank of at least `min_rank`, e.g. to show the 'highway-level'
+    /// part of a route on an overview map. This is faster than `calc_path()` for high ranks,
+    /// because shortcuts that only skip nodes with lower ranks are not unpacked.
+    pub fn calc_coarse_path(
+        &mut self,
+        graph: &FastGraph,
+        start: NodeId,
+        end: NodeId,
+        min_rank: usize,
+    ) -> Option<Vec<NodeId>> {
+        self.check_query(graph, start, end);
+        let (_, meeting_node) = self.calc_meeting_node(graph, start, end)?;
+        if start == end {
+            return Some(vec![start]);
+        }
+        let mut nodes =
+            self.extract_edges_down_to(graph, meeting_node, min_rank, &PathCalculator::tail_node);
+        nodes.push(end);
+        let last = nodes.len() - 1;
+        Some(
+            nodes
+                .iter()
+                .enumerate()
+                .filter(|&(i, &node)| i == 0 || i == last || graph.get_rank(node) >= min_rank)
+                .map(|(_, &node)| node)
+                .collect(),
+        )
+    }
+
+    /// Like `calc_path()`, but the path must not begin with the base edge `forbid_first_edge` and
+    /// must not end with the base edge `forbid_last_edge`. For example, when a location on an
+    /// edge is modelled as a virtual node connected to both ends of the edge, this can be used to
+    /// prevent a U-turn right at the start or the end. The base edges are identified like in
+    /// `FastGraph::unpack_shortcut_fwd()`, and forbidding an edge also forbids the other edges
+    /// between the same nodes, e.g. the second copy of an edge between two core nodes.
+    ///
+    /// If the regular shortest path already avoids the forbidden edges it is returned right away.
+    /// Otherwise this runs one query for every combination of the remaining first and last edges,
+    /// and finding these edges requires a scan over all edges of the graph, so this is only fast
+    /// for nodes with few edges. Edges of the input graph that were dropped during the
+    /// preparation, because they were never part of a shortest path, are not considered.
+    pub fn calc_path_forbidding_edges(
+        &mut self,
+        graph: &FastGraph,
+        start: NodeId,
+        end: NodeId,
+        forbid_first_edge: Option<(EdgeId, bool)>,
+        forbid_last_edge: Option<(EdgeId, bool)>,
+    ) -> Option<ShortestPath> {
+        self.check_query(graph, start, end);
+        if start == end {
+            return Some(ShortestPath::singular(start));
+        }
+        // the edges between core nodes are stored twice, so we compare the nodes of the edges
+        let forbid_first = forbid_first_edge.map(|e| graph.get_edge_nodes(e));
+        let forbid_last = forbid_last_edge.map(|e| graph.get_edge_nodes(e));
+        if let Some((weight, meeting_node)) = self.calc_meeting_node(graph, start, end) {
+            let base_edges = self
+                .extract_base_edges(graph, meeting_node, &|g, e, fwd| g.get_edge_nodes((e, fwd)));
+            if base_edges.first().copied() != forbid_first
+                && base_edges.last().copied() != forbid_last
+            {
+                let node_ids = self.extract_nodes(graph, end, meeting_node);
+                return Some(ShortestPath::new(start, end, weight, node_ids));
+            }
+        } else {
+            return None;
+        }
+        let first_edges: Vec<_> = graph
+            .get_base_out_edges(start)
+            .into_iter()
+            .filter(|&(_, adj, _)| Some((start, adj)) != forbid_first)
+            .collect();
+        let last_edges: Vec<_> = graph
+            .get_base_in_edges(end)
+            .into_iter()
+            .filter(|&(_, adj, _)| Some((adj, end)) != forbid_last)
+            .collect();
+        let mut best: Option<(Weight, NodeId, NodeId)> = None;
+        for &(_, first_node, first_weight) in &first_edges {
+            // a single edge is both the first and the last edge of the path
+            if first_node == end
+                && Some((start, end)) != forbid_last
+                && best.map_or(true, |(w, _, _)| first_weight < w)
+            {
+                best = Some((first_weight, start, end));
+            }
+            for &(_, last_node, last_weight) in &last_edges {
+                if let Some(weight) = self.calc_weight(graph, first_node, last_node) {
+                    let weight = add_weights(add_weights(first_weight, weight), last_weight);
+                    if best.map_or(true, |(w, _, _)| weight < w) {
+                        best = Some((weight, first_node, last_node));
+                    }
+                }
+            }
+        }
+        let (weight, first_node, last_node) = best?;
+        if (first_node, last_node) == (start, end) {
+            return Some(ShortestPath::new(start, end, weight, vec![start, end]));
+        }
+        let middle = self.calc_path(graph, first_node, last_node).unwrap();
+        let mut node_ids = Vec::with_capacity(middle.get_nodes().len() + 2);
+        node_ids.push(start);
+        node_ids.extend(middle.get_nodes());
+        node_ids.push(end);
+        Some(ShortestPath::new(start, end, weight, node_ids))
+    }
+
+    /// Calculates the shortest paths for all the given (start, end) pairs and counts how often
+    /// each base edge is part of them. The counts of the forward edges come first, i.e. the count
+    /// of forward edge `e` is at index `e` and the count of backward edge `e` is at index
+    /// `graph.get_num_out_edges() + e`. The counts of shortcuts are always zero.
+    pub fn calc_edge_usage_counts(
+        &mut self,
+        graph: &FastGraph,
+        pairs: &[(NodeId, NodeId)],
+    ) -> Vec<usize> {
+        let mut counts = vec![0; graph.get_num_edges()];
+        for &(start, end) in pairs {
+            self.check_query(graph, start, end);
+            if start == end {
+                continue;
+            }
+            if let Some((_, meeting_node)) = self.calc_meeting_node(graph, start, end) {
+                let edges = self.extract_base_edges(graph, meeting_node, &|graph, id, fwd| {
+                    if fwd {
+                        id
+                    } else {
+                        graph.get_num_out_edges() + id
+                    }
+                });
+                for index in edges {
+                    counts[index] += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    /// Calculates the shortest path from `start` to `end`, but uses different weights for some of
+    /// the base edges, e.g. to simulate congestion without preparing the graph again. The base
+    /// edges are identified like in `FastGraph::unpack_shortcut_fwd()`, i.e. by their edge id and
+    /// a flag that is true for edges of the forward graph.
+    ///
+    /// Overriding a base edge also changes the weights of all shortcuts it is part of, and even
+    /// shortcuts that were omitted during the preparation could be needed with the new weights, so
+    /// the contraction hierarchy cannot be used. Instead every call copies all base edges into a
+    /// new `PreparationGraph` and runs a plain Dijkstra search on it, which is much slower than
+    /// `calc_path()`. This is meant for debugging and occasional what-if queries. To run many
+    /// queries with the same changed weights use `FastGraph::to_preparation_graph()` once and run
+    /// a `Dijkstra` search on the result, or prepare the changed graph again.
+    ///
+    /// Overriding an edge also overrides the other edges between the same nodes, e.g. the second
+    /// copy of an edge between two core nodes. Note that edges of the input graph that were
+    /// dropped during the preparation, because they were never part of a shortest path, are not
+    /// considered either.
+    pub fn calc_path_with_overrides(
+        &mut self,
+        graph: &FastGraph,
+        start: NodeId,
+        end: NodeId,
+        overrides: &HashMap<(EdgeId, bool), Weight>,
+    ) -> Option<ShortestPath> {
+        self.check_query(graph, start, end);
+        for &(edge_id, fwd) in overrides.keys() {
+            let edges = if fwd {
+                &graph.edges_fwd
+            } else {
+                &graph.edges_bwd
+            };
+            assert!(
+                edge_id < edges.len() && !edges[edge_id].is_shortcut(),
+                "only base edges can be overridden, invalid edge: {:?}",
+                (edge_id, fwd)
+            );
+        }
+        // the edges between core nodes are stored twice, so we apply the overrides by the nodes
+        let overrides: HashMap<(NodeId, NodeId), Weight> = overrides
+            .iter()
+            .map(|(&edge, &weight)| (graph.get_edge_nodes(edge), weight))
+            .collect();
+        let mut base_graph = PreparationGraph::new(self.num_nodes);
+        for (from, to, weight) in graph.iter_base_edges() {
+            let weight = *overrides.get(&(from, to)).unwrap_or(&weight);
+            base_graph.add_edge(from, to, weight);
+        }
+        self.get_base_dijkstra().calc_path(&base_graph, start, end)
+    }
+
+    /// Calculates the shortest path from `start` to `end` and the shortest alternative path that
+    /// differs from it in at least one edge, e.g. to find out how much a single blocked edge of
+    /// the shortest path would cost.
+    ///
+    /// Like for `calc_path_with_overrides()` the contraction hierarchy cannot be used once an
+    /// edge is missing. Instead this converts the graph using `FastGraph::to_preparation_graph()`
+    /// and runs `Dijkstra::calc_second_best()`, i.e. one plain Dijkstra search per edge of the
+    /// shortest path, which is much slower than `calc_path()`. Edges of the input graph that were
+    /// dropped during the preparation are not considered.
+    pub fn calc_second_best(
+        &mut self,
+        graph: &FastGraph,
+        start: NodeId,
+        end: NodeId,
+    ) -> Option<(ShortestPath, Option<ShortestPath>)> {
+        let best = self.calc_path(graph, start, end)?;
+        let base_graph = graph.to_preparation_graph();
+        // the edges of the shortest path, identified like for `Dijkstra::avoid_edge()`
+        let edges: Vec<(NodeId, usize)> = best
+            .get_nodes()
+            .windows(2)
+            .map(|w| {
+                let out_edges = base_graph.get_out_edges(w[0]);
+                let index = (0..out_edges.len())
+                    .filter(|&i| out_edges[i].adj_node == w[1])
+                    .min_by_key(|&i| out_edges[i].weight)
+                    .unwrap();
+                (w[0], index)
+            })
+            .collect();
+        let second_best =
+            self.get_base_dijkstra()
+                .calc_path_avoiding_each(&base_graph, start, end, &edges);
+        Some((best, second_best))
+    }
+
+    /// Returns the Dijkstra search for the base edges, which keeps its memory between the queries.
+    /// The searches run on a new graph each time, so the previous search is never continued.
+    fn get_base_dijkstra(&mut self) -> &mut Dijkstra {
+        let num_nodes = self.num_nodes;
+        let dijkstra = self
+            .base_dijkstra
+            .get_or_insert_with(|| Dijkstra::new(num_nodes));
+        dijkstra.reset(num_nodes);
+        dijkstra
+    }
+
+    /// Runs the bidirectional search and returns the weight of the shortest path and the node
+    /// where the forward and backward searches met, or `None` if there is no path
+    fn calc_meeting_node(
+        &mut self,
+        graph: &FastGraph,
+        start: NodeId,
+        end: NodeId,
+    ) -> Option<(Weight, NodeId)> {
+        self.calc_meeting_node_or_cancel(graph, start, end, None, None)
+            .unwrap()
+    }
+
+    fn calc_meeting_node_or_cancel(
+        &mut self,
+        graph: &FastGraph,
+        start: NodeId,
+        end: NodeId,
+        cancel: Option<&AtomicBool>,
+        allowed: Option<&ValidFlags>,
+    ) -> Result<Option<(Weight, NodeId)>, Cancelled> {
+        #[cfg(feature = "tracing")]
+        let span =
+            tracing::debug_span!("calc_path", start, end, num_settled = tracing::field::Empty)
+                .entered();
+        self.num_settled = 0;
+        self.exceeded_max_settled = false;
         self.heap_fwd.clear();
         self.heap_bwd.clear();
         self.valid_flags_fwd.invalidate_all();
         self.valid_flags_bwd.invalidate_all();
+        self.last_meeting_node = INVALID_NODE;
         if start == end {
-            return Some(ShortestPath::singular(start));
+            self.last_meeting_node = start;
+            return Ok(Some((WEIGHT_ZERO, start)));
         }
 
         self.update_node_fwd(start, 0, INVALID_NODE, INVALID_EDGE);
@@ -79,12 +686,26 @@ impl PathCalculator {
         self.heap_bwd.push(HeapItem::new(0, end));
 
         let mut best_weight = WEIGHT_MAX;
+        let mut stop_weight = WEIGHT_MAX;
         let mut meeting_node = INVALID_NODE;
+        let mut num_iterations = 0;
 
         loop {
             if self.heap_fwd.is_empty() && self.heap_bwd.is_empty() {
                 break;
             }
+            if self.num_settled > self.max_settled {
+                // the meeting node found so far is not necessarily the best one
+                self.exceeded_max_settled = true;
+                meeting_node = INVALID_NODE;
+                break;
+            }
+            if let Some(cancel) = cancel {
+                if num_iterations % CANCEL_CHECK_INTERVAL == 0 && cancel.load(Ordering::Relaxed) {
+                    return Err(Cancelled);
+                }
+                num_iterations += 1;
+            }
             loop {
                 if self.heap_fwd.is_empty() {
                     break;
@@ -93,26 +714,42 @@ impl PathCalculator {
                 if self.is_settled_fwd(curr.node_id) {
                     continue;
                 }
-                if curr.weight > best_weight {
+                if curr.weight > stop_weight {
                     break;
                 }
                 let begin = graph.begin_out_edges(curr.node_id);
                 let end = graph.end_out_edges(curr.node_id);
                 for edge_id in begin..end {
+                    #[cfg(feature = "prefetch")]
+                    if edge_id + 1 < end {
+                        prefetch(&self.data_fwd, graph.edges_fwd[edge_id + 1].adj_node);
+                    }
                     let adj = graph.edges_fwd[edge_id].adj_node;
+                    if allowed.map_or(false, |allowed| !allowed.is_valid(adj)) {
+                        continue;
+                    }
                     let edge_weight = graph.edges_fwd[edge_id].weight;
-                    let weight = curr.weight + edge_weight;
+                    let weight = add_weights(curr.weight, edge_weight);
                     if weight < self.get_weight_fwd(adj) {
                         self.update_node_fwd(adj, weight, curr.node_id, edge_id);
                         self.heap_fwd.push(HeapItem::new(weight, adj));
                     }
                 }
                 self.data_fwd[curr.node_id].settled = true;
-                if self.valid_flags_bwd.is_valid(curr.node_id)
-                    && curr.weight + self.get_weight_bwd(curr.node_id) < best_weight
-                {
-                    best_weight = curr.weight + self.get_weight_bwd(curr.node_id);
-                    meeting_node = curr.node_id;
+                self.num_settled += 1;
+                if self.valid_flags_bwd.is_valid(curr.node_id) {
+                    let weight = add_weights(curr.weight, self.get_weight_bwd(curr.node_id));
+                    if self.is_better_meeting_node(
+                        graph,
+                        weight,
+                        curr.node_id,
+                        best_weight,
+                        meeting_node,
+                    ) {
+                        best_weight = weight;
+                        stop_weight = self.calc_stop_weight(best_weight);
+                        meeting_node = curr.node_id;
+                    }
                 }
                 break;
             }
@@ -125,131 +762,287 @@ impl PathCalculator {
                 if self.is_settled_bwd(curr.node_id) {
                     continue;
                 }
-                if curr.weight > best_weight {
+                if curr.weight > stop_weight {
                     break;
                 }
                 let begin = graph.begin_in_edges(curr.node_id);
                 let end = graph.end_in_edges(curr.node_id);
                 for edge_id in begin..end {
+                    #[cfg(feature = "prefetch")]
+                    if edge_id + 1 < end {
+                        prefetch(&self.data_bwd, graph.edges_bwd[edge_id + 1].adj_node);
+                    }
                     let adj = graph.edges_bwd[edge_id].adj_node;
+                    if allowed.map_or(false, |allowed| !allowed.is_valid(adj)) {
+                        continue;
+                    }
                     let edge_weight = graph.edges_bwd[edge_id].weight;
-                    let weight = curr.weight + edge_weight;
+                    let weight = add_weights(curr.weight, edge_weight);
                     if weight < self.get_weight_bwd(adj) {
                         self.update_node_bwd(adj, weight, curr.node_id, edge_id);
                         self.heap_bwd.push(HeapItem::new(weight, adj));
                     }
                 }
                 self.data_bwd[curr.node_id].settled = true;
-                if self.valid_flags_fwd.is_valid(curr.node_id)
-                    && curr.weight + self.get_weight_fwd(curr.node_id) < best_weight
-                {
-                    best_weight = curr.weight + self.get_weight_fwd(curr.node_id);
-                    meeting_node = curr.node_id;
+                self.num_settled += 1;
+                if self.valid_flags_fwd.is_valid(curr.node_id) {
+                    let weight = add_weights(curr.weight, self.get_weight_fwd(curr.node_id));
+                    if self.is_better_meeting_node(
+                        graph,
+                        weight,
+                        curr.node_id,
+                        best_weight,
+                        meeting_node,
+                    ) {
+                        best_weight = weight;
+                        stop_weight = self.calc_stop_weight(best_weight);
+                        meeting_node = curr.node_id;
+                    }
                 }
                 break;
             }
         }
 
+        #[cfg(feature = "tracing")]
+        span.record("num_settled", self.num_settled);
+        self.last_meeting_node = meeting_node;
         if meeting_node == INVALID_NODE {
-            return None;
+            Ok(None)
         } else {
-            let node_ids = self.extract_nodes(graph, start, end, meeting_node);
-            return Some(ShortestPath::new(start, end, best_weight, node_ids));
+            Ok(Some((best_weight, meeting_node)))
         }
     }
 
-    fn extract_nodes(
+    /// Returns the nodes that were settled by the forward search of the last query along with
+    /// their weights. Note that the forward search only explores the upward search space of the
+    /// start node, so these weights are upper bounds of the actual distances from the start node,
+    /// and many nodes that are close to it will be missing. This iterates over all nodes of the
+    /// graph to find the settled ones.
+    pub fn forward_settled_nodes(&self) -> impl Iterator<Item = (NodeId, Weight)> + '_ {
+        (0..self.num_nodes)
+            .filter(move |&node| self.is_settled_fwd(node))
+            .map(move |node| (node, self.data_fwd[node].weight))
+    }
+
+    /// Returns the node where the forward and the backward search met in the last query, i.e.
+    /// the node with the highest rank on the shortest path. Returns `None` if no path was found or
+    /// the last calculation did not determine a meeting node, like `calc_weights_batch()`.
+    pub fn last_meeting_node(&self) -> Option<NodeId> {
+        if self.last_meeting_node == INVALID_NODE {
+            None
+        } else {
+            Some(self.last_meeting_node)
+        }
+    }
+
+    /// Allows the queries to return paths that are up to `(1 + eps)` times longer than the
+    /// shortest path, which makes them faster because the searches can be stopped earlier. With
+    /// `eps > 0` the results are no longer guaranteed to be optimal. The default is zero, i.e.
+    /// exact results. This only affects the single queries like `calc_path()` and
+    /// `calc_weight()`, but not the batch calculations.
+    pub fn set_approximation_factor(&mut self, eps: f64) {
+        assert!(
+            eps >= 0.0,
+            "the approximation factor must not be negative, but was {}",
+            eps
+        );
+        self.approximation_factor = eps;
+    }
+
+    /// Sets which node is used as meeting node when there are several shortest paths with the
+    /// same weight that meet at different nodes. This only matters for the returned path, not for
+    /// its weight. The default is `MeetingNodeTieBreak::FirstFound`. Only meeting nodes that were
+    /// found by the searches are taken into account, so with an approximation factor larger than
+    /// zero some of them can be missed.
+    pub fn set_meeting_node_tie_break(&mut self, tie_break: MeetingNodeTieBreak) {
+        self.meeting_node_tie_break = tie_break;
+    }
+
+    /// Stops the queries once the forward and the backward search together settled more than
+    /// `max_settled` nodes, which limits the effort per query independently of the hardware. Such
+    /// queries return no path, and `calc_path_or_reason()` returns
+    /// `NoPathReason::ExceededMaxSettled`. The default is no limit. Like
+    /// `set_approximation_factor()` this only affects the single queries, but not the batch
+    /// calculations.
+    pub fn set_max_settled(&mut self, max_settled: usize) {
+        self.max_settled = max_settled;
+    }
+
+    /// Returns the number of nodes settled by the forward and the backward search of the last
+    /// query, which is a measure of the query effort that does not depend on the hardware. Returns
+    /// zero if the last calculation did not run a bidirectional search, like
+    /// `calc_weights_batch()`.
+    pub fn get_num_settled(&self) -> usize {
+        self.num_settled
+    }
+
+    /// Runs the forward search from `start` until all nodes of the upward search space are settled
+    fn calc_fwd_tree(&mut self, graph: &FastGraph, start: NodeId) {
+        self.last_meeting_node = INVALID_NODE;
+        self.num_settled = 0;
+        self.heap_fwd.clear();
+        self.valid_flags_fwd.invalidate_all();
+        self.update_node_fwd(start, 0, INVALID_NODE, INVALID_EDGE);
+        self.heap_fwd.push(HeapItem::new(0, start));
+        while let Some(curr) = self.heap_fwd.pop() {
+            if self.is_settled_fwd(curr.node_id) {
+                continue;
+            }
+            for edge_id in graph.begin_out_edges(curr.node_id)..graph.end_out_edges(curr.node_id) {
+                let adj = graph.edges_fwd[edge_id].adj_node;
+                let weight = add_weights(curr.weight, graph.edges_fwd[edge_id].weight);
+                if weight < self.get_weight_fwd(adj) {
+                    self.update_node_fwd(adj, weight, curr.node_id, edge_id);
+                    self.heap_fwd.push(HeapItem::new(weight, adj));
+                }
+            }
+            self.data_fwd[curr.node_id].settled = true;
+        }
+    }
+
+    /// Runs the backward search from `end` and combines it with the (complete) forward search of
+    /// the last call to `calc_fwd_tree()`
+    fn calc_weight_from_fwd_tree(&mut self, graph: &FastGraph, end: NodeId) -> Option<Weight> {
+        self.heap_bwd.clear();
+        self.valid_flags_bwd.invalidate_all();
+        self.update_node_bwd(end, 0, INVALID_NODE, INVALID_EDGE);
+        self.heap_bwd.push(HeapItem::new(0, end));
+        let mut best_weight = WEIGHT_MAX;
+        while let Some(curr) = self.heap_bwd.pop() {
+            if self.is_settled_bwd(curr.node_id) {
+                continue;
+            }
+            if curr.weight >= best_weight {
+                break;
+            }
+            for edge_id in graph.begin_in_edges(curr.node_id)..graph.end_in_edges(curr.node_id) {
+                let adj = graph.edges_bwd[edge_id].adj_node;
+                let weight = add_weights(curr.weight, graph.edges_bwd[edge_id].weight);
+                if weight < self.get_weight_bwd(adj) {
+                    self.update_node_bwd(adj, weight, curr.node_id, edge_id);
+                    self.heap_bwd.push(HeapItem::new(weight, adj));
+                }
+            }
+            self.data_bwd[curr.node_id].settled = true;
+            if self.valid_flags_fwd.is_valid(curr.node_id) {
+                best_weight =
+                    best_weight.min(add_weights(curr.weight, self.get_weight_fwd(curr.node_id)));
+            }
+        }
+        if best_weight == WEIGHT_MAX {
+            None
+        } else {
+            Some(best_weight)
+        }
+    }
+
+    /// Returns true if `node` reached with `weight` should replace the current meeting node
+    fn is_better_meeting_node(
         &self,
         graph: &FastGraph,
-        _start: NodeId,
-        end: NodeId,
-        meeting_node: NodeId,
-    ) -> Vec<NodeId> {
-        assert_ne!(meeting_node, INVALID_NODE);
-        assert!(self.valid_flags_fwd.is_valid(meeting_node));
-        assert!(self.valid_flags_bwd.is_valid(meeting_node));
-        let mut result = Vec::new();
+        weight: Weight,
+        node: NodeId,
+        best_weight: Weight,
+        best_node: NodeId,
+    ) -> bool {
+        if weight != best_weight || best_node == INVALID_NODE {
+            return weight < best_weight;
+        }
+        match self.meeting_node_tie_break {
+            MeetingNodeTieBreak::FirstFound => false,
+            MeetingNodeTieBreak::HighestRank => graph.get_rank(node) > graph.get_rank(best_node),
+            MeetingNodeTieBreak::LowestRank => graph.get_rank(node) < graph.get_rank(best_node),
+            MeetingNodeTieBreak::LowestNodeId => node < best_node,
+        }
+    }
+
+    /// Nodes with a larger weight cannot improve the best weight found so far by more than the
+    /// approximation factor, so we do not need to explore them any further.
+    fn calc_stop_weight(&self, best_weight: Weight) -> Weight {
+        if self.approximation_factor == 0.0 {
+            best_weight
+        } else {
+            (best_weight as f64 / (1.0 + self.approximation_factor)) as Weight
+        }
+    }
+
+    fn check_query(&self, graph: &FastGraph, start: NodeId, end: NodeId) {
+        assert_eq!(
+            graph.get_num_nodes(),
+            self.num_nodes,
+            "given graph has invalid node count"
+        );
+        assert!(start < self.num_nodes, "invalid start node");
+        assert!(end < self.num_nodes, "invalid end node");
+    }
+
+    fn extract_nodes(&self, graph: &FastGraph, end: NodeId, meeting_node: NodeId) -> Vec<NodeId> {
+        let mut result = self.extract_base_edges(graph, meeting_node, &PathCalculator::tail_node);
+        result.push(end);
+        result
+    }
+
+    /// Returns the (packed) edges of the shortest path found by the last search in the order they
+    /// are traversed
+    fn extract_contracted_edges(&self, meeting_node: NodeId) -> Vec<(EdgeId, bool)> {
+        let mut result = vec![];
+        if !self.valid_flags_fwd.is_valid(meeting_node) {
+            // the search was not run, because the start and end nodes are the same
+            return result;
+        }
         let mut node = meeting_node;
         while self.data_fwd[node].inc_edge != INVALID_EDGE {
-            PathCalculator::unpack_fwd(graph, &mut result, self.data_fwd[node].inc_edge, true);
+            result.push((self.data_fwd[node].inc_edge, true));
             node = self.data_fwd[node].parent;
         }
         result.reverse();
         node = meeting_node;
         while self.data_bwd[node].inc_edge != INVALID_EDGE {
-            PathCalculator::unpack_bwd(graph, &mut result, self.data_bwd[node].inc_edge, false);
+            result.push((self.data_bwd[node].inc_edge, false));
             node = self.data_bwd[node].parent;
         }
-        result.push(end);
         result
     }
 
-    fn unpack_fwd(graph: &FastGraph, nodes: &mut Vec<NodeId>, edge_id: EdgeId, reverse: bool) {
-        if !graph.edges_fwd[edge_id].is_shortcut() {
-            nodes.push(graph.edges_fwd[edge_id].base_node);
-            return;
-        }
-        if reverse {
-            PathCalculator::unpack_fwd(
-                graph,
-                nodes,
-                graph.edges_fwd[edge_id].replaced_out_edge,
-                reverse,
-            );
-            PathCalculator::unpack_bwd(
-                graph,
-                nodes,
-                graph.edges_fwd[edge_id].replaced_in_edge,
-                reverse,
-            );
-        } else {
-            PathCalculator::unpack_bwd(
-                graph,
-                nodes,
-                graph.edges_fwd[edge_id].replaced_in_edge,
-                reverse,
-            );
-            PathCalculator::unpack_fwd(
-                graph,
-                nodes,
-                graph.edges_fwd[edge_id].replaced_out_edge,
-                reverse,
-            );
-        }
+    /// Unpacks the shortest path found by the last search and maps each of its base edges to an
+    /// item using `f`, in the order the edges are traversed.
+    fn extract_base_edges<T, F>(&self, graph: &FastGraph, meeting_node: NodeId, f: &F) -> Vec<T>
+    where
+        F: Fn(&FastGraph, EdgeId, bool) -> T,
+    {
+        self.extract_edges_down_to(graph, meeting_node, 0, f)
     }
 
-    fn unpack_bwd(graph: &FastGraph, nodes: &mut Vec<NodeId>, edge_id: EdgeId, reverse: bool) {
-        if !graph.edges_bwd[edge_id].is_shortcut() {
-            nodes.push(graph.edges_bwd[edge_id].adj_node);
-            return;
+    /// Like `extract_base_edges()`, but only unpacks the shortcuts down to the given rank, see
+    /// `FastGraph::unpack_down_to()`
+    fn extract_edges_down_to<T, F>(
+        &self,
+        graph: &FastGraph,
+        meeting_node: NodeId,
+        min_rank: usize,
+        f: &F,
+    ) -> Vec<T>
+    where
+        F: Fn(&FastGraph, EdgeId, bool) -> T,
+    {
+        assert_ne!(meeting_node, INVALID_NODE);
+        assert!(self.valid_flags_fwd.is_valid(meeting_node));
+        assert!(self.valid_flags_bwd.is_valid(meeting_node));
+        let mut result = Vec::new();
+        let mut node = meeting_node;
+        while self.data_fwd[node].inc_edge != INVALID_EDGE {
+            let edge = (self.data_fwd[node].inc_edge, true);
+            graph.unpack_down_to(&mut result, edge, true, min_rank, f);
+            node = self.data_fwd[node].parent;
         }
-        if reverse {
-            PathCalculator::unpack_fwd(
-                graph,
-                nodes,
-                graph.edges_bwd[edge_id].replaced_out_edge,
-                reverse,
-            );
-            PathCalculator::unpack_bwd(
-                graph,
-                nodes,
-                graph.edges_bwd[edge_id].replaced_in_edge,
-                reverse,
-            );
-        } else {
-            PathCalculator::unpack_bwd(
-                graph,
-                nodes,
-                graph.edges_bwd[edge_id].replaced_in_edge,
-                reverse,
-            );
-            PathCalculator::unpack_fwd(
-                graph,
-                nodes,
-                graph.edges_bwd[edge_id].replaced_out_edge,
-                reverse,
-            );
+        result.reverse();
+        node = meeting_node;
+        while self.data_bwd[node].inc_edge != INVALID_EDGE {
+            let edge = (self.data_bwd[node].inc_edge, false);
+            graph.unpack_down_to(&mut result, edge, false, min_rank, f);
+            node = self.data_bwd[node].parent;
         }
+        result
     }
 
     fn update_node_fwd(&mut self, node: NodeId, weight: Weight, parent: NodeId, inc_edge: EdgeId) {
@@ -313,10 +1106,75 @@ impl Data {
 
 #[cfg(test)]
 mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
     use crate::fast_graph::FastGraphEdge;
+    use crate::input_graph::InputGraph;
+    use crate::queue::DialQueue;
+    use crate::test_utils::random_input_graph;
+    use crate::{prepare, prepare_up_to, prepare_with_order};
 
     use super::*;
 
+    #[test]
+    fn calc_second_best() {
+        // 0 -> 1 -> 2
+        // |         |
+        // 3 -> 4 -> 5 -> 6
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 1);
+        g.add_edge(1, 2, 1);
+        g.add_edge(2, 5, 1);
+        g.add_edge(0, 3, 2);
+        g.add_edge(3, 4, 1);
+        g.add_edge(4, 5, 1);
+        g.add_edge(5, 6, 1);
+        g.freeze();
+        let fast_graph = prepare(&g);
+        let mut calc = PathCalculator::new(fast_graph.get_num_nodes());
+        let (best, second_best) = calc.calc_second_best(&fast_graph, 0, 6).unwrap();
+        assert_eq!(ShortestPath::new(0, 6, 4, vec![0, 1, 2, 5, 6]), best);
+        assert_eq!(
+            Some(ShortestPath::new(0, 6, 5, vec![0, 3, 4, 5, 6])),
+            second_best
+        );
+        // there is no alternative for the last edge
+        let (best, second_best) = calc.calc_second_best(&fast_graph, 5, 6).unwrap();
+        assert_eq!(ShortestPath::new(5, 6, 1, vec![5, 6]), best);
+        assert_eq!(None, second_best);
+        assert!(calc.calc_second_best(&fast_graph, 6, 0).is_none());
+
+        // the same weights as a plain Dijkstra search on the base edges
+        let g = random_input_graph(100, 2.5, 20, 17);
+        let fast_graph = prepare(&g);
+        let base_graph = fast_graph.to_preparation_graph();
+        let mut dijkstra = Dijkstra::new(fast_graph.get_num_nodes());
+        let mut calc = PathCalculator::new(fast_graph.get_num_nodes());
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..50 {
+            let s = rng.gen_range(0, 100);
+            let t = rng.gen_range(0, 100);
+            let result = calc.calc_second_best(&fast_graph, s, t);
+            let expected = dijkstra.calc_second_best(&base_graph, s, t);
+            assert_eq!(
+                expected.as_ref().map(|(best, _)| best.get_weight()),
+                result.as_ref().map(|(best, _)| best.get_weight())
+            );
+            if let (Some((best, second_best)), Some((expected_best, expected_second_best))) =
+                (result, expected)
+            {
+                // with several shortest paths the alternatives can differ
+                if best.get_nodes() == expected_best.get_nodes() {
+                    assert_eq!(
+                        expected_second_best.map(|p| p.get_weight()),
+                        second_best.map(|p| p.get_weight())
+                    );
+                }
+            }
+        }
+    }
+
     #[test]
     fn unpack_fwd_single() {
         // 0 -> 1
@@ -324,7 +1182,7 @@ mod tests {
         g.edges_fwd
             .push(FastGraphEdge::new(0, 1, 3, INVALID_EDGE, INVALID_EDGE));
         let mut nodes = vec![];
-        PathCalculator::unpack_fwd(&g, &mut nodes, 0, false);
+        g.unpack_fwd(&mut nodes, 0, false, &PathCalculator::tail_node);
         assert_eq!(nodes, vec![0]);
     }
 
@@ -339,7 +1197,609 @@ mod tests {
             .push(FastGraphEdge::new(2, 1, 3, INVALID_EDGE, INVALID_EDGE));
         g.first_edge_ids_fwd = vec![0, 2, 0, 0];
         let mut nodes = vec![];
-        PathCalculator::unpack_fwd(&g, &mut nodes, 1, false);
+        g.unpack_fwd(&mut nodes, 1, false, &PathCalculator::tail_node);
         assert_eq!(nodes, vec![1, 0]);
     }
+
+    #[test]
+    fn unpack_deeply_nested_shortcuts() {
+        // 0 -> 1 -> 2 -> ... -> n-1, contracting the nodes from left to right yields shortcuts
+        // 0 -> 2, 0 -> 3, ..., each replacing the previous one
+        let n = 200_000;
+        let mut g = InputGraph::new();
+        for node in 0..n - 1 {
+            g.add_edge(node, node + 1, 1);
+        }
+        g.freeze();
+        let mut order: Vec<NodeId> = (1..n - 1).collect();
+        order.push(0);
+        order.push(n - 1);
+        let fast_graph = prepare_with_order(&g, &order).unwrap();
+        let mut calc = PathCalculator::new(n);
+        let path = calc.calc_path(&fast_graph, 0, n - 1).unwrap();
+        assert_eq!(n - 1, path.get_weight());
+        assert!(path.get_nodes().iter().copied().eq(0..n));
+    }
+
+    #[test]
+    fn calc_path_with_overrides() {
+        // 0 -> 1 -> 3
+        // |         ^
+        // v         |
+        // 2 --------/
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 1);
+        g.add_edge(1, 3, 1);
+        g.add_edge(0, 2, 2);
+        g.add_edge(2, 3, 2);
+        g.freeze();
+        let fast_graph = prepare_with_order(&g, &[1, 2, 0, 3]).unwrap();
+        let mut calc = PathCalculator::new(4);
+        let no_overrides = HashMap::new();
+        let path = calc
+            .calc_path_with_overrides(&fast_graph, 0, 3, &no_overrides)
+            .unwrap();
+        assert_eq!(&vec![0, 1, 3], path.get_nodes());
+        assert_eq!(2, path.get_weight());
+        // node 1 has the lowest rank, so the edge 0->1 is an edge of the backward graph
+        let edge_id = (0..fast_graph.get_num_in_edges())
+            .find(|&e| fast_graph.edges_bwd[e].base_node == 1)
+            .unwrap();
+        let mut overrides = HashMap::new();
+        overrides.insert((edge_id, false), 10);
+        let path = calc
+            .calc_path_with_overrides(&fast_graph, 0, 3, &overrides)
+            .unwrap();
+        assert_eq!(&vec![0, 2, 3], path.get_nodes());
+        assert_eq!(4, path.get_weight());
+        // the regular query is not affected
+        assert_eq!(2, calc.calc_path(&fast_graph, 0, 3).unwrap().get_weight());
+
+        // without contraction the edges are stored twice, overriding either copy must work
+        let core_graph = prepare_up_to(&g, 0.0);
+        let copies: Vec<_> = core_graph
+            .get_base_out_edges(0)
+            .into_iter()
+            .filter(|&(_, adj, _)| adj == 1)
+            .map(|(edge, _, _)| edge)
+            .collect();
+        assert_eq!(2, copies.len());
+        for edge in copies {
+            let overrides = [(edge, 10)].iter().copied().collect();
+            let path = calc
+                .calc_path_with_overrides(&core_graph, 0, 3, &overrides)
+                .unwrap();
+            assert_eq!(&vec![0, 2, 3], path.get_nodes());
+        }
+    }
+
+    #[test]
+    fn calc_path_forbidding_edges() {
+        // 3 - 0 - 2 - 1, where 2 is a virtual node on the edge between 0 and 1
+        let mut g = InputGraph::new();
+        g.add_edge_bidir(0, 1, 9);
+        g.add_edge_bidir(2, 0, 4);
+        g.add_edge_bidir(2, 1, 6);
+        g.add_edge_bidir(0, 3, 1);
+        g.freeze();
+        let fast_graph = prepare(&g);
+        let edge = |from, to| {
+            fast_graph
+                .get_base_out_edges(from)
+                .into_iter()
+                .find(|&(_, adj, _)| adj == to)
+                .unwrap()
+                .0
+        };
+        let mut calc = PathCalculator::new(4);
+        let path = calc
+            .calc_path_forbidding_edges(&fast_graph, 2, 3, None, None)
+            .unwrap();
+        assert_eq!(&vec![2, 0, 3], path.get_nodes());
+        assert_eq!(5, path.get_weight());
+        // no u-turn at the start
+        let path = calc
+            .calc_path_forbidding_edges(&fast_graph, 2, 3, Some(edge(2, 0)), None)
+            .unwrap();
+        assert_eq!(&vec![2, 1, 0, 3], path.get_nodes());
+        assert_eq!(16, path.get_weight());
+        // no u-turn at the end
+        let path = calc
+            .calc_path_forbidding_edges(&fast_graph, 3, 2, None, Some(edge(0, 2)))
+            .unwrap();
+        assert_eq!(&vec![3, 0, 1, 2], path.get_nodes());
+        assert_eq!(16, path.get_weight());
+        // forbidden edges that are not part of the shortest path do not matter
+        let path = calc
+            .calc_path_forbidding_edges(&fast_graph, 3, 2, Some(edge(0, 1)), Some(edge(1, 2)))
+            .unwrap();
+        assert_eq!(5, path.get_weight());
+        // a single edge is both the first and the last edge
+        let path = calc
+            .calc_path_forbidding_edges(&fast_graph, 2, 1, None, Some(edge(2, 1)))
+            .unwrap();
+        assert_eq!(&vec![2, 0, 1], path.get_nodes());
+        assert_eq!(
+            None,
+            calc.calc_path_forbidding_edges(&fast_graph, 3, 0, Some(edge(3, 0)), None)
+        );
+    }
+
+    #[test]
+    fn calc_path_forbidding_core_edges() {
+        // 3 - 0 - 2 - 1, but without contracting any nodes
+        let mut g = InputGraph::new();
+        g.add_edge_bidir(0, 1, 9);
+        g.add_edge_bidir(2, 0, 4);
+        g.add_edge_bidir(2, 1, 6);
+        g.add_edge_bidir(0, 3, 1);
+        g.freeze();
+        let fast_graph = prepare_up_to(&g, 0.0);
+        // the edges between core nodes are stored twice, forbidding either copy must work
+        let copies: Vec<_> = fast_graph
+            .get_base_out_edges(2)
+            .into_iter()
+            .filter(|&(_, adj, _)| adj == 0)
+            .map(|(edge, _, _)| edge)
+            .collect();
+        assert_eq!(2, copies.len());
+        let mut calc = PathCalculator::new(4);
+        for edge in copies {
+            let path = calc
+                .calc_path_forbidding_edges(&fast_graph, 2, 3, Some(edge), None)
+                .unwrap();
+            assert_eq!(&vec![2, 1, 0, 3], path.get_nodes());
+        }
+    }
+
+    #[test]
+    fn round_trip_weights() {
+        // 0 -> 1 -> 2
+        // ^         |
+        //  \-------/
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 3);
+        g.add_edge(1, 2, 4);
+        g.add_edge(2, 0, 10);
+        g.add_edge(3, 2, 1);
+        g.freeze();
+        let fast_graph = prepare_with_order(&g, &[0, 1, 2, 3]).unwrap();
+        let mut calc = PathCalculator::new(4);
+        assert_eq!(
+            (Some(7), Some(10)),
+            calc.calc_round_trip_weights(&fast_graph, 0, 2)
+        );
+        assert_eq!(
+            (Some(14), Some(3)),
+            calc.calc_round_trip_weights(&fast_graph, 1, 0)
+        );
+        assert_eq!(
+            (None, Some(11)),
+            calc.calc_round_trip_weights(&fast_graph, 0, 3)
+        );
+    }
+
+    #[test]
+    fn approximation_factor() {
+        let g = random_input_graph(500, 2.5, 100, 11);
+        let n = g.get_num_nodes();
+        let fast_graph = prepare(&g);
+        let preparation_graph = PreparationGraph::from_input_graph(&g);
+        let mut dijkstra = Dijkstra::new(n);
+        let mut exact = PathCalculator::new(n);
+        let mut rng = StdRng::seed_from_u64(3);
+        for &eps in &[0.05, 0.5] {
+            let mut calc = PathCalculator::new(n);
+            calc.set_approximation_factor(eps);
+            let mut num_settled = 0;
+            let mut num_settled_exact = 0;
+            for _ in 0..200 {
+                let s = rng.gen_range(0, n);
+                let t = rng.gen_range(0, n);
+                let opt = dijkstra
+                    .calc_path(&preparation_graph, s, t)
+                    .map(|p| p.get_weight());
+                let approx = calc.calc_path(&fast_graph, s, t);
+                num_settled += calc.get_num_settled();
+                assert_eq!(opt, exact.calc_weight(&fast_graph, s, t));
+                num_settled_exact += exact.get_num_settled();
+                match (opt, approx) {
+                    (None, None) => {}
+                    (Some(opt), Some(approx)) => {
+                        assert!(approx.get_weight() >= opt);
+                        assert!(approx.get_weight() as f64 <= (1.0 + eps) * opt as f64);
+                    }
+                    _ => panic!("no agreement for query {} -> {}", s, t),
+                }
+            }
+            assert!(num_settled <= num_settled_exact);
+        }
+    }
+
+    #[test]
+    fn meeting_node_tie_break() {
+        // 0 -> 1 -> 3 and 0 -> 2 -> 3 have the same weight, node 1 has the highest rank
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 1);
+        g.add_edge(1, 3, 1);
+        g.add_edge(0, 2, 1);
+        g.add_edge(2, 3, 1);
+        g.freeze();
+        let fast_graph = prepare_with_order(&g, &[0, 3, 2, 1]).unwrap();
+        let mut calc = PathCalculator::new(fast_graph.get_num_nodes());
+        let default_path = calc.calc_path(&fast_graph, 0, 3).unwrap();
+        calc.set_meeting_node_tie_break(MeetingNodeTieBreak::FirstFound);
+        assert_eq!(Some(default_path), calc.calc_path(&fast_graph, 0, 3));
+        for (tie_break, expected) in [
+            (MeetingNodeTieBreak::HighestRank, 1),
+            (MeetingNodeTieBreak::LowestRank, 2),
+            (MeetingNodeTieBreak::LowestNodeId, 1),
+        ] {
+            calc.set_meeting_node_tie_break(tie_break);
+            let path = calc.calc_path(&fast_graph, 0, 3).unwrap();
+            assert_eq!(2, path.get_weight());
+            assert_eq!(&vec![0, expected, 3], path.get_nodes());
+            assert_eq!(Some(expected), calc.last_meeting_node());
+        }
+    }
+
+    #[test]
+    fn dial_queue() {
+        let g = random_input_graph(300, 2.5, 50, 5);
+        let n = g.get_num_nodes();
+        let fast_graph = prepare(&g);
+        let preparation_graph = PreparationGraph::from_input_graph(&g);
+        let mut dijkstra = Dijkstra::<DialQueue>::with_queue(n, 0);
+        let mut binary_heap = PathCalculator::new(n);
+        let mut dial = PathCalculator::<DialQueue>::with_queue(n, 0);
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..200 {
+            let s = rng.gen_range(0, n);
+            let t = rng.gen_range(0, n);
+            let expected = binary_heap.calc_path(&fast_graph, s, t);
+            assert_eq!(expected, dial.calc_path(&fast_graph, s, t));
+            assert_eq!(expected, dijkstra.calc_path(&preparation_graph, s, t));
+        }
+    }
+
+    #[test]
+    fn edge_path_contracted() {
+        let g = random_input_graph(200, 2.5, 20, 11);
+        let n = g.get_num_nodes();
+        let fast_graph = prepare(&g);
+        let mut calc = PathCalculator::new(n);
+        let mut rng = StdRng::seed_from_u64(8);
+        for _ in 0..100 {
+            let s = rng.gen_range(0, n);
+            let t = rng.gen_range(0, n);
+            let path = calc.calc_path(&fast_graph, s, t);
+            let edges = calc.calc_edge_path_contracted(&fast_graph, s, t);
+            assert_eq!(path.is_some(), edges.is_some());
+            let (path, edges) = match (path, edges) {
+                (Some(path), Some(edges)) => (path, edges),
+                _ => continue,
+            };
+            let edge = |(id, fwd): (EdgeId, bool)| {
+                if fwd {
+                    &fast_graph.edges_fwd[id]
+                } else {
+                    &fast_graph.edges_bwd[id]
+                }
+            };
+            let weight: Weight = edges.iter().map(|&e| edge(e).weight).sum();
+            assert_eq!(path.get_weight(), weight);
+            let unpacked: Vec<(EdgeId, bool)> = edges
+                .iter()
+                .flat_map(|&(id, fwd)| {
+                    if fwd {
+                        fast_graph.unpack_shortcut_fwd(id)
+                    } else {
+                        fast_graph.unpack_shortcut_bwd(id)
+                    }
+                })
+                .collect();
+            assert_eq!(path.get_nodes().len() - 1, unpacked.len());
+            assert!(edges.len() <= unpacked.len());
+        }
+        assert_eq!(
+            Some(vec![]),
+            calc.calc_edge_path_contracted(&fast_graph, 4, 4)
+        );
+    }
+
+    #[test]
+    fn path_iter() {
+        let g = random_input_graph(200, 2.5, 20, 10);
+        let n = g.get_num_nodes();
+        let fast_graph = prepare(&g);
+        let mut calc = PathCalculator::new(n);
+        let mut rng = StdRng::seed_from_u64(6);
+        for _ in 0..100 {
+            let s = rng.gen_range(0, n);
+            let t = rng.gen_range(0, n);
+            let expected = calc
+                .calc_path(&fast_graph, s, t)
+                .map(|p| p.get_nodes().clone());
+            let nodes = calc
+                .calc_path_iter(&fast_graph, s, t)
+                .map(|nodes| nodes.collect::<Vec<_>>());
+            assert_eq!(expected, nodes);
+        }
+        assert_eq!(
+            vec![5],
+            calc.calc_path_iter(&fast_graph, 5, 5)
+                .unwrap()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn coarse_path() {
+        let g = random_input_graph(200, 2.5, 20, 9);
+        let n = g.get_num_nodes();
+        let fast_graph = prepare(&g);
+        let mut calc = PathCalculator::new(n);
+        let mut rng = StdRng::seed_from_u64(4);
+        for _ in 0..100 {
+            let s = rng.gen_range(0, n);
+            let t = rng.gen_range(0, n);
+            let path = calc.calc_path(&fast_graph, s, t);
+            for &min_rank in &[0, n / 2, n - 10, n] {
+                let coarse = calc.calc_coarse_path(&fast_graph, s, t, min_rank);
+                let expected = path.as_ref().map(|p| {
+                    let nodes = p.get_nodes();
+                    let last = nodes.len() - 1;
+                    nodes
+                        .iter()
+                        .enumerate()
+                        .filter(|&(i, &node)| {
+                            i == 0 || i == last || fast_graph.get_rank(node) >= min_rank
+                        })
+                        .map(|(_, &node)| node)
+                        .collect::<Vec<_>>()
+                });
+                assert_eq!(expected, coarse);
+            }
+        }
+        assert_eq!(
+            Some(vec![3]),
+            calc.calc_coarse_path(&fast_graph, 3, 3, n / 2)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid start node")]
+    fn empty_graph() {
+        // there are no valid node ids, so every query is invalid
+        let mut g = InputGraph::new();
+        g.freeze();
+        let fast_graph = prepare(&g);
+        assert_eq!(0, fast_graph.get_num_nodes());
+        let mut calc = PathCalculator::new(0);
+        calc.calc_path(&fast_graph, 0, 0);
+    }
+
+    #[test]
+    fn single_node() {
+        let fast_graph = prepare(&InputGraph::from_edges(1, vec![]));
+        let mut calc = PathCalculator::new(1);
+        let path = calc.calc_path(&fast_graph, 0, 0).unwrap();
+        assert_eq!(0, path.get_weight());
+        assert_eq!(&vec![0], path.get_nodes());
+        assert_eq!(
+            vec![Some(0)],
+            calc.calc_weights_batch(&fast_graph, &[(0, 0)])
+        );
+    }
+
+    #[test]
+    fn calc_path_masked() {
+        // 0 -> 1 -> 2
+        //  \-> 3 ->/
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 1);
+        g.add_edge(1, 2, 1);
+        g.add_edge(0, 3, 5);
+        g.add_edge(3, 2, 5);
+        g.freeze();
+        let fast_graph = prepare_with_order(&g, &[0, 2, 3, 1]).unwrap();
+        let mut calc = PathCalculator::new(4);
+        let mut allowed = ValidFlags::new(4);
+        for node in [0, 2, 3] {
+            allowed.set_valid(node);
+        }
+        let path = calc.calc_path_masked(&fast_graph, 0, 2, &allowed).unwrap();
+        assert_eq!(10, path.get_weight());
+        assert_eq!(&vec![0, 3, 2], path.get_nodes());
+        assert!(calc.calc_path_masked(&fast_graph, 1, 2, &allowed).is_none());
+
+        let mask = fast_graph.calc_upward_closed_mask(&[0, 2]);
+        assert!((0..4).all(|node| mask.is_valid(node)));
+        let path = calc.calc_path_masked(&fast_graph, 0, 2, &mask).unwrap();
+        assert_eq!(2, path.get_weight());
+        let mask = fast_graph.calc_upward_closed_mask(&[1]);
+        assert!(mask.is_valid(1));
+        assert!((0..4)
+            .filter(|&node| node != 1)
+            .all(|node| !mask.is_valid(node)));
+    }
+
+    #[test]
+    fn last_meeting_node() {
+        // 0 -> 1 -> 2 -> 3
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 3);
+        g.add_edge(1, 2, 4);
+        g.add_edge(2, 3, 1);
+        g.freeze();
+        let fast_graph = prepare_with_order(&g, &[1, 3, 0, 2]).unwrap();
+        let mut calc = PathCalculator::new(4);
+        assert_eq!(None, calc.last_meeting_node());
+        calc.calc_path(&fast_graph, 0, 3).unwrap();
+        assert_eq!(Some(2), calc.last_meeting_node());
+        assert_eq!(3, fast_graph.get_rank(2));
+        calc.calc_weight(&fast_graph, 1, 1).unwrap();
+        assert_eq!(Some(1), calc.last_meeting_node());
+        assert_eq!(None, calc.calc_path(&fast_graph, 3, 0));
+        assert_eq!(None, calc.last_meeting_node());
+        calc.calc_path(&fast_graph, 0, 1).unwrap();
+        calc.calc_weights_batch(&fast_graph, &[(0, 1)]);
+        assert_eq!(None, calc.last_meeting_node());
+    }
+
+    #[test]
+    fn no_path_reason() {
+        // 0 -> 1
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 3);
+        g.freeze();
+        let fast_graph = prepare_with_order(&g, &[0, 1]).unwrap();
+        let mut calc = PathCalculator::new(2);
+        assert_eq!(
+            3,
+            calc.calc_path_or_reason(&fast_graph, 0, 1)
+                .unwrap()
+                .get_weight()
+        );
+        assert_eq!(
+            Err(NoPathReason::Disconnected),
+            calc.calc_path_or_reason(&fast_graph, 1, 0)
+        );
+    }
+
+    #[test]
+    fn max_settled() {
+        // 0 -> 1 -> 2 -> 3 -> 4 -> 5, the searches need to settle all nodes
+        let mut g = InputGraph::new();
+        for i in 0..5 {
+            g.add_edge(i, i + 1, 1);
+        }
+        g.freeze();
+        let fast_graph = prepare_with_order(&g, &[0, 1, 2, 3, 4, 5]).unwrap();
+        let mut calc = PathCalculator::new(6);
+        calc.calc_path(&fast_graph, 0, 5).unwrap();
+        let num_settled = calc.get_num_settled();
+
+        calc.set_max_settled(num_settled - 2);
+        assert_eq!(
+            Err(NoPathReason::ExceededMaxSettled),
+            calc.calc_path_or_reason(&fast_graph, 0, 5)
+        );
+        assert_eq!(None, calc.last_meeting_node());
+        assert_eq!(None, calc.calc_weight(&fast_graph, 0, 5));
+        assert_eq!(
+            Err(NoPathReason::Disconnected),
+            calc.calc_path_or_reason(&fast_graph, 1, 0)
+        );
+        assert_eq!(Some(1), calc.calc_weight(&fast_graph, 2, 3));
+
+        calc.set_max_settled(num_settled);
+        assert_eq!(
+            5,
+            calc.calc_path_or_reason(&fast_graph, 0, 5)
+                .unwrap()
+                .get_weight()
+        );
+        calc.set_max_settled(usize::MAX);
+        assert_eq!(Some(5), calc.calc_weight(&fast_graph, 0, 5));
+    }
+
+    #[test]
+    fn edge_usage_counts() {
+        // 0 -> 1 -> 2 -> 3
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 3);
+        g.add_edge(1, 2, 4);
+        g.add_edge(2, 3, 1);
+        g.freeze();
+        let fast_graph = prepare_with_order(&g, &[1, 2, 0, 3]).unwrap();
+        let mut calc = PathCalculator::new(4);
+        let pairs = [(0, 3), (1, 3), (1, 2), (2, 2), (3, 0)];
+        let counts = calc.calc_edge_usage_counts(&fast_graph, &pairs);
+        assert_eq!(fast_graph.get_num_edges(), counts.len());
+        let count = |from: NodeId, to: NodeId| {
+            let num_out_edges = fast_graph.get_num_out_edges();
+            let fwd = (0..num_out_edges).find(|&e| {
+                let edge = &fast_graph.edges_fwd[e];
+                !edge.is_shortcut() && (edge.base_node, edge.adj_node) == (from, to)
+            });
+            let bwd = (0..fast_graph.get_num_in_edges()).find(|&e| {
+                let edge = &fast_graph.edges_bwd[e];
+                !edge.is_shortcut() && (edge.adj_node, edge.base_node) == (from, to)
+            });
+            fwd.map_or_else(|| counts[num_out_edges + bwd.unwrap()], |e| counts[e])
+        };
+        assert_eq!(1, count(0, 1));
+        assert_eq!(3, count(1, 2));
+        assert_eq!(2, count(2, 3));
+        // the two shortcuts are never counted
+        assert_eq!(6, counts.iter().sum::<usize>());
+    }
+
+    #[test]
+    fn cancel() {
+        // 0 -> 1 -> 2
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 3);
+        g.add_edge(1, 2, 4);
+        g.freeze();
+        let fast_graph = prepare_with_order(&g, &[1, 0, 2]).unwrap();
+        let mut calc = PathCalculator::new(3);
+        let cancel = AtomicBool::new(false);
+        let path = calc.calc_path_cancellable(&fast_graph, 0, 2, &cancel);
+        assert_eq!(&vec![0, 1, 2], path.unwrap().unwrap().get_nodes());
+        let pairs = [(0, 2), (2, 0), (1, 1)];
+        assert_eq!(
+            Ok(vec![Some(7), None, Some(0)]),
+            calc.calc_weights_batch_cancellable(&fast_graph, &pairs, &cancel)
+        );
+
+        cancel.store(true, Ordering::Relaxed);
+        assert_eq!(
+            Err(Cancelled),
+            calc.calc_path_cancellable(&fast_graph, 0, 2, &cancel)
+        );
+        assert_eq!(
+            Err(Cancelled),
+            calc.calc_weights_batch_cancellable(&fast_graph, &pairs, &cancel)
+        );
+        let result = calc.calc_path_cancellable(&fast_graph, 0, 2, &cancel);
+        assert_eq!(
+            Err(NoPathReason::Cancelled),
+            result.map_err(NoPathReason::from)
+        );
+        // the calculator can still be used after cancelling
+        let path = calc.calc_path(&fast_graph, 0, 2).unwrap();
+        assert_eq!(&vec![0, 1, 2], path.get_nodes());
+        assert_eq!(7, path.get_weight());
+    }
+
+    #[test]
+    fn with_capacity() {
+        let calc = PathCalculator::with_capacity(5, 100);
+        assert!(calc.heap_fwd.capacity() >= 100);
+        assert!(calc.heap_bwd.capacity() >= 100);
+        assert_eq!(5, calc.data_fwd.len());
+        assert_eq!(0, PathCalculator::new(5).heap_fwd.capacity());
+    }
+
+    #[test]
+    fn forward_settled_nodes() {
+        // 0 -> 1 -> 2 -> 3
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 3);
+        g.add_edge(1, 2, 4);
+        g.add_edge(2, 3, 1);
+        g.freeze();
+        // contracting nodes 1 and 2 first yields the shortcut 0->3
+        let fast_graph = prepare_with_order(&g, &[1, 2, 0, 3]).unwrap();
+        let mut calc = PathCalculator::new(4);
+        calc.calc_path(&fast_graph, 0, 3).unwrap();
+        let settled: Vec<_> = calc.forward_settled_nodes().collect();
+        assert_eq!(vec![(0, 0), (3, 8)], settled);
+        // the nodes of the previous query are no longer included
+        calc.calc_path(&fast_graph, 1, 3).unwrap();
+        let settled: Vec<_> = calc.forward_settled_nodes().collect();
+        assert_eq!(vec![(1, 0), (2, 4), (3, 5)], settled);
+    }
 }
@@ -0,0 +1,50 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied.  See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+/// Hints the CPU to load the given element of the slice into the cache, so it is (hopefully)
+/// available once it is actually needed. This is a no-op unless the target architecture supports
+/// it. Indices that are out of bounds are ignored. The call sites are only compiled with the
+/// `prefetch` feature, so without it the query loops do not even read the next edge.
+#[inline(always)]
+#[allow(unused_variables)]
+pub(crate) fn prefetch<T>(slice: &[T], index: usize) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        if index < slice.len() {
+            // safety: the pointer is within the slice and prefetching never has side effects
+            unsafe { _mm_prefetch::<_MM_HINT_T0>(slice.as_ptr().add(index) as *const i8) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefetch_does_not_change_anything() {
+        let values = vec![1, 2, 3];
+        prefetch(&values, 0);
+        prefetch(&values, 2);
+        prefetch(&values, 3);
+        prefetch(&Vec::<usize>::new(), 0);
+        assert_eq!(vec![1, 2, 3], values);
+    }
+}
@@ -17,10 +17,15 @@
  * under the License.
  */
 
+use std::mem::size_of;
+
 use crate::constants::Weight;
 use crate::constants::{NodeId, INVALID_NODE};
 use crate::input_graph::InputGraph;
 
+/// The adjacency list graph the node contraction is working on, which is also used by `Dijkstra`,
+/// `AStar` and `Alt`. Unlike `InputGraph` it does not check or clean up the edges that are added,
+/// so it should usually be created using `from_input_graph()`.
 pub struct PreparationGraph {
     pub out_edges: Vec<Vec<Arc>>,
     pub in_edges: Vec<Vec<Arc>>,
@@ -38,16 +43,86 @@ impl PreparationGraph {
         }
     }
 
+    /// Like `new()`, but reserves space for `avg_degree` edges in each of the adjacency lists
+    pub fn with_capacity(num_nodes: usize, avg_degree: usize) -> Self {
+        PreparationGraph {
+            out_edges: (0..num_nodes)
+                .map(|_| Vec::with_capacity(avg_degree))
+                .collect(),
+            in_edges: (0..num_nodes)
+                .map(|_| Vec::with_capacity(avg_degree))
+                .collect(),
+            num_nodes,
+        }
+    }
+
+    /// Creates a graph containing all edges of the given (frozen) input graph
     pub fn from_input_graph(input_graph: &InputGraph) -> Self {
-        let mut graph = PreparationGraph::new(input_graph.get_num_nodes());
+        let num_nodes = input_graph.get_num_nodes();
+        // rounded up, and zero for empty graphs
+        let avg_degree = (input_graph.get_num_edges() + num_nodes)
+            .saturating_sub(1)
+            .checked_div(num_nodes)
+            .unwrap_or(0);
+        let mut graph = PreparationGraph::with_capacity(num_nodes, avg_degree);
         for e in input_graph.get_edges() {
-            graph.add_edge(e.from, e.to, e.weight);
+            graph.add_edge_with_payload(e.from, e.to, e.weight, e.payload);
         }
         graph
     }
 
     pub fn add_edge(&mut self, from: NodeId, to: NodeId, weight: Weight) {
-        self.add_edge_or_shortcut(from, to, weight, INVALID_NODE);
+        self.add_edge_with_payload(from, to, weight, 0);
+    }
+
+    /// Adds an edge in both directions, i.e. from `from` to `to` and from `to` to `from`, which is
+    /// what undirected graphs need
+    pub fn add_bidirectional_edge(&mut self, from: NodeId, to: NodeId, weight: Weight) {
+        self.add_edge(from, to, weight);
+        self.add_edge(to, from, weight);
+    }
+
+    /// Adds an edge with the given payload. Like for `InputGraph` loops (edges from a node to
+    /// itself) are skipped, because they are never part of a shortest path.
+    pub fn add_edge_with_payload(
+        &mut self,
+        from: NodeId,
+        to: NodeId,
+        weight: Weight,
+        payload: u64,
+    ) {
+        if from == to {
+            return;
+        }
+        self.do_add_edge(from, to, weight, INVALID_NODE, payload);
+    }
+
+    /// Adds all the given (from, to, weight) edges like `add_edge()`, but first reserves the exact
+    /// space needed in each adjacency list, which is faster than adding many edges one by one.
+    pub fn add_edges(&mut self, edges: &[(NodeId, NodeId, Weight)]) {
+        let mut num_out_edges = vec![0; self.num_nodes];
+        let mut num_in_edges = vec![0; self.num_nodes];
+        for &(from, to, _) in edges {
+            if from != to {
+                self.assert_valid_node_id(from);
+                self.assert_valid_node_id(to);
+                num_out_edges[from] += 1;
+                num_in_edges[to] += 1;
+            }
+        }
+        for (node, (&num_out, &num_in)) in num_out_edges.iter().zip(&num_in_edges).enumerate() {
+            self.out_edges[node].reserve_exact(num_out);
+            self.in_edges[node].reserve_exact(num_in);
+        }
+        for &(from, to, weight) in edges {
+            self.add_edge(from, to, weight);
+        }
+    }
+
+    /// Like `add_edges()`, but adds each edge in both directions, see `add_bidirectional_edge()`
+    pub fn add_bidirectional_edges(&mut self, edges: &[(NodeId, NodeId, Weight)]) {
+        let reversed: Vec<_> = edges.iter().map(|&(from, to, w)| (to, from, w)).collect();
+        self.add_edges(&[edges, &reversed].concat());
     }
 
     pub fn add_edge_or_shortcut(
@@ -56,10 +131,21 @@ impl PreparationGraph {
         to: NodeId,
         weight: Weight,
         center_node: NodeId,
+    ) {
+        self.do_add_edge(from, to, weight, center_node, 0);
+    }
+
+    fn do_add_edge(
+        &mut self,
+        from: NodeId,
+        to: NodeId,
+        weight: Weight,
+        center_node: NodeId,
+        payload: u64,
     ) {
         self.assert_valid_node_id(to);
-        self.out_edges[from].push(Arc::new(to, weight, center_node));
-        self.in_edges[to].push(Arc::new(from, weight, center_node));
+        self.out_edges[from].push(Arc::new(to, weight, center_node, payload));
+        self.in_edges[to].push(Arc::new(from, weight, center_node, payload));
     }
 
     pub fn add_or_reduce_edge(
@@ -98,13 +184,22 @@ impl PreparationGraph {
                 return true;
             }
         }
-        return false;
+        false
     }
 
     pub fn get_num_nodes(&self) -> usize {
         self.num_nodes
     }
 
+    /// Returns the (approximate) number of bytes of heap and stack memory used by this graph,
+    /// based on the allocated capacities of the adjacency lists.
+    pub fn memory_usage_bytes(&self) -> usize {
+        let lists = self.out_edges.iter().chain(self.in_edges.iter());
+        size_of::<PreparationGraph>()
+            + (self.out_edges.capacity() + self.in_edges.capacity()) * size_of::<Vec<Arc>>()
+            + lists.map(|l| l.capacity()).sum::<usize>() * size_of::<Arc>()
+    }
+
     pub fn disconnect(&mut self, node: NodeId) {
         for i in 0..self.out_edges[node].len() {
             let adj = self.out_edges[node][i].adj_node;
@@ -137,20 +232,55 @@ impl PreparationGraph {
     }
 
     pub fn get_out_edges(&self, node: NodeId) -> &Vec<Arc> {
-        return &self.out_edges[node];
+        &self.out_edges[node]
     }
 
     pub fn get_in_edges(&self, node: NodeId) -> &Vec<Arc> {
-        return &self.in_edges[node];
+        &self.in_edges[node]
+    }
+
+    /// Returns the weight of the edge from `from` to `to`, or the lowest weight if there are
+    /// multiple such edges, or `None` if there is no such edge.
+    pub fn get_edge_weight(&self, from: NodeId, to: NodeId) -> Option<Weight> {
+        self.out_edges[from]
+            .iter()
+            .filter(|e| e.adj_node == to)
+            .map(|e| e.weight)
+            .min()
+    }
+
+    pub fn contains_edge(&self, from: NodeId, to: NodeId) -> bool {
+        self.out_edges[from].iter().any(|e| e.adj_node == to)
+    }
+
+    /// Lists all node pairs that are connected by more than one edge along with the weights of
+    /// these edges, ordered by `from` and `to`. `from_input_graph()` never creates such parallel
+    /// edges, but `add_edge()` does not prevent them.
+    pub fn parallel_edge_report(&self) -> Vec<(NodeId, NodeId, Vec<Weight>)> {
+        let mut result = vec![];
+        for (from, out_edges) in self.out_edges.iter().enumerate() {
+            let mut edges: Vec<(NodeId, Weight)> =
+                out_edges.iter().map(|e| (e.adj_node, e.weight)).collect();
+            edges.sort_by_key(|&(adj_node, _)| adj_node);
+            let mut begin = 0;
+            while begin < edges.len() {
+                let to = edges[begin].0;
+                let end = begin + edges[begin..].iter().take_while(|e| e.0 == to).count();
+                if end - begin > 1 {
+                    result.push((from, to, edges[begin..end].iter().map(|e| e.1).collect()));
+                }
+                begin = end;
+            }
+        }
+        result
     }
 
     fn assert_valid_node_id(&self, node: NodeId) {
         assert!(
             node < self.num_nodes,
-            format!(
-                "invalid node id {}, must be in [0, {}[",
-                node, self.num_nodes
-            )
+            "invalid node id {}, must be in [0, {}[",
+            node,
+            self.num_nodes
         );
     }
 }
@@ -160,14 +290,16 @@ pub struct Arc {
     pub adj_node: NodeId,
     pub weight: Weight,
     pub center_node: NodeId,
+    pub payload: u64,
 }
 
 impl Arc {
-    pub fn new(adj_node: NodeId, weight: Weight, center_node: NodeId) -> Self {
+    pub fn new(adj_node: NodeId, weight: Weight, center_node: NodeId, payload: u64) -> Self {
         Arc {
             adj_node,
             weight,
             center_node,
+            payload,
         }
     }
 }
@@ -195,6 +327,73 @@ mod tests {
         assert_eq!(adj_nodes(g.get_in_edges(3)), vec![2]);
     }
 
+    #[test]
+    fn with_capacity() {
+        let g = PreparationGraph::with_capacity(3, 4);
+        assert_eq!(3, g.get_num_nodes());
+        assert!((0..3).all(|n| g.get_out_edges(n).capacity() == 4));
+        assert!((0..3).all(|n| g.get_in_edges(n).capacity() == 4));
+    }
+
+    #[test]
+    fn add_edges() {
+        let mut g = PreparationGraph::new(4);
+        g.add_edge(0, 3, 2);
+        g.add_edges(&[(0, 1, 1), (0, 2, 3), (2, 2, 5), (2, 3, 1)]);
+        assert_eq!(adj_nodes(g.get_out_edges(0)), vec![3, 1, 2]);
+        assert_eq!(adj_nodes(g.get_in_edges(3)), vec![0, 2]);
+        assert_eq!(adj_nodes(g.get_out_edges(2)), vec![3]);
+        assert_eq!(Some(3), g.get_edge_weight(0, 2));
+        assert_eq!(3, g.get_out_edges(0).capacity());
+    }
+
+    #[test]
+    fn edge_lookup() {
+        let mut g = PreparationGraph::new(3);
+        g.add_edge(0, 1, 5);
+        g.add_edge(0, 1, 3);
+        g.add_edge(1, 2, 4);
+        assert_eq!(Some(3), g.get_edge_weight(0, 1));
+        assert_eq!(Some(4), g.get_edge_weight(1, 2));
+        assert_eq!(None, g.get_edge_weight(2, 1));
+        assert!(g.contains_edge(0, 1));
+        assert!(!g.contains_edge(1, 0));
+        assert!(!g.contains_edge(0, 2));
+    }
+
+    #[test]
+    fn skips_loops() {
+        let mut g = PreparationGraph::new(2);
+        g.add_edge(0, 1, 3);
+        g.add_edge(1, 1, 2);
+        g.add_bidirectional_edge(0, 0, 1);
+        assert_eq!(vec![1], adj_nodes(g.get_out_edges(0)));
+        assert!(g.get_in_edges(0).is_empty());
+        assert!(g.get_out_edges(1).is_empty());
+        assert_eq!(vec![0], adj_nodes(g.get_in_edges(1)));
+    }
+
+    #[test]
+    fn add_bidirectional_edge() {
+        // 0 <-> 1 <-> 2
+        let mut g = PreparationGraph::new(3);
+        g.add_bidirectional_edge(0, 1, 3);
+        g.add_bidirectional_edge(1, 2, 5);
+        assert_eq!(vec![0, 2], adj_nodes(g.get_out_edges(1)));
+        assert_eq!(vec![0, 2], adj_nodes(g.get_in_edges(1)));
+        assert_eq!(vec![1], adj_nodes(g.get_out_edges(2)));
+        assert_eq!(vec![1], adj_nodes(g.get_in_edges(0)));
+        assert_eq!(5, g.get_out_edges(2)[0].weight);
+
+        let mut h = PreparationGraph::new(3);
+        h.add_bidirectional_edges(&[(0, 1, 3), (1, 2, 5)]);
+        for node in 0..3 {
+            for (from, to) in [(node, (node + 1) % 3), ((node + 1) % 3, node)] {
+                assert_eq!(g.get_edge_weight(from, to), h.get_edge_weight(from, to));
+            }
+        }
+    }
+
     #[test]
     fn add_or_remove_edge() {
         // 0 -> 1
@@ -226,7 +425,43 @@ mod tests {
         assert_eq!(0, adj_nodes(g.get_in_edges(2)).len());
     }
 
-    fn adj_nodes(edges: &Vec<Arc>) -> Vec<NodeId> {
+    #[test]
+    fn memory_usage() {
+        let mut g = PreparationGraph::new(2);
+        let before = g.memory_usage_bytes();
+        assert!(before >= size_of::<PreparationGraph>() + 4 * size_of::<Vec<Arc>>());
+        for _ in 0..10 {
+            g.add_edge(0, 1, 3);
+        }
+        assert!(g.memory_usage_bytes() >= before + 2 * 7 * size_of::<Arc>());
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_input_graph_requires_frozen_graph() {
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 3);
+        PreparationGraph::from_input_graph(&g);
+    }
+
+    #[test]
+    fn parallel_edge_report() {
+        let mut g = PreparationGraph::new(4);
+        g.add_edge(0, 1, 5);
+        g.add_edge(0, 2, 1);
+        g.add_edge(0, 1, 3);
+        g.add_edge(2, 3, 1);
+        g.add_edge(3, 2, 1);
+        g.add_edge(2, 3, 2);
+        g.add_edge(2, 3, 2);
+        assert_eq!(
+            vec![(0, 1, vec![5, 3]), (2, 3, vec![1, 2, 2])],
+            g.parallel_edge_report()
+        );
+        assert!(PreparationGraph::new(3).parallel_edge_report().is_empty());
+    }
+
+    fn adj_nodes(edges: &[Arc]) -> Vec<NodeId> {
         edges.iter().map(|e| e.adj_node).collect::<Vec<NodeId>>()
     }
 }
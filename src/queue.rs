@@ -0,0 +1,160 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied.  See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use std::collections::{BinaryHeap, VecDeque};
+
+use crate::constants::{NodeId, Weight};
+use crate::heap_item::HeapItem;
+
+/// The priority queue used by `PathCalculator` and `Dijkstra`. `pop()` must return an item with
+/// the smallest weight among all items in the queue.
+pub trait Queue {
+    fn with_capacity(capacity: usize) -> Self;
+    fn push(&mut self, item: HeapItem);
+    fn pop(&mut self) -> Option<HeapItem>;
+    fn is_empty(&self) -> bool;
+    fn clear(&mut self);
+}
+
+/// The default queue, a binary heap from the standard library
+pub type BinaryHeapQueue = BinaryHeap<HeapItem>;
+
+impl Queue for BinaryHeapQueue {
+    fn with_capacity(capacity: usize) -> Self {
+        BinaryHeap::with_capacity(capacity)
+    }
+
+    fn push(&mut self, item: HeapItem) {
+        BinaryHeap::push(self, item)
+    }
+
+    fn pop(&mut self) -> Option<HeapItem> {
+        BinaryHeap::pop(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        BinaryHeap::is_empty(self)
+    }
+
+    fn clear(&mut self) {
+        BinaryHeap::clear(self)
+    }
+}
+
+/// A bucket queue as used by Dial's algorithm, which keeps one bucket per weight. This only works
+/// for monotone searches, i.e. no item can be pushed with a smaller weight than the last popped
+/// one, which is the case for all searches with non-negative weights. Pushing and popping takes
+/// constant time, but the number of buckets grows with the weight difference between the last
+/// popped and the heaviest pushed item, so this is only useful for small integer weights.
+pub struct DialQueue {
+    buckets: VecDeque<Vec<NodeId>>,
+    /// The weight of the first bucket
+    base_weight: Weight,
+    len: usize,
+}
+
+impl Queue for DialQueue {
+    fn with_capacity(capacity: usize) -> Self {
+        DialQueue {
+            buckets: VecDeque::with_capacity(capacity),
+            base_weight: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, item: HeapItem) {
+        assert!(
+            item.weight >= self.base_weight,
+            "cannot push weight {} after popping weight {}",
+            item.weight,
+            self.base_weight
+        );
+        let index = item.weight - self.base_weight;
+        if index >= self.buckets.len() {
+            self.buckets.resize_with(index + 1, Vec::new);
+        }
+        self.buckets[index].push(item.node_id);
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<HeapItem> {
+        if self.len == 0 {
+            return None;
+        }
+        while self.buckets[0].is_empty() {
+            // move the empty bucket to the end so we keep its memory
+            self.buckets.rotate_left(1);
+            self.base_weight += 1;
+        }
+        self.len -= 1;
+        let node = self.buckets[0].pop().unwrap();
+        Some(HeapItem::new(self.base_weight, node))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn clear(&mut self) {
+        self.buckets.iter_mut().for_each(|b| b.clear());
+        self.base_weight = 0;
+        self.len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dial_queue() {
+        let mut queue = DialQueue::with_capacity(0);
+        assert!(queue.is_empty());
+        queue.push(HeapItem::new(5, 1));
+        queue.push(HeapItem::new(2, 2));
+        queue.push(HeapItem::new(7, 3));
+        assert_eq!(Some((2, 2)), pop(&mut queue));
+        queue.push(HeapItem::new(2, 4));
+        queue.push(HeapItem::new(3, 5));
+        assert_eq!(Some((2, 4)), pop(&mut queue));
+        assert_eq!(Some((3, 5)), pop(&mut queue));
+        assert_eq!(Some((5, 1)), pop(&mut queue));
+        assert_eq!(Some((7, 3)), pop(&mut queue));
+        assert_eq!(None, pop(&mut queue));
+        assert!(queue.is_empty());
+        queue.push(HeapItem::new(9, 6));
+        queue.clear();
+        assert!(queue.is_empty());
+        queue.push(HeapItem::new(0, 7));
+        assert_eq!(Some((0, 7)), pop(&mut queue));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot push weight 1 after popping weight 3")]
+    fn dial_queue_not_monotone() {
+        let mut queue = DialQueue::with_capacity(0);
+        queue.push(HeapItem::new(3, 0));
+        queue.pop();
+        queue.push(HeapItem::new(1, 1));
+    }
+
+    fn pop<Q: Queue>(queue: &mut Q) -> Option<(Weight, NodeId)> {
+        queue.pop().map(|item| (item.weight, item.node_id))
+    }
+}
@@ -0,0 +1,70 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied.  See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use crate::constants::{EdgeId, NodeId, Weight};
+
+/// A directed graph in the compressed sparse row format. Unlike in `FastGraph` the first edge ids
+/// are indexed by the node ids, see `FastGraph::upward_graph()` and
+/// `FastGraph::downward_graph()`.
+#[derive(Debug, PartialEq)]
+pub struct SearchGraph {
+    pub first_edge_ids: Vec<EdgeId>,
+    pub adj_nodes: Vec<NodeId>,
+    pub weights: Vec<Weight>,
+}
+
+impl SearchGraph {
+    /// Creates a graph with the given edges, which can be given in any order
+    pub(crate) fn from_edges<I>(num_nodes: usize, edges: I) -> Self
+    where
+        I: Iterator<Item = (NodeId, NodeId, Weight)>,
+    {
+        let mut edges: Vec<(NodeId, NodeId, Weight)> = edges.collect();
+        edges.sort_by_key(|&(from, _, _)| from);
+        let mut first_edge_ids = vec![0; num_nodes + 1];
+        for &(from, _, _) in &edges {
+            first_edge_ids[from + 1] += 1;
+        }
+        for node in 0..num_nodes {
+            first_edge_ids[node + 1] += first_edge_ids[node];
+        }
+        SearchGraph {
+            first_edge_ids,
+            adj_nodes: edges.iter().map(|&(_, to, _)| to).collect(),
+            weights: edges.iter().map(|&(_, _, weight)| weight).collect(),
+        }
+    }
+
+    pub fn get_num_nodes(&self) -> usize {
+        self.first_edge_ids.len() - 1
+    }
+
+    pub fn get_num_edges(&self) -> usize {
+        self.adj_nodes.len()
+    }
+
+    /// Returns the adjacent nodes and weights of the edges leaving the given node
+    pub fn get_out_edges(&self, node: NodeId) -> impl Iterator<Item = (NodeId, Weight)> + '_ {
+        let edges = self.first_edge_ids[node]..self.first_edge_ids[node + 1];
+        self.adj_nodes[edges.clone()]
+            .iter()
+            .copied()
+            .zip(self.weights[edges].iter().copied())
+    }
+}
@@ -0,0 +1,118 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied.  See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::fast_graph::FastGraph;
+use crate::path_calculator::PathCalculator;
+
+/// The number of nodes settled by a sample of random queries, see
+/// `benchmark_query_search_space()`
+#[derive(Debug, PartialEq)]
+pub struct SearchSpaceReport {
+    pub num_samples: usize,
+    pub avg_settled: f64,
+    pub p95_settled: usize,
+    pub max_settled: usize,
+}
+
+/// Runs `num_samples` queries between random nodes and reports how many nodes were settled by
+/// the forward and backward searches. Unlike the query time this does not depend on the hardware
+/// or other processes, which makes it useful to compare different node orderings or preparation
+/// parameters. The same `seed` always yields the same queries for the same graph.
+pub fn benchmark_query_search_space(
+    graph: &FastGraph,
+    calc: &mut PathCalculator,
+    num_samples: usize,
+    seed: u64,
+) -> SearchSpaceReport {
+    let num_nodes = graph.get_num_nodes();
+    let mut settled = Vec::with_capacity(num_samples);
+    if num_nodes > 0 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        for _ in 0..num_samples {
+            let start = rng.gen_range(0, num_nodes);
+            let end = rng.gen_range(0, num_nodes);
+            calc.calc_weight(graph, start, end);
+            settled.push(calc.get_num_settled());
+        }
+    }
+    if settled.is_empty() {
+        return SearchSpaceReport {
+            num_samples: 0,
+            avg_settled: 0.0,
+            p95_settled: 0,
+            max_settled: 0,
+        };
+    }
+    settled.sort_unstable();
+    let p95_index = (settled.len() * 95 + 99) / 100 - 1;
+    SearchSpaceReport {
+        num_samples: settled.len(),
+        avg_settled: settled.iter().sum::<usize>() as f64 / settled.len() as f64,
+        p95_settled: settled[p95_index],
+        max_settled: settled[settled.len() - 1],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input_graph::InputGraph;
+    use crate::prepare;
+    use crate::test_utils::random_input_graph;
+
+    use super::*;
+
+    #[test]
+    fn search_space_chain() {
+        // 0 -> 1 -> 2
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 1);
+        g.add_edge(1, 2, 1);
+        g.freeze();
+        let fast_graph = prepare(&g);
+        let mut calc = PathCalculator::new(3);
+        calc.calc_path(&fast_graph, 0, 0);
+        assert_eq!(0, calc.get_num_settled());
+        calc.calc_path(&fast_graph, 0, 2);
+        assert!(calc.get_num_settled() >= 2);
+
+        let report = benchmark_query_search_space(&fast_graph, &mut calc, 20, 42);
+        assert_eq!(20, report.num_samples);
+        assert!(report.avg_settled <= report.max_settled as f64);
+        assert!(report.p95_settled <= report.max_settled);
+        assert!(report.max_settled <= 6);
+    }
+
+    #[test]
+    fn search_space_reproducible() {
+        let g = random_input_graph(100, 2.0, 10, 3);
+        let fast_graph = prepare(&g);
+        let mut calc = PathCalculator::new(100);
+        let report = benchmark_query_search_space(&fast_graph, &mut calc, 50, 7);
+        assert_eq!(
+            report,
+            benchmark_query_search_space(&fast_graph, &mut calc, 50, 7)
+        );
+        assert!(report.avg_settled > 0.0);
+        let empty = benchmark_query_search_space(&fast_graph, &mut calc, 0, 7);
+        assert_eq!(0, empty.num_samples);
+    }
+}
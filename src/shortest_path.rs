@@ -17,12 +17,18 @@
  * under the License.
  */
 
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::constants::add_weights;
 use crate::constants::NodeId;
 use crate::constants::Weight;
 use crate::constants::WEIGHT_MAX;
 use crate::constants::WEIGHT_ZERO;
+use crate::fast_graph::FastGraph;
+use crate::path_calculator::Cancelled;
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ShortestPath {
     source: NodeId,
     target: NodeId,
@@ -84,4 +90,284 @@ impl ShortestPath {
     pub fn is_found(&self) -> bool {
         self.weight != WEIGHT_MAX
     }
+
+    /// Returns the number of (base) edges of this path, which is zero for singular paths and
+    /// paths that were not found.
+    pub fn num_edges(&self) -> usize {
+        self.nodes.len().saturating_sub(1)
+    }
+
+    /// Appends the given path, which must start where this path ends, such that the node in
+    /// between is not duplicated and the weights are added up.
+    pub(crate) fn join(mut self, other: ShortestPath) -> ShortestPath {
+        assert_eq!(self.target, other.source, "paths must be adjacent");
+        self.nodes.extend(other.nodes.into_iter().skip(1));
+        self.target = other.target;
+        self.weight = add_weights(self.weight, other.weight);
+        self
+    }
+
+    /// Returns true if both paths have the same weight and the same nodes, either in the same or
+    /// in reverse order. Unlike `==` this compares the nodes, but ignores the direction of the
+    /// paths, which is useful for undirected graphs.
+    pub fn equivalent_undirected(&self, other: &ShortestPath) -> bool {
+        let same_ends = (self.source, self.target) == (other.source, other.target)
+            || (self.source, self.target) == (other.target, other.source);
+        same_ends
+            && self.weight == other.weight
+            && (self.nodes == other.nodes || self.nodes.iter().eq(other.nodes.iter().rev()))
+    }
+
+    /// Returns the nodes of this path, but skips all nodes that are connected to exactly two
+    /// other nodes, because the path cannot branch off at these nodes anyway. The source and
+    /// target nodes are always kept. `num_neighbors` holds the number of neighbors of each node,
+    /// see `FastGraph::calc_num_base_neighbors()`.
+    pub fn simplify(&self, num_neighbors: &[usize]) -> Vec<NodeId> {
+        let last = self.nodes.len().saturating_sub(1);
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|&(i, &node)| i == 0 || i == last || num_neighbors[node] != 2)
+            .map(|(_, &node)| node)
+            .collect()
+    }
+
+    /// Returns the weight of each edge of this path, so the result has one element less than
+    /// `get_nodes()` and its sum is the weight of the path. `graph` must be the graph this path
+    /// was calculated on. There are no parallel edges in a `FastGraph`, because only the lightest
+    /// of them is kept when the `InputGraph` is frozen, so the weights are unambiguous.
+    pub fn segment_weights(&self, graph: &FastGraph) -> Vec<Weight> {
+        self.nodes
+            .windows(2)
+            .map(|w| {
+                graph
+                    .get_base_edge_weight(w[0], w[1])
+                    .expect("the path does not belong to this graph")
+            })
+            .collect()
+    }
+
+    /// Returns each node of this path along with the weight of the path from the source to this
+    /// node, so the first entry is `(source, 0)` and the last is `(target, get_weight())`. Returns
+    /// an empty vector if the path was not found. `graph` must be the graph this path was
+    /// calculated on, see `segment_weights()`.
+    pub fn cumulative_weights(&self, graph: &FastGraph) -> Vec<(NodeId, Weight)> {
+        let mut weight = WEIGHT_ZERO;
+        let mut result = Vec::with_capacity(self.nodes.len());
+        result.extend(self.nodes.first().map(|&node| (node, weight)));
+        for (&node, segment_weight) in self.nodes.iter().skip(1).zip(self.segment_weights(graph)) {
+            weight = add_weights(weight, segment_weight);
+            result.push((node, weight));
+        }
+        result
+    }
+
+    /// Splits this path into segments between the nodes returned by `simplify()`, so each
+    /// segment is a run of nodes where the path cannot branch off and is fully described by its
+    /// first and last node and its weight. `graph` must be the graph this path was calculated on.
+    pub fn run_length_segments(&self, graph: &FastGraph, num_neighbors: &[usize]) -> Vec<Segment> {
+        let mut result = vec![];
+        let segment_weights = self.segment_weights(graph);
+        let last = self.nodes.len().saturating_sub(1);
+        let mut start = 0;
+        let mut weight = 0;
+        for i in 1..self.nodes.len() {
+            weight = add_weights(weight, segment_weights[i - 1]);
+            if i == last || num_neighbors[self.nodes[i]] != 2 {
+                result.push(Segment {
+                    start: self.nodes[start],
+                    end: self.nodes[i],
+                    weight,
+                });
+                start = i;
+                weight = 0;
+            }
+        }
+        result
+    }
+}
+
+/// The reason why no shortest path was returned
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum NoPathReason {
+    /// There is no path between the two nodes
+    Disconnected,
+    /// The search stopped at the maximum weight before the target was reached, so there might be
+    /// a path with a larger weight
+    ExceededMaxWeight,
+    /// The calculation was cancelled, see `PathCalculator::calc_path_cancellable()`
+    Cancelled,
+    /// The search settled more nodes than allowed before the shortest path was found, see
+    /// `PathCalculator::set_max_settled()`
+    ExceededMaxSettled,
+}
+
+impl From<Cancelled> for NoPathReason {
+    fn from(_: Cancelled) -> Self {
+        NoPathReason::Cancelled
+    }
+}
+
+/// A part of a path between two nodes where the path cannot branch off, see
+/// `ShortestPath::run_length_segments()`
+#[derive(Debug, PartialEq)]
+pub struct Segment {
+    pub start: NodeId,
+    pub end: NodeId,
+    pub weight: Weight,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input_graph::InputGraph;
+    use crate::{calc_path, prepare};
+
+    use super::*;
+
+    #[test]
+    fn serialize_and_deserialize() {
+        let path = ShortestPath::new(2, 7, 13, vec![2, 5, 4, 7]);
+        let bytes = bincode::serialize(&path).unwrap();
+        let restored: ShortestPath = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(2, restored.get_source());
+        assert_eq!(7, restored.get_target());
+        assert_eq!(13, restored.get_weight());
+        assert_eq!(&vec![2, 5, 4, 7], restored.get_nodes());
+    }
+
+    #[test]
+    fn join() {
+        let first = ShortestPath::new(2, 5, 3, vec![2, 4, 5]);
+        let second = ShortestPath::new(5, 7, 4, vec![5, 7]);
+        assert_eq!(
+            ShortestPath::new(2, 7, 7, vec![2, 4, 5, 7]),
+            first.join(second)
+        );
+        // the weights saturate like for the searches
+        let first = ShortestPath::new(2, 5, WEIGHT_MAX - 1, vec![2, 5]);
+        let second = ShortestPath::new(5, 7, 4, vec![5, 7]);
+        assert_eq!(WEIGHT_MAX, first.join(second).get_weight());
+    }
+
+    #[test]
+    fn num_edges() {
+        assert_eq!(3, ShortestPath::new(0, 3, 5, vec![0, 1, 2, 3]).num_edges());
+        assert_eq!(1, ShortestPath::new(0, 1, 5, vec![0, 1]).num_edges());
+        assert_eq!(0, ShortestPath::singular(4).num_edges());
+        assert_eq!(0, ShortestPath::none(1, 2).num_edges());
+    }
+
+    #[test]
+    fn equivalent_undirected() {
+        let path = ShortestPath::new(0, 3, 5, vec![0, 1, 2, 3]);
+        let reversed = ShortestPath::new(3, 0, 5, vec![3, 2, 1, 0]);
+        assert_ne!(path, reversed);
+        assert!(path.equivalent_undirected(&reversed));
+        assert!(reversed.equivalent_undirected(&path));
+        assert!(path.equivalent_undirected(&path));
+        // different weight
+        assert!(!path.equivalent_undirected(&ShortestPath::new(3, 0, 6, vec![3, 2, 1, 0])));
+        // different nodes
+        assert!(!path.equivalent_undirected(&ShortestPath::new(3, 0, 5, vec![3, 4, 1, 0])));
+        assert!(!path.equivalent_undirected(&ShortestPath::new(0, 3, 5, vec![0, 2, 1, 3])));
+        assert!(ShortestPath::none(1, 2).equivalent_undirected(&ShortestPath::none(2, 1)));
+        assert!(!ShortestPath::none(1, 2).equivalent_undirected(&ShortestPath::none(1, 3)));
+    }
+
+    #[test]
+    fn segment_weights() {
+        // 0 -> 1 -> 2 -> 3, with two parallel edges 0->1
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 5);
+        g.add_edge(0, 1, 3);
+        g.add_edge(1, 2, 2);
+        g.add_edge(2, 3, 4);
+        g.freeze();
+        let fast_graph = prepare(&g);
+        let path = calc_path(&fast_graph, 0, 3).unwrap();
+        let weights = path.segment_weights(&fast_graph);
+        assert_eq!(vec![3, 2, 4], weights);
+        assert_eq!(path.get_weight(), weights.iter().sum());
+        let path = calc_path(&fast_graph, 2, 2).unwrap();
+        assert!(path.segment_weights(&fast_graph).is_empty());
+    }
+
+    #[test]
+    fn cumulative_weights() {
+        // 0 -> 1 -> 2 -> 3, with two parallel edges 1->2
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 5);
+        g.add_edge(1, 2, 7);
+        g.add_edge(1, 2, 2);
+        g.add_edge(2, 3, 4);
+        g.freeze();
+        let fast_graph = prepare(&g);
+        let path = calc_path(&fast_graph, 0, 3).unwrap();
+        let cumulative = path.cumulative_weights(&fast_graph);
+        assert_eq!(vec![(0, 0), (1, 5), (2, 7), (3, 11)], cumulative);
+        assert_eq!(path.get_weight(), cumulative.last().unwrap().1);
+        let path = calc_path(&fast_graph, 2, 2).unwrap();
+        assert_eq!(vec![(2, 0)], path.cumulative_weights(&fast_graph));
+        assert!(ShortestPath::none(3, 0)
+            .cumulative_weights(&fast_graph)
+            .is_empty());
+    }
+
+    #[test]
+    fn run_length_segments() {
+        // 0 - 1 - 2 - 3 - 4 -> 5
+        //         |
+        //         6
+        let mut g = InputGraph::new();
+        g.add_edge_bidir(0, 1, 1);
+        g.add_edge_bidir(1, 2, 2);
+        g.add_edge_bidir(2, 3, 3);
+        g.add_edge_bidir(3, 4, 4);
+        g.add_edge(4, 5, 5);
+        g.add_edge_bidir(2, 6, 6);
+        g.freeze();
+        let fast_graph = prepare(&g);
+        let num_neighbors = fast_graph.calc_num_base_neighbors();
+        let segment = |start, end, weight| Segment { start, end, weight };
+        let path = calc_path(&fast_graph, 0, 5).unwrap();
+        assert_eq!(
+            vec![segment(0, 2, 3), segment(2, 5, 12)],
+            path.run_length_segments(&fast_graph, &num_neighbors)
+        );
+        let path = calc_path(&fast_graph, 6, 0).unwrap();
+        assert_eq!(
+            vec![segment(6, 2, 6), segment(2, 0, 3)],
+            path.run_length_segments(&fast_graph, &num_neighbors)
+        );
+        let path = calc_path(&fast_graph, 3, 3).unwrap();
+        assert!(path
+            .run_length_segments(&fast_graph, &num_neighbors)
+            .is_empty());
+    }
+
+    #[test]
+    fn simplify() {
+        // 0 - 1 - 2 - 3 - 4 -> 5
+        //         |
+        //         6
+        let mut g = InputGraph::new();
+        g.add_edge_bidir(0, 1, 1);
+        g.add_edge_bidir(1, 2, 1);
+        g.add_edge_bidir(2, 3, 1);
+        g.add_edge_bidir(3, 4, 1);
+        g.add_edge(4, 5, 1);
+        g.add_edge_bidir(2, 6, 1);
+        g.freeze();
+        let fast_graph = prepare(&g);
+        let num_neighbors = fast_graph.calc_num_base_neighbors();
+        assert_eq!(vec![1, 2, 3, 2, 2, 1, 1], num_neighbors);
+        let path = calc_path(&fast_graph, 0, 5).unwrap();
+        assert_eq!(&vec![0, 1, 2, 3, 4, 5], path.get_nodes());
+        assert_eq!(vec![0, 2, 5], path.simplify(&num_neighbors));
+        let path = calc_path(&fast_graph, 1, 4).unwrap();
+        assert_eq!(vec![1, 2, 4], path.simplify(&num_neighbors));
+        let path = calc_path(&fast_graph, 3, 3).unwrap();
+        assert_eq!(vec![3], path.simplify(&num_neighbors));
+    }
 }
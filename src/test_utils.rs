@@ -0,0 +1,272 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied.  See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+
+//! Random graph generators that are useful for tests and benchmarks. The generated graphs only
+//! depend on the given seed.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::constants::{NodeId, Weight};
+use crate::dijkstra::Dijkstra;
+use crate::fast_graph::FastGraph;
+use crate::input_graph::InputGraph;
+use crate::path_calculator::PathCalculator;
+use crate::preparation_graph::PreparationGraph;
+
+/// Creates a grid graph, see `random_grid_input_graph`.
+pub fn random_grid_graph(rows: usize, cols: usize, seed: u64) -> PreparationGraph {
+    PreparationGraph::from_input_graph(&random_grid_input_graph(rows, cols, seed))
+}
+
+/// Creates a random graph, see `random_input_graph`.
+pub fn random_graph(
+    num_nodes: usize,
+    avg_degree: f32,
+    max_weight: Weight,
+    seed: u64,
+) -> PreparationGraph {
+    PreparationGraph::from_input_graph(&random_input_graph(num_nodes, avg_degree, max_weight, seed))
+}
+
+/// Creates a (frozen) grid graph with `rows * cols` nodes, where node `row * cols + col` is
+/// connected to its (up to four) horizontal and vertical neighbors. The weights are random
+/// between 1 and 100, and are chosen separately for both directions.
+pub fn random_grid_input_graph(rows: usize, cols: usize, seed: u64) -> InputGraph {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut graph = InputGraph::new();
+    let node = |row: usize, col: usize| -> NodeId { row * cols + col };
+    for row in 0..rows {
+        for col in 0..cols {
+            if col + 1 < cols {
+                graph.add_edge(node(row, col), node(row, col + 1), rng.gen_range(1, 101));
+                graph.add_edge(node(row, col + 1), node(row, col), rng.gen_range(1, 101));
+            }
+            if row + 1 < rows {
+                graph.add_edge(node(row, col), node(row + 1, col), rng.gen_range(1, 101));
+                graph.add_edge(node(row + 1, col), node(row, col), rng.gen_range(1, 101));
+            }
+        }
+    }
+    graph.freeze();
+    graph
+}
+
+/// Creates a (frozen) graph with `num_nodes * avg_degree` random directed edges with weights
+/// between 0 and `max_weight`. There are no loops, but there can be duplicate edges, of which only
+/// the one with the lowest weight is kept. Note that the number of nodes of the graph is smaller
+/// than `num_nodes` if the nodes with the largest ids happen to have no edges.
+pub fn random_input_graph(
+    num_nodes: usize,
+    avg_degree: f32,
+    max_weight: Weight,
+    seed: u64,
+) -> InputGraph {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut graph = InputGraph::new();
+    let num_edges = (num_nodes as f32 * avg_degree) as usize;
+    if num_nodes > 1 {
+        let mut edge_count = 0;
+        while edge_count < num_edges {
+            let from = rng.gen_range(0, num_nodes);
+            let to = rng.gen_range(0, num_nodes);
+            edge_count += graph.add_edge(from, to, rng.gen_range(0, max_weight + 1));
+        }
+    }
+    graph.freeze();
+    graph
+}
+
+/// Compares the weights of `num_queries` random shortest paths calculated on `fast_graph` with
+/// the ones found by Dijkstra's algorithm on the given `input_graph`, which must be the graph that
+/// was used to create `fast_graph`. Returns an error describing the first query that did not agree,
+/// so the failing case can be reproduced using the same seed.
+pub fn check_same_weights_as_dijkstra(
+    input_graph: &InputGraph,
+    fast_graph: &FastGraph,
+    num_queries: usize,
+    seed: u64,
+) -> Result<(), String> {
+    let num_nodes = input_graph.get_num_nodes();
+    if num_nodes != fast_graph.get_num_nodes() {
+        return Err(format!(
+            "the input graph has {} nodes, but the fast graph has {}",
+            num_nodes,
+            fast_graph.get_num_nodes()
+        ));
+    }
+    if num_nodes == 0 {
+        return Ok(());
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let preparation_graph = PreparationGraph::from_input_graph(input_graph);
+    let mut dijkstra = Dijkstra::new(num_nodes);
+    let mut path_calculator = PathCalculator::new(num_nodes);
+    for _ in 0..num_queries {
+        let source = rng.gen_range(0, num_nodes);
+        let target = rng.gen_range(0, num_nodes);
+        let weight_dijkstra = dijkstra
+            .calc_path(&preparation_graph, source, target)
+            .map(|p| p.get_weight());
+        let weight_fast = path_calculator
+            .calc_path(fast_graph, source, target)
+            .map(|p| p.get_weight());
+        if weight_dijkstra != weight_fast {
+            return Err(format!(
+                "No agreement for routing query from: {} to: {} (seed: {})\nDijkstra: {:?}\nCH: {:?}",
+                source, target, seed, weight_dijkstra, weight_fast
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Compares the weights of `num_queries` random shortest paths calculated on the two given
+/// graphs, e.g. to check that changing the preparation does not change the query results. The
+/// graphs can have different node orderings and shortcuts, and the paths can differ as long as
+/// their weights are the same. Returns an error describing the first query that did not agree.
+pub fn check_query_equivalent(
+    a: &FastGraph,
+    b: &FastGraph,
+    num_queries: usize,
+    seed: u64,
+) -> Result<(), String> {
+    let num_nodes = a.get_num_nodes();
+    if num_nodes != b.get_num_nodes() {
+        return Err(format!(
+            "the first graph has {} nodes, but the second has {}",
+            num_nodes,
+            b.get_num_nodes()
+        ));
+    }
+    if num_nodes == 0 {
+        return Ok(());
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut calc_a = PathCalculator::new(num_nodes);
+    let mut calc_b = PathCalculator::new(num_nodes);
+    for _ in 0..num_queries {
+        let source = rng.gen_range(0, num_nodes);
+        let target = rng.gen_range(0, num_nodes);
+        let weight_a = calc_a.calc_weight(a, source, target);
+        let weight_b = calc_b.calc_weight(b, source, target);
+        if weight_a != weight_b {
+            return Err(format!(
+                "No agreement for routing query from: {} to: {} (seed: {})\nfirst: {:?}\nsecond: {:?}",
+                source, target, seed, weight_a, weight_b
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Like `check_query_equivalent()`, but panics if the graphs do not agree
+pub fn assert_query_equivalent(a: &FastGraph, b: &FastGraph, num_queries: usize, seed: u64) {
+    if let Err(message) = check_query_equivalent(a, b, num_queries, seed) {
+        panic!("{}", message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{prepare, prepare_with_order};
+
+    use super::*;
+
+    #[test]
+    fn same_weights_as_dijkstra() {
+        for seed in 0..20 {
+            let g = random_input_graph(50, 2.0, 10, seed);
+            assert_eq!(
+                Ok(()),
+                check_same_weights_as_dijkstra(&g, &prepare(&g), 500, seed)
+            );
+            let g = random_grid_input_graph(6, 7, seed);
+            assert_eq!(
+                Ok(()),
+                check_same_weights_as_dijkstra(&g, &prepare(&g), 500, seed)
+            );
+        }
+    }
+
+    #[test]
+    fn detects_different_weights() {
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 3);
+        g.freeze();
+        let mut other = InputGraph::new();
+        other.add_edge(0, 1, 4);
+        other.freeze();
+        let fast_graph = prepare_with_order(&other, &[0, 1]).unwrap();
+        let result = check_same_weights_as_dijkstra(&g, &fast_graph, 100, 1);
+        assert!(result
+            .unwrap_err()
+            .contains("Dijkstra: Some(3)\nCH: Some(4)"));
+    }
+
+    #[test]
+    fn query_equivalent() {
+        let g = random_input_graph(100, 2.5, 10, 3);
+        let fast_graph = prepare(&g);
+        let mut order = fast_graph.get_node_ordering();
+        order.reverse();
+        let reversed = prepare_with_order(&g, &order).unwrap();
+        assert_query_equivalent(&fast_graph, &reversed, 500, 4);
+        let other = prepare(&random_input_graph(100, 2.5, 10, 5));
+        assert!(check_query_equivalent(&fast_graph, &other, 500, 4)
+            .unwrap_err()
+            .starts_with("No agreement for routing query"));
+    }
+
+    #[test]
+    #[should_panic(expected = "the first graph has 2 nodes, but the second has 3")]
+    fn query_equivalent_different_sizes() {
+        let mut g = InputGraph::new();
+        g.add_edge(0, 1, 3);
+        g.freeze();
+        let mut other = InputGraph::new();
+        other.add_edge(0, 2, 4);
+        other.freeze();
+        assert_query_equivalent(&prepare(&g), &prepare(&other), 10, 1);
+    }
+
+    #[test]
+    fn grid_graph() {
+        let g = random_grid_input_graph(3, 4, 1);
+        assert_eq!(12, g.get_num_nodes());
+        // 3 * 3 horizontal and 2 * 4 vertical connections in both directions
+        assert_eq!(2 * (9 + 8), g.get_num_edges());
+        let p = random_grid_graph(3, 4, 1);
+        assert_eq!(2, p.get_out_edges(0).len());
+        assert_eq!(4, p.get_out_edges(5).len());
+    }
+
+    #[test]
+    fn random_graph_is_reproducible() {
+        let a = random_input_graph(20, 3.0, 10, 42);
+        let b = random_input_graph(20, 3.0, 10, 42);
+        assert_eq!(a.unit_test_output_string(), b.unit_test_output_string());
+        assert!(a
+            .get_edges()
+            .iter()
+            .all(|e| e.weight <= 10 && e.from != e.to));
+        let c = random_input_graph(20, 3.0, 10, 43);
+        assert_ne!(a.unit_test_output_string(), c.unit_test_output_string());
+    }
+}
@@ -17,8 +17,6 @@
  * under the License.
  */
 
-use std::u32::MAX;
-
 use crate::constants::NodeId;
 
 /// Maintains a collection of N boolean flags that can efficiently be reset by incrementing a
@@ -44,8 +42,15 @@ impl ValidFlags {
         self.valid_flags[node] = self.valid_flag;
     }
 
+    /// Changes the number of flags and invalidates all of them, but keeps the allocated memory
+    pub fn reset(&mut self, num_nodes: usize) {
+        self.valid_flags.clear();
+        self.valid_flags.resize(num_nodes, 0);
+        self.valid_flag = 1;
+    }
+
     pub fn invalidate_all(&mut self) {
-        if self.valid_flag == MAX {
+        if self.valid_flag == u32::MAX {
             self.valid_flags = vec![0; self.valid_flags.len()];
             self.valid_flag = 1;
         } else {
@@ -67,4 +72,14 @@ mod tests {
         flags.invalidate_all();
         assert!(!flags.is_valid(3));
     }
+
+    #[test]
+    fn reset() {
+        let mut flags = ValidFlags::new(5);
+        flags.set_valid(3);
+        flags.reset(8);
+        assert!((0..8).all(|node| !flags.is_valid(node)));
+        flags.set_valid(7);
+        assert!(flags.is_valid(7));
+    }
 }